@@ -2,9 +2,10 @@ use clap::{Parser, Subcommand};
 use anyhow::Result;
 use core::{
     config::ConfigManager,
-    GameSaveSync, GameConfig,
-    watcher::WatcherManager,
+    GameSaveSync, GameConfig, ConflictPolicy, ConflictResolution, SaveFilter, WatchBackend, WatchSettings,
+    watcher::{WatchState, WatcherManager},
     daemon::DaemonService,
+    ipc::DaemonClient,
 };
 use std::collections::HashMap;
 use tracing::{info, error, warn};
@@ -37,6 +38,27 @@ enum Command {
         /// Path to the save file or directory
         #[arg(short, long)]
         path: Vec<String>,
+        /// Only sync files matching these globs (relative to the save path)
+        #[arg(long)]
+        include: Vec<String>,
+        /// Skip files matching these globs, e.g. "*.log" or "ShaderCache"
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// Skip files larger than this many bytes
+        #[arg(long)]
+        max_file_size: Option<u64>,
+        /// How changes are noticed: auto, native or polling
+        #[arg(long, default_value = "auto")]
+        watch_backend: WatchBackend,
+        /// Milliseconds between polls with the polling backend (default 5000)
+        #[arg(long)]
+        poll_interval_ms: Option<u64>,
+        /// Milliseconds without changes before the watcher syncs (default 500)
+        #[arg(long)]
+        debounce_ms: Option<u64>,
+        /// Polls a file held open by the game must stay unchanged before it is synced (default 3)
+        #[arg(long)]
+        settle_polls: Option<u32>,
     },
     /// List all configured games
     List,
@@ -44,8 +66,42 @@ enum Command {
     Config,
     /// Initialize configuration with default values
     Init,
+    /// List saves that changed both locally and in the cloud
+    Conflicts,
+    /// Resolve a pending conflict
+    Resolve {
+        /// Name of the game
+        game: String,
+        /// Local path of the conflicting save file
+        file: String,
+        /// newest, local, remote or both
+        resolution: ConflictResolution,
+    },
+    /// Set how conflicts are handled for a game
+    SetConflictPolicy {
+        /// Name of the game
+        game: String,
+        /// newest, local, remote, both or ask
+        policy: ConflictPolicy,
+    },
+    /// Set the end-to-end encryption passphrase (read from stdin; empty disables encryption)
+    SetPassphrase,
     /// Run as a background daemon service
     Daemon,
+    /// Show what the running daemon is watching
+    Status,
+    /// Stop the running daemon from watching a game until resumed
+    Pause {
+        /// Name of the game
+        game: String,
+    },
+    /// Let the running daemon watch a paused game again
+    Resume {
+        /// Name of the game
+        game: String,
+    },
+    /// Make the running daemon re-read the configuration
+    Reload,
     /// Service management commands
     Service {
         #[command(subcommand)]
@@ -121,8 +177,10 @@ async fn main() -> Result<()> {
         Command::Sync { game } => {
             sync_game(&config_manager, &game).await?;
         }
-        Command::AddGame { name, path } => {
-            add_game(&config_manager, &name, path).await?;
+        Command::AddGame { name, path, include, exclude, max_file_size, watch_backend, poll_interval_ms, debounce_ms, settle_polls } => {
+            let filter = SaveFilter { include, exclude, max_file_size };
+            let watch = WatchSettings { watch_backend, poll_interval_ms, debounce_ms, settle_polls };
+            add_game(&config_manager, &name, path, filter, watch).await?;
         }
         Command::List => {
             list_games(&config_manager).await?;
@@ -133,9 +191,36 @@ async fn main() -> Result<()> {
         Command::Init => {
             init_config(&config_manager).await?;
         }
+        Command::Conflicts => {
+            list_conflicts(&config_manager).await?;
+        }
+        Command::Resolve { game, file, resolution } => {
+            resolve_conflict(&config_manager, &game, &file, resolution).await?;
+        }
+        Command::SetConflictPolicy { game, policy } => {
+            set_conflict_policy(&config_manager, &game, policy).await?;
+        }
+        Command::SetPassphrase => {
+            set_passphrase(&config_manager).await?;
+        }
         Command::Daemon => {
             run_daemon().await?;
         }
+        Command::Status => {
+            show_daemon_status().await?;
+        }
+        Command::Pause { game } => {
+            DaemonClient::connect_default().await?.pause(&game).await?;
+            info!("Paused watching {}", game);
+        }
+        Command::Resume { game } => {
+            DaemonClient::connect_default().await?.resume(&game).await?;
+            info!("Resumed watching {}", game);
+        }
+        Command::Reload => {
+            DaemonClient::connect_default().await?.reload().await?;
+            info!("Daemon reloaded its configuration");
+        }
         Command::Service { action } => {
             handle_service_command(action).await?;
         }
@@ -185,6 +270,35 @@ fn initialize_daemon_logging() -> Result<()> {
     try_initialize_daemon_logging()
 }
 
+async fn show_daemon_status() -> Result<()> {
+    let status = DaemonClient::connect_default().await?.status().await?;
+
+    println!("Daemon running (pid {}) since {}", status.pid, status.started_at.to_rfc3339());
+    for game in &status.games {
+        let state = if !game.sync_enabled {
+            "sync disabled"
+        } else if game.paused {
+            "paused"
+        } else if game.watching {
+            "watching"
+        } else {
+            "not watching"
+        };
+        println!("  {}: {}", game.name, state);
+
+        for (path, watch_state) in &game.watch_states {
+            match watch_state {
+                WatchState::Watching { polling: false } => println!("    {}: watching", path),
+                WatchState::Watching { polling: true } => println!("    {}: polling", path),
+                WatchState::Waiting { ancestor } => println!("    {}: waiting to appear (in {})", path, ancestor.display()),
+                WatchState::Failed { error } => println!("    {}: failed: {}", path, error),
+            }
+        }
+    }
+
+    Ok(())
+}
+
 async fn run_daemon() -> Result<()> {
     info!("Starting DeckSaves daemon");
 
@@ -621,6 +735,13 @@ async fn watch_game(config_manager: &ConfigManager, game_name: &str) -> Result<(
         return Ok(());
     }
 
+    // Watching here as well would sync every change twice
+    if let Ok(mut client) = DaemonClient::connect_default().await {
+        client.resume(game_name).await?;
+        info!("The daemon is running and watching {}; see `game-sync status`", game_name);
+        return Ok(());
+    }
+
     info!("Starting to watch game: {}", game_name);
     info!("Watching paths: {:?}", game_config.save_paths);
 
@@ -633,6 +754,15 @@ async fn watch_game(config_manager: &ConfigManager, game_name: &str) -> Result<(
         sync_handler,
     ).await?;
 
+    for (path, state) in watcher_manager.watch_states(game_name) {
+        match state {
+            WatchState::Watching { polling: false } => info!("Watching {}", path),
+            WatchState::Watching { polling: true } => info!("Polling {}", path),
+            WatchState::Waiting { ancestor } => info!("Waiting for {} to appear (watching {})", path, ancestor.display()),
+            WatchState::Failed { error } => warn!("Not watching {}: {}", path, error),
+        }
+    }
+
     info!("Press Ctrl+C to stop watching...");
     
     // Keep the program running
@@ -644,23 +774,38 @@ async fn watch_game(config_manager: &ConfigManager, game_name: &str) -> Result<(
 }
 
 async fn sync_game(config_manager: &ConfigManager, game_name: &str) -> Result<()> {
+    // The running daemon syncs for us, so two processes never sync at once
+    if let Ok(mut client) = DaemonClient::connect_default().await {
+        info!("Syncing game through the daemon: {}", game_name);
+        client.sync_now(Some(game_name.to_string())).await?;
+        info!("Sync completed for: {}", game_name);
+        return Ok(());
+    }
+
     let config = config_manager.load_config().await?;
     let sync_handler = GameSaveSync::new(config).await?;
     
     info!("Syncing game: {}", game_name);
     sync_handler.sync_game(game_name).await?;
     info!("Sync completed for: {}", game_name);
+
+    for conflict in sync_handler.conflicts().iter().filter(|c| c.game_name == game_name) {
+        warn!("Conflict: {} changed on this device and in the cloud; neither copy was overwritten", conflict.file_path);
+    }
     
     Ok(())
 }
 
-async fn add_game(config_manager: &ConfigManager, name: &str, paths: Vec<String>) -> Result<()> {
+async fn add_game(config_manager: &ConfigManager, name: &str, paths: Vec<String>, filter: SaveFilter, watch: WatchSettings) -> Result<()> {
     let mut config = config_manager.load_config().await?;
     
     let game_config = GameConfig {
         name: name.to_string(),
         save_paths: paths.clone(),
         sync_enabled: true,
+        conflict_policy: ConflictPolicy::default(),
+        filter,
+        watch,
     };
     
     config.games.insert(name.to_string(), game_config);
@@ -686,6 +831,15 @@ async fn list_games(config_manager: &ConfigManager) -> Result<()> {
         for path in &game_config.save_paths {
             println!("      - {}", path);
         }
+        if !game_config.filter.include.is_empty() {
+            println!("    Include: {}", game_config.filter.include.join(", "));
+        }
+        if !game_config.filter.exclude.is_empty() {
+            println!("    Exclude: {}", game_config.filter.exclude.join(", "));
+        }
+        if let Some(max_file_size) = game_config.filter.max_file_size {
+            println!("    Max file size: {} bytes", max_file_size);
+        }
         println!();
     }
     
@@ -698,13 +852,90 @@ async fn show_config(config_manager: &ConfigManager) -> Result<()> {
     println!("Configuration file: {}", config_manager.config_path().display());
     println!("S3 Bucket: {}", config.s3_bucket.unwrap_or_else(|| "Not configured".to_string()));
     println!("S3 Region: {}", config.s3_region.unwrap_or_else(|| "Not configured".to_string()));
+    println!("S3 Endpoint: {}", config.s3_endpoint_url.unwrap_or_else(|| "AWS default".to_string()));
+    println!("S3 Path-style addressing: {}", config.s3_force_path_style);
+    println!("S3 Key prefix: {}", config.s3_key_prefix.unwrap_or_else(|| "None".to_string()));
     println!("Peer sync enabled: {}", config.peer_sync_enabled);
     println!("WebSocket URL: {}", config.websocket_url.unwrap_or_else(|| "Not configured".to_string()));
+    println!("End-to-end encryption: {}", if config.encryption_passphrase.is_some() { "enabled" } else { "disabled" });
     println!("Number of games: {}", config.games.len());
     
     Ok(())
 }
 
+async fn list_conflicts(config_manager: &ConfigManager) -> Result<()> {
+    let conflicts = match DaemonClient::connect_default().await {
+        Ok(mut client) => client.conflicts().await?,
+        Err(_) => {
+            let config = config_manager.load_config().await?;
+            GameSaveSync::new(config).await?.conflicts()
+        }
+    };
+
+    if conflicts.is_empty() {
+        println!("No pending conflicts.");
+        return Ok(());
+    }
+
+    println!("Pending conflicts:");
+    for conflict in &conflicts {
+        println!("  {}: {}", conflict.game_name, conflict.file_path);
+        println!("    Detected: {}", conflict.detected_at.to_rfc3339());
+        println!("    Local hash:  {}", conflict.local_hash);
+        println!("    Remote hash: {}", conflict.remote_hash);
+    }
+    println!();
+    println!("Resolve with: game-sync resolve <game> <file> <newest|local|remote|both>");
+
+    Ok(())
+}
+
+async fn resolve_conflict(config_manager: &ConfigManager, game_name: &str, file_path: &str, resolution: ConflictResolution) -> Result<()> {
+    // A running daemon resolves the conflict itself, so it is not settled
+    // while the daemon is syncing the same file
+    match DaemonClient::connect_default().await {
+        Ok(mut client) => client.resolve_conflict(game_name, file_path, resolution).await?,
+        Err(_) => {
+            let config = config_manager.load_config().await?;
+            GameSaveSync::new(config).await?
+                .resolve_conflict(game_name, file_path, resolution).await?;
+        }
+    }
+    info!("Resolved conflict for {} ({})", file_path, game_name);
+    Ok(())
+}
+
+async fn set_conflict_policy(config_manager: &ConfigManager, game_name: &str, policy: ConflictPolicy) -> Result<()> {
+    let mut config = config_manager.load_config().await?;
+
+    let game_config = config.games.get_mut(game_name)
+        .ok_or_else(|| anyhow::anyhow!("Game not found in configuration: {}", game_name))?;
+    game_config.conflict_policy = policy;
+    config_manager.save_config(&config).await?;
+
+    info!("Conflict policy for '{}' set to {:?}", game_name, policy);
+    Ok(())
+}
+
+async fn set_passphrase(config_manager: &ConfigManager) -> Result<()> {
+    let mut config = config_manager.load_config().await?;
+
+    println!("Enter encryption passphrase (leave empty to disable encryption):");
+    let mut passphrase = String::new();
+    std::io::stdin().read_line(&mut passphrase)?;
+    let passphrase = passphrase.trim_end_matches(['\r', '\n']);
+
+    config.set_encryption_passphrase(passphrase)?;
+    config_manager.save_config(&config).await?;
+
+    if passphrase.is_empty() {
+        info!("End-to-end encryption disabled for new uploads");
+    } else {
+        info!("End-to-end encryption passphrase saved. Use the same passphrase on every device.");
+    }
+    Ok(())
+}
+
 async fn init_config(config_manager: &ConfigManager) -> Result<()> {
     let mut config = core::SyncConfig::default();
     
@@ -718,6 +949,12 @@ async fn init_config(config_manager: &ConfigManager) -> Result<()> {
             "~/.local/share/Steam/steamapps/compatdata/12345/pfx/drive_c/users/steamuser/Documents/SaveGame".to_string(),
         ],
         sync_enabled: false,
+        conflict_policy: ConflictPolicy::default(),
+        filter: SaveFilter {
+            exclude: vec!["*.log".to_string(), "*.tmp".to_string()],
+            ..SaveFilter::default()
+        },
+        watch: WatchSettings::default(),
     });
     
     config.games = games;
@@ -0,0 +1,387 @@
+use anyhow::{anyhow, Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs::{File, Metadata};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use tracing::{debug, warn};
+
+use crate::storage::CompressionCodec;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// One file written to or read from an archive
+#[derive(Debug, Clone)]
+pub struct ArchiveProgress<'a> {
+    /// Path inside the archive
+    pub path: &'a Path,
+    pub size: u64,
+    /// Files processed so far, including this one
+    pub files_done: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArchiveSummary {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Write a tar archive of `source_dir`, compressed with `codec`.
+///
+/// Entries are stored relative to `source_dir` in sorted order with their
+/// mtimes and permissions. `include` decides which files are archived from
+/// their path relative to `source_dir`; symlinks and special files are skipped.
+pub fn create_archive(
+    source_dir: &Path,
+    archive_path: &Path,
+    codec: CompressionCodec,
+    include: impl Fn(&Path, &Metadata) -> bool,
+    progress: impl FnMut(ArchiveProgress),
+) -> Result<ArchiveSummary> {
+    if !source_dir.is_dir() {
+        return Err(anyhow!("Path is not a directory: {}", source_dir.display()));
+    }
+
+    let file = BufWriter::new(
+        File::create(archive_path)
+            .with_context(|| format!("Failed to create archive {}", archive_path.display()))?,
+    );
+
+    // Encoders are finished explicitly: dropping one finishes the stream too,
+    // but silently loses any error writing its last block
+    let (summary, mut file) = match codec {
+        CompressionCodec::None => append_tree(file, source_dir, include, progress)?,
+        CompressionCodec::Gzip => {
+            let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+            let (summary, encoder) = append_tree(encoder, source_dir, include, progress)?;
+            (summary, encoder.finish().context("Failed to finish gzip stream")?)
+        }
+        CompressionCodec::Zstd => {
+            let encoder = zstd::Encoder::new(file, 0)?;
+            let (summary, encoder) = append_tree(encoder, source_dir, include, progress)?;
+            (summary, encoder.finish().context("Failed to finish zstd stream")?)
+        }
+    };
+    file.flush().context("Failed to flush archive")?;
+
+    Ok(summary)
+}
+
+/// Write the tar entries of `source_dir` to `writer`, returning the writer
+/// once the archive is complete
+fn append_tree<W: Write>(
+    writer: W,
+    source_dir: &Path,
+    include: impl Fn(&Path, &Metadata) -> bool,
+    mut progress: impl FnMut(ArchiveProgress),
+) -> Result<(ArchiveSummary, W)> {
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+
+    let mut summary = ArchiveSummary::default();
+    let mut pending_dirs = vec![PathBuf::new()];
+
+    while let Some(rel_dir) = pending_dirs.pop() {
+        let mut entries = std::fs::read_dir(source_dir.join(&rel_dir))
+            .with_context(|| format!("Failed to read directory {}", source_dir.join(&rel_dir).display()))?
+            .collect::<std::io::Result<Vec<_>>>()
+            .context("Failed to read directory entry")?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        let mut subdirs = Vec::new();
+        for entry in entries {
+            let rel = rel_dir.join(entry.file_name());
+            let metadata = std::fs::symlink_metadata(entry.path())
+                .with_context(|| format!("Failed to read metadata of {}", entry.path().display()))?;
+
+            if metadata.is_dir() {
+                builder.append_dir(&rel, entry.path())
+                    .with_context(|| format!("Failed to archive directory {}", rel.display()))?;
+                subdirs.push(rel);
+            } else if metadata.is_file() {
+                if !include(&rel, &metadata) {
+                    debug!("Leaving filtered file out of archive: {}", rel.display());
+                    continue;
+                }
+
+                let mut source = File::open(entry.path())
+                    .with_context(|| format!("Failed to open {}", entry.path().display()))?;
+                builder.append_file(&rel, &mut source)
+                    .with_context(|| format!("Failed to archive {}", rel.display()))?;
+
+                summary.files += 1;
+                summary.bytes += metadata.len();
+                progress(ArchiveProgress { path: &rel, size: metadata.len(), files_done: summary.files });
+            } else {
+                debug!("Skipping non-regular file in archive: {}", rel.display());
+            }
+        }
+
+        // Depth-first in sorted order, so archives of the same tree are identical
+        pending_dirs.extend(subdirs.into_iter().rev());
+    }
+
+    let writer = builder.into_inner().context("Failed to finish archive")?;
+    Ok((summary, writer))
+}
+
+/// Extract an archive written by `create_archive` or by the system `tar`
+/// (`tar -czf`, as earlier releases did) into `target_dir`.
+///
+/// The compression is detected from the data. Entries that would land outside
+/// `target_dir` abort the extraction; links and special files are skipped.
+pub fn extract_archive(
+    archive_path: &Path,
+    target_dir: &Path,
+    mut progress: impl FnMut(ArchiveProgress),
+) -> Result<ArchiveSummary> {
+    std::fs::create_dir_all(target_dir).context("Failed to create target directory")?;
+
+    let mut archive = tar::Archive::new(open_decoder(archive_path)?);
+    archive.set_preserve_permissions(true);
+    archive.set_preserve_mtime(true);
+
+    let mut summary = ArchiveSummary::default();
+    for entry in archive.entries().context("Failed to read archive")? {
+        let mut entry = entry.context("Failed to read archive entry")?;
+        let raw_path = entry.path().context("Invalid path in archive")?.into_owned();
+        let Some(rel) = safe_relative_path(&raw_path) else {
+            return Err(anyhow!("Refusing to extract {} outside the target directory", raw_path.display()));
+        };
+        if rel.as_os_str().is_empty() {
+            continue;
+        }
+
+        let entry_type = entry.header().entry_type();
+        if !(entry_type.is_file() || entry_type.is_dir()) {
+            warn!("Skipping {:?} entry in archive: {}", entry_type, rel.display());
+            continue;
+        }
+
+        if let Some(parent) = target_dir.join(&rel).parent() {
+            std::fs::create_dir_all(parent).context("Failed to create directory")?;
+        }
+        // unpack_in also refuses to write through symlinks pointing outside target_dir
+        if !entry.unpack_in(target_dir).with_context(|| format!("Failed to extract {}", rel.display()))? {
+            return Err(anyhow!("Refusing to extract {} outside the target directory", rel.display()));
+        }
+
+        if entry_type.is_file() {
+            let size = entry.header().size().unwrap_or(0);
+            summary.files += 1;
+            summary.bytes += size;
+            progress(ArchiveProgress { path: &rel, size, files_done: summary.files });
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Hash of the directories and file contents in an archive, ignoring
+/// compression, timestamps and permissions. Two archives of the same tree
+/// hash the same even when their bytes differ.
+pub fn content_hash(archive_path: &Path) -> Result<String> {
+    let mut archive = tar::Archive::new(open_decoder(archive_path)?);
+    let mut hasher = Sha256::new();
+
+    for entry in archive.entries().context("Failed to read archive")? {
+        let mut entry = entry.context("Failed to read archive entry")?;
+        let raw_path = entry.path().context("Invalid path in archive")?.into_owned();
+        let Some(rel) = safe_relative_path(&raw_path) else {
+            return Err(anyhow!("Invalid path in archive: {}", raw_path.display()));
+        };
+        if rel.as_os_str().is_empty() {
+            continue;
+        }
+
+        // Length-prefix each field so different trees cannot produce the same stream
+        let rel = rel.to_string_lossy();
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_dir() {
+            hasher.update(b"d");
+            hasher.update((rel.len() as u64).to_le_bytes());
+            hasher.update(rel.as_bytes());
+        } else if entry_type.is_file() {
+            hasher.update(b"f");
+            hasher.update((rel.len() as u64).to_le_bytes());
+            hasher.update(rel.as_bytes());
+            hasher.update(entry.header().size().unwrap_or(0).to_le_bytes());
+            std::io::copy(&mut entry, &mut HashWriter(&mut hasher))
+                .with_context(|| format!("Failed to read {} from archive", rel))?;
+        }
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+struct HashWriter<'a>(&'a mut Sha256);
+
+impl Write for HashWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn open_decoder(archive_path: &Path) -> Result<Box<dyn Read>> {
+    let mut file = BufReader::new(File::open(archive_path)
+        .with_context(|| format!("Failed to open archive {}", archive_path.display()))?);
+
+    // Peek at the magic bytes without consuming them
+    let header = file.fill_buf().context("Failed to read archive")?;
+
+    Ok(if header.starts_with(GZIP_MAGIC) {
+        Box::new(flate2::read::GzDecoder::new(file))
+    } else if header.starts_with(ZSTD_MAGIC) {
+        Box::new(zstd::Decoder::with_buffer(file)?)
+    } else {
+        Box::new(file)
+    })
+}
+
+/// Normalise an archive path to a relative path, or `None` if it is absolute
+/// or climbs out with `..`
+fn safe_relative_path(path: &Path) -> Option<PathBuf> {
+    let mut rel = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => rel.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(rel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_tree(root: &Path) {
+        std::fs::create_dir_all(root.join("profiles/1")).unwrap();
+        std::fs::create_dir_all(root.join("empty")).unwrap();
+        std::fs::write(root.join("global.dat"), b"global").unwrap();
+        std::fs::write(root.join("profiles/1/slot 1.sav"), b"slot one").unwrap();
+        std::fs::write(root.join("output.log"), b"noise").unwrap();
+    }
+
+    #[test]
+    fn test_roundtrip_preserves_paths_mtimes_and_permissions() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let source = dir.join("source");
+        write_tree(&source);
+
+        let save = source.join("profiles/1/slot 1.sav");
+        let mtime = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+        File::options().write(true).open(&save).unwrap().set_modified(mtime).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&save, std::fs::Permissions::from_mode(0o600)).unwrap();
+        }
+
+        for codec in [CompressionCodec::None, CompressionCodec::Gzip, CompressionCodec::Zstd] {
+            let archive = dir.join(format!("snapshot.{}", codec.as_str()));
+            let target = dir.join(format!("restored_{}", codec.as_str()));
+            let mut archived = Vec::new();
+
+            let summary = create_archive(
+                &source,
+                &archive,
+                codec,
+                |rel, _| rel.extension().is_none_or(|ext| ext != "log"),
+                |p| archived.push(p.path.to_path_buf()),
+            ).unwrap();
+            assert_eq!(summary.files, 2);
+            assert_eq!(archived, [PathBuf::from("global.dat"), PathBuf::from("profiles/1/slot 1.sav")]);
+
+            let extracted = extract_archive(&archive, &target, |_| {}).unwrap();
+            assert_eq!(extracted, summary);
+
+            let restored = target.join("profiles/1/slot 1.sav");
+            assert_eq!(std::fs::read(&restored).unwrap(), b"slot one");
+            assert_eq!(std::fs::metadata(&restored).unwrap().modified().unwrap(), mtime);
+            assert!(target.join("empty").is_dir());
+            assert!(!target.join("output.log").exists());
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                assert_eq!(std::fs::metadata(&restored).unwrap().permissions().mode() & 0o777, 0o600);
+            }
+        }
+    }
+
+    #[test]
+    fn test_content_hash_ignores_mtimes_and_codec() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let source = dir.join("source");
+        write_tree(&source);
+
+        let gzip = dir.join("first.tar.gz");
+        create_archive(&source, &gzip, CompressionCodec::Gzip, |_, _| true, |_| {}).unwrap();
+
+        // Touching a file changes the archive bytes but not its content
+        let later = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        File::options().write(true).open(source.join("global.dat")).unwrap().set_modified(later).unwrap();
+        let zstd = dir.join("second.tar.zst");
+        create_archive(&source, &zstd, CompressionCodec::Zstd, |_, _| true, |_| {}).unwrap();
+
+        assert_ne!(std::fs::read(&gzip).unwrap(), std::fs::read(&zstd).unwrap());
+        assert_eq!(content_hash(&gzip).unwrap(), content_hash(&zstd).unwrap());
+
+        std::fs::write(source.join("global.dat"), b"changed").unwrap();
+        let changed = dir.join("third.tar.gz");
+        create_archive(&source, &changed, CompressionCodec::Gzip, |_, _| true, |_| {}).unwrap();
+        assert_ne!(content_hash(&gzip).unwrap(), content_hash(&changed).unwrap());
+    }
+
+    #[test]
+    fn test_extracts_archives_from_system_tar() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let source = dir.join("source");
+        write_tree(&source);
+
+        let archive = dir.join("legacy.tar.gz");
+        let created = std::process::Command::new("tar")
+            .args(["-czf", &archive.to_string_lossy(), "-C", &source.to_string_lossy(), "."])
+            .status();
+        if !created.is_ok_and(|status| status.success()) {
+            // No tar on this machine, nothing to be compatible with
+            return;
+        }
+
+        let target = dir.join("restored");
+        let summary = extract_archive(&archive, &target, |_| {}).unwrap();
+        assert_eq!(summary.files, 3);
+        assert_eq!(std::fs::read(target.join("profiles/1/slot 1.sav")).unwrap(), b"slot one");
+    }
+
+    #[test]
+    fn test_rejects_path_traversal() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let archive = dir.join("evil.tar");
+
+        let mut builder = tar::Builder::new(File::create(&archive).unwrap());
+        let mut header = tar::Header::new_gnu();
+        let name = b"../escaped.txt";
+        header.as_old_mut().name[..name.len()].copy_from_slice(name);
+        header.set_size(4);
+        header.set_mode(0o644);
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_cksum();
+        builder.append(&header, &b"evil"[..]).unwrap();
+        builder.into_inner().unwrap();
+
+        let target = dir.join("target");
+        assert!(extract_archive(&archive, &target, |_| {}).is_err());
+        assert!(!dir.join("escaped.txt").exists());
+    }
+}
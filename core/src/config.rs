@@ -35,8 +35,12 @@ impl ConfigManager {
         println!("DEBUG CONFIG: Config file content length: {} chars", content.len());
         println!("DEBUG CONFIG: Config file content:\n{}", content);
         
-        let config: SyncConfig = toml::from_str(&content)
+        let mut config: SyncConfig = toml::from_str(&content)
             .context("Failed to parse config file")?;
+
+        if config.migrate_credentials()? {
+            self.save_config(&config).await?;
+        }
         
         println!("DEBUG CONFIG: Parsed config - aws_access_key_id present: {}, aws_secret_access_key present: {}", 
                 config.aws_access_key_id.is_some(), 
@@ -3,19 +3,97 @@ use aes_gcm::{
     Aes256Gcm, Key, Nonce
 };
 use base64::{Engine as _, engine::general_purpose};
-use anyhow::{Result, anyhow};
+use anyhow::{Result, Context, anyhow};
+use argon2::Argon2;
+use directories::ProjectDirs;
+use rand::RngCore;
 use sha2::{Sha256, Digest};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Ciphertext prefix for values encrypted with the random key file
+const KEY_FILE_PREFIX: &str = "kf1:";
+/// Ciphertext prefix for values encrypted with an Argon2-derived passphrase key
+const PASSPHRASE_PREFIX: &str = "pp1:";
+/// Environment variable selecting the passphrase key source
+pub const PASSPHRASE_ENV_VAR: &str = "DECKSAVES_CREDENTIAL_PASSPHRASE";
+
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+
+/// Where the credential encryption key comes from
+#[derive(Debug, Clone)]
+pub enum KeySource {
+    /// Random 256-bit key stored in a file readable only by the owner
+    KeyFile(PathBuf),
+    /// Key derived with Argon2id from a user passphrase, salted per value
+    Passphrase(String),
+}
+
+impl KeySource {
+    /// Passphrase from `DECKSAVES_CREDENTIAL_PASSPHRASE` if set, otherwise the
+    /// key file in the config directory
+    pub fn from_env() -> Result<Self> {
+        match std::env::var(PASSPHRASE_ENV_VAR) {
+            Ok(passphrase) if !passphrase.is_empty() => Ok(KeySource::Passphrase(passphrase)),
+            _ => Ok(KeySource::KeyFile(Self::default_key_file()?)),
+        }
+    }
+
+    pub fn default_key_file() -> Result<PathBuf> {
+        let project_dirs = ProjectDirs::from("com", "decksaves", "game-sync")
+            .context("Failed to get project directories")?;
+        Ok(project_dirs.config_dir().join("credentials.key"))
+    }
+
+    fn prefix(&self) -> &'static str {
+        match self {
+            KeySource::KeyFile(_) => KEY_FILE_PREFIX,
+            KeySource::Passphrase(_) => PASSPHRASE_PREFIX,
+        }
+    }
+}
 
 pub struct CredentialCrypto {
-    cipher: Aes256Gcm,
+    source: KeySource,
+    /// Loaded key for `KeySource::KeyFile`; passphrase keys depend on the per-value salt
+    file_key: Option<Key<Aes256Gcm>>,
 }
 
 impl CredentialCrypto {
-    /// Create a new crypto instance with a key derived from the machine
+    /// Create a new crypto instance using the key source selected by the
+    /// environment. With a passphrase, values from an existing key file can
+    /// still be read so they can be migrated.
     pub fn new() -> Result<Self> {
-        let key = Self::derive_machine_key()?;
-        let cipher = Aes256Gcm::new(&key);
-        Ok(Self { cipher })
+        let crypto = Self::with_key_source(KeySource::from_env()?)?;
+        match crypto.source {
+            KeySource::Passphrase(_) => Ok(crypto.with_key_file_fallback(&KeySource::default_key_file()?)),
+            KeySource::KeyFile(_) => Ok(crypto),
+        }
+    }
+
+    /// Also decrypt values written with the key file at `path`, if it exists.
+    /// The file is never created.
+    pub fn with_key_file_fallback(mut self, path: &Path) -> Self {
+        if self.file_key.is_none() && path.exists() {
+            match load_or_create_key_file(path) {
+                Ok(key) => self.file_key = Some(key),
+                Err(e) => warn!("Cannot read key file values: {:#}", e),
+            }
+        }
+        self
+    }
+
+    pub fn with_key_source(source: KeySource) -> Result<Self> {
+        let file_key = match &source {
+            KeySource::KeyFile(path) => Some(load_or_create_key_file(path)?),
+            KeySource::Passphrase(passphrase) if passphrase.is_empty() => {
+                return Err(anyhow!("Credential passphrase must not be empty"));
+            }
+            KeySource::Passphrase(_) => None,
+        };
+
+        Ok(Self { source, file_key })
     }
 
     /// Encrypt a credential string
@@ -24,35 +102,64 @@ impl CredentialCrypto {
             return Ok(String::new());
         }
 
+        // Layout after the scheme prefix: base64([salt] + nonce + ciphertext)
+        let mut combined = Vec::new();
+        let key = match (&self.source, &self.file_key) {
+            (KeySource::Passphrase(passphrase), _) => {
+                let mut salt = [0u8; SALT_LEN];
+                rand::thread_rng().fill_bytes(&mut salt);
+                combined.extend_from_slice(&salt);
+                derive_passphrase_key(passphrase, &salt)?
+            }
+            (KeySource::KeyFile(_), Some(key)) => *key,
+            (KeySource::KeyFile(path), None) => {
+                return Err(anyhow!("Key file {} was not loaded", path.display()));
+            }
+        };
+
         let nonce = Aes256Gcm::generate_nonce(&mut rand::thread_rng());
-        let ciphertext = self.cipher
+        let ciphertext = Aes256Gcm::new(&key)
             .encrypt(&nonce, plaintext.as_bytes())
             .map_err(|e| anyhow!("Encryption failed: {}", e))?;
 
-        // Combine nonce + ciphertext and encode as base64
-        let mut combined = nonce.to_vec();
+        combined.extend_from_slice(&nonce);
         combined.extend_from_slice(&ciphertext);
-        Ok(general_purpose::STANDARD.encode(&combined))
+        Ok(format!("{}{}", self.source.prefix(), general_purpose::STANDARD.encode(&combined)))
     }
 
-    /// Decrypt a credential string
+    /// Decrypt a credential string. The prefix selects the scheme; unprefixed
+    /// values are from the legacy hostname/username key.
     pub fn decrypt(&self, ciphertext: &str) -> Result<String> {
         if ciphertext.is_empty() {
             return Ok(String::new());
         }
 
-        let combined = general_purpose::STANDARD
-            .decode(ciphertext)
-            .map_err(|e| anyhow!("Base64 decode failed: {}", e))?;
+        let (key, body) = if let Some(body) = ciphertext.strip_prefix(KEY_FILE_PREFIX) {
+            let key = self.file_key
+                .ok_or_else(|| anyhow!("Credential was encrypted with a key file, but a passphrase is configured"))?;
+            (key, decode_base64(body)?)
+        } else if let Some(body) = ciphertext.strip_prefix(PASSPHRASE_PREFIX) {
+            let KeySource::Passphrase(passphrase) = &self.source else {
+                return Err(anyhow!("Credential was encrypted with a passphrase; set {}", PASSPHRASE_ENV_VAR));
+            };
+            let mut combined = decode_base64(body)?;
+            if combined.len() < SALT_LEN {
+                return Err(anyhow!("Invalid ciphertext length"));
+            }
+            let body = combined.split_off(SALT_LEN);
+            (derive_passphrase_key(passphrase, &combined)?, body)
+        } else {
+            (derive_machine_key(), decode_base64(ciphertext)?)
+        };
 
-        if combined.len() < 12 {
+        if body.len() < NONCE_LEN {
             return Err(anyhow!("Invalid ciphertext length"));
         }
 
-        let (nonce_bytes, ciphertext_bytes) = combined.split_at(12);
+        let (nonce_bytes, ciphertext_bytes) = body.split_at(NONCE_LEN);
         let nonce = Nonce::from_slice(nonce_bytes);
 
-        let plaintext = self.cipher
+        let plaintext = Aes256Gcm::new(&key)
             .decrypt(nonce, ciphertext_bytes)
             .map_err(|e| anyhow!("Decryption failed: {}", e))?;
 
@@ -60,49 +167,208 @@ impl CredentialCrypto {
             .map_err(|e| anyhow!("UTF-8 conversion failed: {}", e))
     }
 
-    /// Derive a machine-specific key for encryption
-    fn derive_machine_key() -> Result<Key<Aes256Gcm>> {
-        // Use hostname and user as seed for machine-specific key
-        let hostname = std::env::var("HOSTNAME")
-            .or_else(|_| std::env::var("COMPUTERNAME"))
-            .unwrap_or_else(|_| "unknown".to_string());
-        
-        let username = std::env::var("USER")
-            .or_else(|_| std::env::var("USERNAME"))
-            .unwrap_or_else(|_| "unknown".to_string());
-
-        // Create a deterministic key from machine info
-        let mut hasher = Sha256::default();
-        hasher.update(b"decksaves_crypto_v1");
-        hasher.update(hostname.as_bytes());
-        hasher.update(username.as_bytes());
-        
-        let key_bytes = hasher.finalize();
-        Ok(*Key::<Aes256Gcm>::from_slice(&key_bytes))
+    /// Whether a stored value should be re-encrypted with the current key source
+    pub fn needs_migration(&self, ciphertext: &str) -> bool {
+        !ciphertext.is_empty() && !ciphertext.starts_with(self.source.prefix())
+    }
+
+    /// Decrypt a value from any scheme and re-encrypt it with the current key source.
+    /// Returns `None` when the value is already current.
+    pub fn reencrypt(&self, ciphertext: &str) -> Result<Option<String>> {
+        if !self.needs_migration(ciphertext) {
+            return Ok(None);
+        }
+        let plaintext = self.decrypt(ciphertext)?;
+        Ok(Some(self.encrypt(&plaintext)?))
+    }
+}
+
+fn decode_base64(data: &str) -> Result<Vec<u8>> {
+    general_purpose::STANDARD
+        .decode(data)
+        .map_err(|e| anyhow!("Base64 decode failed: {}", e))
+}
+
+fn derive_passphrase_key(passphrase: &str, salt: &[u8]) -> Result<Key<Aes256Gcm>> {
+    let mut key_bytes = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key_bytes)
+        .map_err(|e| anyhow!("Key derivation failed: {}", e))?;
+    Ok(*Key::<Aes256Gcm>::from_slice(&key_bytes))
+}
+
+/// Load the random key, creating it with owner-only permissions on first use
+fn load_or_create_key_file(path: &Path) -> Result<Key<Aes256Gcm>> {
+    if path.exists() {
+        let key_bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read key file {}", path.display()))?;
+        if key_bytes.len() != 32 {
+            return Err(anyhow!("Key file {} is corrupt (expected 32 bytes, found {})", path.display(), key_bytes.len()));
+        }
+        restrict_permissions(path)?;
+        return Ok(*Key::<Aes256Gcm>::from_slice(&key_bytes));
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory for key file {}", path.display()))?;
     }
+
+    let mut key_bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut key_bytes);
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    use std::io::Write;
+    let mut file = options.open(path)
+        .with_context(|| format!("Failed to create key file {}", path.display()))?;
+    file.write_all(&key_bytes)?;
+    file.sync_all()?;
+
+    Ok(*Key::<Aes256Gcm>::from_slice(&key_bytes))
+}
+
+#[cfg(unix)]
+fn restrict_permissions(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = std::fs::metadata(path)?.permissions().mode();
+    if mode & 0o077 != 0 {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("Failed to restrict permissions on {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Legacy key from hostname and username. Only used to read values written
+/// before key sources existed; new values are never encrypted with it.
+fn derive_machine_key() -> Key<Aes256Gcm> {
+    let hostname = std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string());
+
+    let username = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string());
+
+    let mut hasher = Sha256::default();
+    hasher.update(b"decksaves_crypto_v1");
+    hasher.update(hostname.as_bytes());
+    hasher.update(username.as_bytes());
+
+    let key_bytes = hasher.finalize();
+    *Key::<Aes256Gcm>::from_slice(&key_bytes)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Encrypt the way releases before key sources did
+    fn legacy_encrypt(plaintext: &str) -> String {
+        let nonce = Aes256Gcm::generate_nonce(&mut rand::thread_rng());
+        let ciphertext = Aes256Gcm::new(&derive_machine_key()).encrypt(&nonce, plaintext.as_bytes()).unwrap();
+        let mut combined = nonce.to_vec();
+        combined.extend_from_slice(&ciphertext);
+        general_purpose::STANDARD.encode(&combined)
+    }
+
     #[test]
     fn test_encrypt_decrypt() {
-        let crypto = CredentialCrypto::new().unwrap();
+        let temp = tempfile::tempdir().unwrap();
+        let crypto = CredentialCrypto::with_key_source(KeySource::KeyFile(temp.path().join("credentials.key"))).unwrap();
         let plaintext = "test_secret_key";
-        
+
         let encrypted = crypto.encrypt(plaintext).unwrap();
         let decrypted = crypto.decrypt(&encrypted).unwrap();
-        
+
         assert_eq!(plaintext, decrypted);
     }
 
     #[test]
     fn test_empty_string() {
-        let crypto = CredentialCrypto::new().unwrap();
+        let temp = tempfile::tempdir().unwrap();
+        let crypto = CredentialCrypto::with_key_source(KeySource::KeyFile(temp.path().join("credentials.key"))).unwrap();
         let encrypted = crypto.encrypt("").unwrap();
         let decrypted = crypto.decrypt(&encrypted).unwrap();
-        
+
         assert_eq!("", decrypted);
     }
+
+    #[test]
+    fn test_key_file_is_created_private_and_reused() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("credentials.key");
+        let crypto = CredentialCrypto::with_key_source(KeySource::KeyFile(path.clone())).unwrap();
+        let encrypted = crypto.encrypt("AKIAEXAMPLE").unwrap();
+        assert!(encrypted.starts_with(KEY_FILE_PREFIX));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let reloaded = CredentialCrypto::with_key_source(KeySource::KeyFile(path.clone())).unwrap();
+        assert_eq!(reloaded.decrypt(&encrypted).unwrap(), "AKIAEXAMPLE");
+    }
+
+    #[test]
+    fn test_passphrase_source() {
+        let crypto = CredentialCrypto::with_key_source(KeySource::Passphrase("hunter2".to_string())).unwrap();
+        let encrypted = crypto.encrypt("secret").unwrap();
+        assert!(encrypted.starts_with(PASSPHRASE_PREFIX));
+        assert_eq!(crypto.decrypt(&encrypted).unwrap(), "secret");
+
+        let wrong = CredentialCrypto::with_key_source(KeySource::Passphrase("hunter3".to_string())).unwrap();
+        assert!(wrong.decrypt(&encrypted).is_err());
+    }
+
+    #[test]
+    fn test_key_file_values_migrate_to_passphrase() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("credentials.key");
+        let key_file = CredentialCrypto::with_key_source(KeySource::KeyFile(path.clone())).unwrap();
+        let encrypted = key_file.encrypt("AKIAEXAMPLE").unwrap();
+
+        let passphrase = CredentialCrypto::with_key_source(KeySource::Passphrase("hunter2".to_string())).unwrap();
+        assert!(passphrase.decrypt(&encrypted).is_err());
+
+        let passphrase = passphrase.with_key_file_fallback(&path);
+        let migrated = passphrase.reencrypt(&encrypted).unwrap().unwrap();
+        assert!(migrated.starts_with(PASSPHRASE_PREFIX));
+        assert_eq!(passphrase.decrypt(&migrated).unwrap(), "AKIAEXAMPLE");
+
+        // A missing key file is not created for the fallback
+        let missing = temp.path().join("missing.key");
+        let _ = CredentialCrypto::with_key_source(KeySource::Passphrase("hunter2".to_string())).unwrap()
+            .with_key_file_fallback(&missing);
+        assert!(!missing.exists());
+    }
+
+    #[test]
+    fn test_legacy_values_are_migrated() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("credentials.key");
+        let crypto = CredentialCrypto::with_key_source(KeySource::KeyFile(path.clone())).unwrap();
+
+        let legacy = legacy_encrypt("old_secret");
+        assert!(crypto.needs_migration(&legacy));
+        assert_eq!(crypto.decrypt(&legacy).unwrap(), "old_secret");
+
+        let migrated = crypto.reencrypt(&legacy).unwrap().unwrap();
+        assert!(migrated.starts_with(KEY_FILE_PREFIX));
+        assert_eq!(crypto.decrypt(&migrated).unwrap(), "old_secret");
+        assert!(crypto.reencrypt(&migrated).unwrap().is_none());
+    }
 }
@@ -1,8 +1,10 @@
-use anyhow::Result;
-#[cfg(not(target_os = "windows"))]
-use anyhow::Context;
+use anyhow::{Context, Result};
 #[cfg(not(target_os = "windows"))]
 use std::path::PathBuf;
+use std::collections::HashSet;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::sleep;
 use tracing::{info, error, warn, debug};
@@ -14,11 +16,25 @@ use signal_hook_tokio::Signals;
 #[cfg(unix)]
 use futures_util::stream::StreamExt;
 
-use crate::{config::ConfigManager, GameSaveSync, watcher::WatcherManager};
+use crate::{config::ConfigManager, GameSaveSync, SyncConfig, SyncStateStore, watcher::{WatchState, WatcherManager}};
+use crate::ipc::{DaemonRequest, DaemonStatus, GameStatus};
+
+/// Answer to a control request
+enum Reply {
+    Done(serde_json::Value),
+    /// Work that takes as long as a sync, run in a task of its own so the
+    /// service loop keeps answering signals and other requests meanwhile
+    Pending(Pin<Box<dyn Future<Output = Result<serde_json::Value>> + Send>>),
+}
 
 pub struct DaemonService {
     config_manager: ConfigManager,
     watcher_manager: WatcherManager,
+    /// Games paused through the control socket, until resumed or the daemon restarts
+    paused: HashSet<String>,
+    /// Held by requested syncs and conflict resolutions, so they run one at a time
+    sync_lock: Arc<tokio::sync::Mutex<()>>,
+    started_at: chrono::DateTime<chrono::Utc>,
     should_stop: bool,
 }
 
@@ -30,6 +46,9 @@ impl DaemonService {
         Ok(Self {
             config_manager,
             watcher_manager,
+            paused: HashSet::new(),
+            sync_lock: Arc::new(tokio::sync::Mutex::new(())),
+            started_at: chrono::Utc::now(),
             should_stop: false,
         })
     }
@@ -47,17 +66,33 @@ impl DaemonService {
         #[cfg(target_os = "linux")]
         self.notify_systemd_ready()?;
 
+        // Listen for requests from the CLI and the UI; this also makes sure
+        // only one daemon runs
+        #[cfg(unix)]
+        let socket_path = crate::ipc::default_socket_path()?;
+        #[cfg(unix)]
+        let (control_tx, mut control_rx) = tokio::sync::mpsc::channel(16);
+        #[cfg(unix)]
+        let control_server = crate::ipc::serve(&socket_path, control_tx).await?;
+        #[cfg(unix)]
+        info!("Listening for control requests on {}", socket_path.display());
+
         // Start watching all configured games
         self.start_all_watchers().await?;
 
         // Main service loop
         #[cfg(unix)]
-        self.run_unix_service_loop(&mut signals).await?;
+        self.run_unix_service_loop(&mut signals, &mut control_rx).await?;
         
         #[cfg(windows)]
         self.run_windows_service_loop().await?;
 
         // Cleanup
+        #[cfg(unix)]
+        {
+            control_server.abort();
+            let _ = std::fs::remove_file(&socket_path);
+        }
         self.shutdown().await?;
         info!("DeckSaves daemon service stopped");
         
@@ -65,9 +100,31 @@ impl DaemonService {
     }
 
     #[cfg(unix)]
-    async fn run_unix_service_loop(&mut self, signals: &mut futures_util::stream::Fuse<Signals>) -> Result<()> {
+    async fn run_unix_service_loop(
+        &mut self,
+        signals: &mut futures_util::stream::Fuse<Signals>,
+        control_rx: &mut tokio::sync::mpsc::Receiver<crate::ipc::Incoming>,
+    ) -> Result<()> {
         loop {
             tokio::select! {
+                // Requests from the CLI and the UI
+                Some(incoming) = control_rx.recv() => {
+                    debug!("Control request: {:?}", incoming.request);
+                    match self.handle_request(incoming.request).await {
+                        Ok(Reply::Done(result)) => {
+                            let _ = incoming.reply.send(Ok(result));
+                        }
+                        Ok(Reply::Pending(work)) => {
+                            tokio::spawn(async move {
+                                let _ = incoming.reply.send(work.await);
+                            });
+                        }
+                        Err(e) => {
+                            let _ = incoming.reply.send(Err(e));
+                        }
+                    }
+                }
+
                 // Handle shutdown signals
                 Some(signal) = signals.next() => {
                     match signal {
@@ -134,30 +191,140 @@ impl DaemonService {
         let config = self.config_manager.load_config().await?;
         
         for (game_name, game_config) in &config.games {
-            if game_config.sync_enabled {
+            if game_config.sync_enabled && !self.paused.contains(game_name) {
                 info!("Starting watcher for game: {}", game_name);
-                
-                let sync_handler = GameSaveSync::new(config.clone()).await?;
-                
-                if let Err(e) = self.watcher_manager.start_watching_game(
-                    game_name.clone(),
-                    game_config.save_paths.clone(),
-                    sync_handler,
-                ).await {
-                    error!("Failed to start watcher for {}: {}", game_name, e);
-                }
+                self.start_game_watcher(&config, game_name).await?;
             }
         }
         
         Ok(())
     }
 
+    async fn start_game_watcher(&mut self, config: &SyncConfig, game_name: &str) -> Result<()> {
+        let game_config = config.games.get(game_name)
+            .context("Game not found in configuration")?;
+        let sync_handler = GameSaveSync::new(config.clone()).await?;
+
+        if let Err(e) = self.watcher_manager.start_watching_game(
+            game_name.to_string(),
+            game_config.save_paths.clone(),
+            sync_handler,
+        ).await {
+            error!("Failed to start watcher for {}: {}", game_name, e);
+        }
+        Ok(())
+    }
+
+    /// Answer a request from the control socket
+    async fn handle_request(&mut self, request: DaemonRequest) -> Result<Reply> {
+        match request {
+            DaemonRequest::Status => {
+                let config = self.config_manager.load_config().await?;
+                let watched_games = self.watcher_manager.watched_games();
+                let mut games: Vec<GameStatus> = config.games.iter()
+                    .map(|(game_name, game_config)| GameStatus {
+                        name: game_name.clone(),
+                        sync_enabled: game_config.sync_enabled,
+                        watching: watched_games.contains(game_name),
+                        paused: self.paused.contains(game_name),
+                        watch_states: self.watcher_manager.watch_states(game_name),
+                    })
+                    .collect();
+                games.sort_by(|a, b| a.name.cmp(&b.name));
+
+                Ok(Reply::Done(serde_json::to_value(DaemonStatus {
+                    pid: std::process::id(),
+                    started_at: self.started_at,
+                    games,
+                })?))
+            }
+            DaemonRequest::SyncNow { game } => {
+                let config = self.config_manager.load_config().await?;
+                let games: Vec<String> = match game {
+                    Some(game_name) => {
+                        config.games.get(&game_name)
+                            .with_context(|| format!("Game '{}' not found in configuration", game_name))?;
+                        vec![game_name]
+                    }
+                    None => config.games.iter()
+                        .filter(|(game_name, game_config)| game_config.sync_enabled && !self.paused.contains(*game_name))
+                        .map(|(game_name, _)| game_name.clone())
+                        .collect(),
+                };
+
+                let sync_lock = self.sync_lock.clone();
+                Ok(Reply::Pending(Box::pin(async move {
+                    let _guard = sync_lock.lock().await;
+                    let sync_handler = GameSaveSync::new(config).await?;
+                    let mut failed = Vec::new();
+                    for game_name in &games {
+                        info!("Syncing {} on request", game_name);
+                        if let Err(e) = sync_handler.sync_game(game_name).await {
+                            error!("Failed to sync {}: {}", game_name, e);
+                            failed.push(format!("{}: {:#}", game_name, e));
+                        }
+                    }
+                    if !failed.is_empty() {
+                        anyhow::bail!("Sync failed for {}", failed.join("; "));
+                    }
+                    Ok(serde_json::to_value(games)?)
+                })))
+            }
+            DaemonRequest::Pause { game } => {
+                let config = self.config_manager.load_config().await?;
+                if !config.games.contains_key(&game) {
+                    anyhow::bail!("Game '{}' not found in configuration", game);
+                }
+                self.watcher_manager.stop_watching_game(&game).await;
+                self.paused.insert(game.clone());
+                info!("Paused watching {}", game);
+                Ok(Reply::Done(serde_json::Value::Null))
+            }
+            DaemonRequest::Resume { game } => {
+                let config = self.config_manager.load_config().await?;
+                let game_config = config.games.get(&game)
+                    .with_context(|| format!("Game '{}' not found in configuration", game))?;
+                self.paused.remove(&game);
+                if game_config.sync_enabled && !self.watcher_manager.is_watching(&game) {
+                    self.start_game_watcher(&config, &game).await?;
+                }
+                info!("Resumed watching {}", game);
+                Ok(Reply::Done(serde_json::Value::Null))
+            }
+            DaemonRequest::ListConflicts => {
+                Ok(Reply::Done(serde_json::to_value(SyncStateStore::shared_default()?.conflicts())?))
+            }
+            DaemonRequest::ResolveConflict { game, file, resolution } => {
+                let config = self.config_manager.load_config().await?;
+                let sync_lock = self.sync_lock.clone();
+                Ok(Reply::Pending(Box::pin(async move {
+                    let _guard = sync_lock.lock().await;
+                    GameSaveSync::new(config).await?
+                        .resolve_conflict(&game, &file, resolution).await?;
+                    info!("Resolved conflict for {} ({}) on request", file, game);
+                    Ok(serde_json::Value::Null)
+                })))
+            }
+            DaemonRequest::Reload => {
+                self.reload_configuration().await?;
+                Ok(Reply::Done(serde_json::Value::Null))
+            }
+        }
+    }
+
     async fn health_check(&self) -> Result<()> {
         debug!("Performing health check");
         
         // Check if watchers are still running
         let watched_games = self.watcher_manager.watched_games();
         debug!("Currently watching {} games: {:?}", watched_games.len(), watched_games);
+        for game_name in &watched_games {
+            for (path, state) in self.watcher_manager.watch_states(game_name) {
+                if !matches!(state, WatchState::Watching { .. }) {
+                    debug!("Save path of {} not watched yet: {} ({:?})", game_name, path, state);
+                }
+            }
+        }
         
         // Verify configuration file is accessible
         if !self.config_manager.config_path().exists() {
@@ -175,18 +342,9 @@ impl DaemonService {
         
         // Check for new games to watch
         for (game_name, game_config) in &config.games {
-            if game_config.sync_enabled && !currently_watched.contains(game_name) {
+            if game_config.sync_enabled && !currently_watched.contains(game_name) && !self.paused.contains(game_name) {
                 info!("Starting watcher for new game: {}", game_name);
-                
-                let sync_handler = GameSaveSync::new(config.clone()).await?;
-                
-                if let Err(e) = self.watcher_manager.start_watching_game(
-                    game_name.clone(),
-                    game_config.save_paths.clone(),
-                    sync_handler,
-                ).await {
-                    error!("Failed to start watcher for {}: {}", game_name, e);
-                }
+                self.start_game_watcher(&config, game_name).await?;
             }
         }
         
@@ -0,0 +1,381 @@
+//! Local control API of the daemon.
+//!
+//! The daemon listens on a Unix domain socket for newline-delimited JSON-RPC
+//! 2.0 requests, so the CLI and the UI can steer the one running daemon
+//! instead of each starting their own watchers:
+//!
+//! ```text
+//! {"jsonrpc":"2.0","id":1,"method":"pause","params":{"game":"Celeste"}}
+//! {"jsonrpc":"2.0","id":1,"result":null}
+//! ```
+
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::sync_state::ConflictResolution;
+use crate::watcher::WatchState;
+
+/// Requests the daemon understands, by JSON-RPC method name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum DaemonRequest {
+    /// Returns a `DaemonStatus`
+    Status,
+    /// Sync one game, or every enabled game that is not paused
+    SyncNow {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        game: Option<String>,
+    },
+    /// Stop watching a game until it is resumed
+    Pause { game: String },
+    Resume { game: String },
+    /// Returns the pending `SyncConflict`s
+    ListConflicts,
+    /// Settle a pending conflict of one save file
+    ResolveConflict {
+        game: String,
+        file: String,
+        resolution: ConflictResolution,
+    },
+    /// Re-read the configuration, starting and stopping watchers to match
+    Reload,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Request {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(flatten)]
+    pub request: DaemonRequest,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+/// JSON-RPC error codes
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+/// The request was understood but failed, e.g. an unknown game
+pub const REQUEST_FAILED: i64 = -32000;
+
+impl Response {
+    fn new(id: Value, result: std::result::Result<Value, RpcError>) -> Self {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Self { jsonrpc: "2.0".to_string(), id, result, error }
+    }
+}
+
+/// State of the running daemon, as reported by `DaemonRequest::Status`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub pid: u32,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub games: Vec<GameStatus>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameStatus {
+    pub name: String,
+    pub sync_enabled: bool,
+    pub watching: bool,
+    pub paused: bool,
+    /// Watch state of each save path while the game is watched
+    #[serde(default)]
+    pub watch_states: BTreeMap<String, WatchState>,
+}
+
+/// Socket the daemon listens on: in the per-user runtime directory where
+/// there is one, next to the other local data otherwise
+pub fn default_socket_path() -> Result<PathBuf> {
+    let project_dirs = ProjectDirs::from("com", "decksaves", "game-sync")
+        .context("Failed to get project directories")?;
+    let dir = project_dirs.runtime_dir().unwrap_or_else(|| project_dirs.data_local_dir());
+    Ok(dir.join("daemon.sock"))
+}
+
+/// A request received by the server, answered through `reply`
+pub struct Incoming {
+    pub request: DaemonRequest,
+    pub reply: tokio::sync::oneshot::Sender<Result<Value>>,
+}
+
+/// Answer one line of a connection, forwarding valid requests to the daemon
+#[cfg_attr(not(unix), allow(dead_code))]
+async fn handle_line(line: &str, requests: &tokio::sync::mpsc::Sender<Incoming>) -> Response {
+    let value: Value = match serde_json::from_str(line) {
+        Ok(value) => value,
+        Err(e) => return Response::new(Value::Null, Err(RpcError { code: PARSE_ERROR, message: e.to_string() })),
+    };
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    let request: Request = match serde_json::from_value(value) {
+        Ok(request) => request,
+        Err(e) => return Response::new(id, Err(RpcError { code: INVALID_REQUEST, message: e.to_string() })),
+    };
+
+    let (reply, answer) = tokio::sync::oneshot::channel();
+    let failed = |message: String| Err(RpcError { code: REQUEST_FAILED, message });
+    if requests.send(Incoming { request: request.request, reply }).await.is_err() {
+        return Response::new(id, failed("Daemon is shutting down".to_string()));
+    }
+    let result = match answer.await {
+        Ok(Ok(result)) => Ok(result),
+        Ok(Err(e)) => failed(format!("{:#}", e)),
+        Err(_) => failed("Daemon dropped the request".to_string()),
+    };
+    Response::new(id, result)
+}
+
+#[cfg(unix)]
+pub use unix::{serve, DaemonClient};
+
+#[cfg(unix)]
+mod unix {
+    use super::*;
+    use std::path::Path;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{UnixListener, UnixStream};
+    use tokio::sync::mpsc;
+    use tracing::{debug, warn};
+
+    /// Listen on `socket_path`, forwarding every request to `requests`.
+    ///
+    /// Fails if another daemon is already answering on the socket; a stale
+    /// socket left by a daemon that crashed is replaced.
+    pub async fn serve(socket_path: &Path, requests: mpsc::Sender<Incoming>) -> Result<tokio::task::JoinHandle<()>> {
+        if UnixStream::connect(socket_path).await.is_ok() {
+            anyhow::bail!("Another daemon is already listening on {}", socket_path.display());
+        }
+        let _ = std::fs::remove_file(socket_path);
+        if let Some(parent) = socket_path.parent() {
+            std::fs::create_dir_all(parent)
+                .context("Failed to create socket directory")?;
+        }
+
+        let listener = UnixListener::bind(socket_path)
+            .with_context(|| format!("Failed to listen on {}", socket_path.display()))?;
+        // Only this user may steer the daemon
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(socket_path, std::fs::Permissions::from_mode(0o600))
+                .context("Failed to restrict socket permissions")?;
+        }
+
+        Ok(tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        tokio::spawn(serve_connection(stream, requests.clone()));
+                    }
+                    Err(e) => warn!("Failed to accept control connection: {}", e),
+                }
+            }
+        }))
+    }
+
+    async fn serve_connection(stream: UnixStream, requests: mpsc::Sender<Incoming>) {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = handle_line(&line, &requests).await;
+            let Ok(mut encoded) = serde_json::to_vec(&response) else {
+                continue;
+            };
+            encoded.push(b'\n');
+            if let Err(e) = writer.write_all(&encoded).await {
+                debug!("Control client went away: {}", e);
+                break;
+            }
+        }
+    }
+
+    /// Connection to a running daemon
+    pub struct DaemonClient {
+        lines: tokio::io::Lines<BufReader<tokio::net::unix::OwnedReadHalf>>,
+        writer: tokio::net::unix::OwnedWriteHalf,
+        next_id: u64,
+    }
+
+    impl DaemonClient {
+        pub async fn connect(socket_path: &Path) -> Result<Self> {
+            let stream = UnixStream::connect(socket_path).await
+                .with_context(|| format!("Daemon is not running (no socket at {})", socket_path.display()))?;
+            let (reader, writer) = stream.into_split();
+            Ok(Self { lines: BufReader::new(reader).lines(), writer, next_id: 1 })
+        }
+
+        /// Connect to the daemon of this user, if it is running
+        pub async fn connect_default() -> Result<Self> {
+            Self::connect(&default_socket_path()?).await
+        }
+
+        /// Send a request and wait for its result
+        pub async fn call(&mut self, request: DaemonRequest) -> Result<Value> {
+            let id = self.next_id;
+            self.next_id += 1;
+
+            let mut encoded = serde_json::to_vec(&Request {
+                jsonrpc: "2.0".to_string(),
+                id: Value::from(id),
+                request,
+            })?;
+            encoded.push(b'\n');
+            self.writer.write_all(&encoded).await
+                .context("Failed to send request to daemon")?;
+
+            let line = self.lines.next_line().await
+                .context("Failed to read daemon response")?
+                .context("Daemon closed the connection")?;
+            let response: Response = serde_json::from_str(&line)
+                .context("Failed to parse daemon response")?;
+            if let Some(error) = response.error {
+                anyhow::bail!("{}", error.message);
+            }
+            Ok(response.result.unwrap_or(Value::Null))
+        }
+    }
+}
+
+/// Stand-in where the control socket is not supported; connecting always fails
+#[cfg(not(unix))]
+pub struct DaemonClient {
+    _private: (),
+}
+
+#[cfg(not(unix))]
+impl DaemonClient {
+    pub async fn connect(_socket_path: &std::path::Path) -> Result<Self> {
+        anyhow::bail!("Controlling the daemon is only supported on Unix")
+    }
+
+    pub async fn connect_default() -> Result<Self> {
+        Self::connect(&default_socket_path()?).await
+    }
+
+    pub async fn call(&mut self, _request: DaemonRequest) -> Result<Value> {
+        anyhow::bail!("Controlling the daemon is only supported on Unix")
+    }
+}
+
+impl DaemonClient {
+    pub async fn status(&mut self) -> Result<DaemonStatus> {
+        Ok(serde_json::from_value(self.call(DaemonRequest::Status).await?)?)
+    }
+
+    pub async fn sync_now(&mut self, game: Option<String>) -> Result<()> {
+        self.call(DaemonRequest::SyncNow { game }).await.map(|_| ())
+    }
+
+    pub async fn pause(&mut self, game: &str) -> Result<()> {
+        self.call(DaemonRequest::Pause { game: game.to_string() }).await.map(|_| ())
+    }
+
+    pub async fn resume(&mut self, game: &str) -> Result<()> {
+        self.call(DaemonRequest::Resume { game: game.to_string() }).await.map(|_| ())
+    }
+
+    pub async fn conflicts(&mut self) -> Result<Vec<crate::SyncConflict>> {
+        Ok(serde_json::from_value(self.call(DaemonRequest::ListConflicts).await?)?)
+    }
+
+    pub async fn resolve_conflict(&mut self, game: &str, file: &str, resolution: ConflictResolution) -> Result<()> {
+        self.call(DaemonRequest::ResolveConflict {
+            game: game.to_string(),
+            file: file.to_string(),
+            resolution,
+        }).await.map(|_| ())
+    }
+
+    pub async fn reload(&mut self) -> Result<()> {
+        self.call(DaemonRequest::Reload).await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_use_json_rpc_method_and_params() {
+        let request: Request = serde_json::from_str(
+            r#"{"jsonrpc":"2.0","id":7,"method":"pause","params":{"game":"Celeste"}}"#
+        ).unwrap();
+        assert_eq!(request.id, Value::from(7));
+        assert_eq!(request.request, DaemonRequest::Pause { game: "Celeste".to_string() });
+
+        let request: Request = serde_json::from_str(r#"{"jsonrpc":"2.0","id":"a","method":"status"}"#).unwrap();
+        assert_eq!(request.request, DaemonRequest::Status);
+
+        let request: Request = serde_json::from_str(r#"{"jsonrpc":"2.0","id":1,"method":"sync_now","params":{}}"#).unwrap();
+        assert_eq!(request.request, DaemonRequest::SyncNow { game: None });
+
+        let request: Request = serde_json::from_str(
+            r#"{"jsonrpc":"2.0","id":1,"method":"resolve_conflict","params":{"game":"Celeste","file":"saves/0.celeste","resolution":"keep_local"}}"#
+        ).unwrap();
+        assert_eq!(request.request, DaemonRequest::ResolveConflict {
+            game: "Celeste".to_string(),
+            file: "saves/0.celeste".to_string(),
+            resolution: ConflictResolution::KeepLocal,
+        });
+
+        assert!(serde_json::from_str::<Request>(r#"{"jsonrpc":"2.0","id":1,"method":"format_disk"}"#).is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_client_round_trip_through_socket() {
+        let temp = tempfile::tempdir().unwrap();
+        let socket_path = temp.path().join("daemon.sock");
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<Incoming>(4);
+        let server = serve(&socket_path, tx).await.unwrap();
+
+        // A stand-in daemon answering requests
+        let daemon = tokio::spawn(async move {
+            while let Some(incoming) = rx.recv().await {
+                let result = match incoming.request {
+                    DaemonRequest::Pause { game } if game == "Celeste" => Ok(Value::Null),
+                    DaemonRequest::Pause { game } => Err(anyhow::anyhow!("Game '{}' not found", game)),
+                    DaemonRequest::ListConflicts => Ok(serde_json::json!([])),
+                    other => Err(anyhow::anyhow!("unexpected {:?}", other)),
+                };
+                let _ = incoming.reply.send(result);
+            }
+        });
+
+        // A second daemon must not take over the socket
+        let (second_tx, _second_rx) = tokio::sync::mpsc::channel::<Incoming>(1);
+        assert!(serve(&socket_path, second_tx).await.is_err());
+
+        let mut client = DaemonClient::connect(&socket_path).await.unwrap();
+        client.pause("Celeste").await.unwrap();
+        let error = client.pause("Hades").await.unwrap_err();
+        assert!(error.to_string().contains("not found"), "{}", error);
+        assert!(client.conflicts().await.unwrap().is_empty());
+
+        server.abort();
+        daemon.abort();
+    }
+}
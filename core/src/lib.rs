@@ -1,59 +1,137 @@
 use notify::{RecommendedWatcher, RecursiveMode, Watcher, Event, EventKind};
 use tokio::sync::mpsc;
 use tokio::fs;
-use tokio::time::{Duration, sleep};
+use tokio::time::sleep;
 use aws_sdk_s3::{Client, primitives::ByteStream};
 use aws_config::BehaviorVersion;
 use sha2::{Sha256, Digest};
 use anyhow::{Result, Context};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
-use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::path::{Path, PathBuf};
+use std::collections::{HashMap, HashSet};
 use tracing::{info, warn, error, debug};
 
+pub mod archive;
 pub mod config;
 pub mod sync;
 pub mod watcher;
 pub mod daemon;
+pub mod ipc;
 pub mod crypto;
 pub mod steam;
 pub mod versioning;
 pub mod storage;
 pub mod versioned_sync;
+pub mod sync_state;
+pub mod save_paths;
+pub mod settle;
+pub mod poll_watcher;
 
 use crypto::CredentialCrypto;
-pub use versioning::{VersionManager, FileVersion, GameVersionManifest, VersionConfig};
+pub use versioning::{AddedVersion, VersionManager, FileVersion, GameVersionManifest, VersionConfig};
 pub use versioned_sync::VersionedSync;
 pub use storage::providers::{StorageProvider, S3StorageProvider, LocalStorageProvider};
+pub use storage::webdav::WebDavStorageProvider;
+pub use sync_state::{ConflictPolicy, ConflictResolution, SyncConflict, SyncStateStore};
+pub use save_paths::SaveFilter;
+pub use settle::{WatchBackend, WatchSettings};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameConfig {
     pub name: String,
     pub save_paths: Vec<String>,
     pub sync_enabled: bool,
+    /// What to do when a save changed both here and in the cloud
+    #[serde(default)]
+    pub conflict_policy: ConflictPolicy,
+    /// Which files inside the save paths are synced
+    #[serde(default, flatten)]
+    pub filter: save_paths::SaveFilter,
+    /// When the file watcher considers a changed save ready to sync
+    #[serde(default, flatten)]
+    pub watch: settle::WatchSettings,
 }
 
-#[derive(Debug, Clone)]
-#[allow(dead_code)]
-struct FileInfo {
-    pub path: String,
-    pub modified_time: SystemTime,
-    pub size: u64,
-    pub hash: Option<String>,
+impl GameConfig {
+    /// Whether the filter lets a file inside the save paths sync.
+    /// Configured save paths themselves are always synced.
+    /// Copies kept when resolving a conflict are never synced.
+    pub fn should_sync(&self, file_path: &str, size: Option<u64>) -> bool {
+        match locate_in_save_roots(&self.save_paths, file_path) {
+            Some((_, _, _, inner)) if !inner.is_empty() => {
+                !sync_state::is_conflict_copy(Path::new(&inner))
+                    && (self.filter.is_empty() || self.filter.allows(&inner, size))
+            }
+            _ => true,
+        }
+    }
 }
 
+/// Remote object as seen by `head_object`
 #[derive(Debug, Clone)]
-enum SyncAction {
+struct CloudObject {
+    /// Key the object was found under (may be a legacy flat key)
+    key: String,
+    etag: Option<String>,
+    last_modified: Option<chrono::DateTime<chrono::Utc>>,
+    /// Modification time of the save on the device that uploaded it; missing
+    /// on objects uploaded by older releases
+    save_modified: Option<chrono::DateTime<chrono::Utc>>,
+    /// SHA-256 recorded at upload; missing on objects uploaded by older releases
+    content_hash: Option<String>,
+    /// Set when the object is a tombstone: the SHA-256 of the deleted save
+    deleted: Option<String>,
+}
+
+/// S3 object metadata key holding the SHA-256 of the uploaded content
+const CONTENT_HASH_METADATA_KEY: &str = "sha256";
+
+/// S3 object metadata key marking an empty object as the tombstone of a save
+/// deleted on some device, holding the SHA-256 of what was deleted
+const TOMBSTONE_METADATA_KEY: &str = "deleted";
+
+/// Days a tombstone is kept for devices that have not synced since the deletion
+const TOMBSTONE_RETENTION_DAYS: i64 = 90;
+
+/// S3 object metadata key holding the RFC 3339 modification time of the
+/// uploaded save, which unlike the object's LastModified is not the upload time
+const SAVE_MODIFIED_METADATA_KEY: &str = "mtime";
+
+/// S3 object metadata key marking an object encrypted with the configured
+/// passphrase: the body is a `PayloadCodec` blob and the hashes are sealed
+const PAYLOAD_METADATA_KEY: &str = "payload";
+const SEALED_PAYLOAD: &str = "sealed-blob";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncAction {
     UploadToS3,
     DownloadFromS3,
     NoAction,
+    /// Deleted in the cloud since the last sync
+    DeleteLocal,
+    /// Deleted locally since the last sync
+    DeleteRemote,
+    /// Both sides changed since the last sync; neither is overwritten
+    Conflict {
+        local_hash: String,
+        remote_hash: String,
+        base_hash: Option<String>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncConfig {
     pub s3_bucket: Option<String>,
     pub s3_region: Option<String>,
+    /// Custom endpoint for S3-compatible services such as MinIO or Garage
+    #[serde(default)]
+    pub s3_endpoint_url: Option<String>,
+    /// Use path-style bucket addressing (required by most self-hosted S3 servers)
+    #[serde(default)]
+    pub s3_force_path_style: bool,
+    /// Optional prefix for every object key, e.g. "decksaves/"
+    #[serde(default)]
+    pub s3_key_prefix: Option<String>,
     #[serde(default)]
     pub aws_access_key_id: Option<String>,
     #[serde(default)]
@@ -64,6 +142,21 @@ pub struct SyncConfig {
     pub use_local_storage: bool,
     #[serde(default)]
     pub local_base_path: String,
+    #[serde(default)]
+    pub webdav_url: Option<String>,
+    #[serde(default)]
+    pub webdav_username: Option<String>,
+    #[serde(default)]
+    pub webdav_password: Option<String>,
+    /// Passphrase for end-to-end encryption of remote saves (stored encrypted).
+    /// Every device syncing the same saves must use the same passphrase.
+    #[serde(default)]
+    pub encryption_passphrase: Option<String>,
+    /// Storage layout for new versions: `per_version` (default),
+    /// `content_addressed`, which stores identical content once, or `chunked`,
+    /// which also splits large files so only changed chunks are uploaded
+    #[serde(default)]
+    pub storage_layout: storage::StorageLayout,
     pub games: HashMap<String, GameConfig>,
 }
 
@@ -72,18 +165,78 @@ impl Default for SyncConfig {
         Self {
             s3_bucket: None,
             s3_region: Some("us-east-1".to_string()),
+            s3_endpoint_url: None,
+            s3_force_path_style: false,
+            s3_key_prefix: None,
             aws_access_key_id: None,
             aws_secret_access_key: None,
             peer_sync_enabled: false,
             websocket_url: None,
             use_local_storage: false,
             local_base_path: String::from("~/.decksaves"),
+            webdav_url: None,
+            webdav_username: None,
+            webdav_password: None,
+            encryption_passphrase: None,
+            storage_layout: storage::StorageLayout::PerVersion,
             games: HashMap::new(),
         }
     }
 }
 
 impl SyncConfig {
+    /// Endpoint settings for the configured S3 (or S3-compatible) service
+    pub fn s3_endpoint(&self) -> storage::S3Endpoint {
+        storage::S3Endpoint {
+            endpoint_url: self.s3_endpoint_url.clone().filter(|url| !url.is_empty()),
+            force_path_style: self.s3_force_path_style,
+            key_prefix: self.s3_key_prefix.clone(),
+        }
+    }
+
+    /// Re-encrypt stored credentials written with an older key scheme
+    /// (such as the legacy hostname/username key) using the current key source.
+    /// Returns whether anything changed so the caller can persist the config.
+    ///
+    /// Values that cannot be decrypted, e.g. legacy values after a hostname
+    /// change, are logged and kept as they are rather than failing, so the
+    /// rest of the configuration still loads.
+    pub fn migrate_credentials(&mut self) -> Result<bool> {
+        let crypto = match CredentialCrypto::new() {
+            Ok(crypto) => crypto,
+            Err(e) => {
+                warn!("Cannot migrate stored credentials: {:#}", e);
+                return Ok(false);
+            }
+        };
+        Ok(self.migrate_credentials_with(&crypto))
+    }
+
+    fn migrate_credentials_with(&mut self, crypto: &CredentialCrypto) -> bool {
+        let mut changed = false;
+
+        for (name, field) in [
+            ("aws_access_key_id", &mut self.aws_access_key_id),
+            ("aws_secret_access_key", &mut self.aws_secret_access_key),
+            ("webdav_password", &mut self.webdav_password),
+            ("encryption_passphrase", &mut self.encryption_passphrase),
+        ] {
+            if let Some(encrypted) = field.as_mut() {
+                match crypto.reencrypt(encrypted) {
+                    Ok(Some(migrated)) => {
+                        info!("Re-encrypted {} with the current credential key", name);
+                        *encrypted = migrated;
+                        changed = true;
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!("Keeping {} as stored, it cannot be decrypted: {:#}", name, e),
+                }
+            }
+        }
+
+        changed
+    }
+
     /// Get decrypted AWS access key
     pub fn get_aws_access_key(&self) -> Result<Option<String>> {
         match &self.aws_access_key_id {
@@ -106,6 +259,50 @@ impl SyncConfig {
         }
     }
 
+    /// Get decrypted WebDAV password
+    pub fn get_webdav_password(&self) -> Result<Option<String>> {
+        match &self.webdav_password {
+            Some(encrypted) if !encrypted.is_empty() => {
+                let crypto = CredentialCrypto::new()?;
+                Ok(Some(crypto.decrypt(encrypted)?))
+            }
+            _ => Ok(None)
+        }
+    }
+
+    /// Set encrypted WebDAV password
+    pub fn set_webdav_password(&mut self, password: &str) -> Result<()> {
+        if password.is_empty() {
+            self.webdav_password = None;
+        } else {
+            let crypto = CredentialCrypto::new()?;
+            self.webdav_password = Some(crypto.encrypt(password)?);
+        }
+        Ok(())
+    }
+
+    /// Get decrypted end-to-end encryption passphrase
+    pub fn get_encryption_passphrase(&self) -> Result<Option<String>> {
+        match &self.encryption_passphrase {
+            Some(encrypted) if !encrypted.is_empty() => {
+                let crypto = CredentialCrypto::new()?;
+                Ok(Some(crypto.decrypt(encrypted)?))
+            }
+            _ => Ok(None)
+        }
+    }
+
+    /// Set encrypted end-to-end encryption passphrase. An empty passphrase disables encryption.
+    pub fn set_encryption_passphrase(&mut self, passphrase: &str) -> Result<()> {
+        if passphrase.is_empty() {
+            self.encryption_passphrase = None;
+        } else {
+            let crypto = CredentialCrypto::new()?;
+            self.encryption_passphrase = Some(crypto.encrypt(passphrase)?);
+        }
+        Ok(())
+    }
+
     /// Set encrypted AWS access key
     pub fn set_aws_access_key(&mut self, key: &str) -> Result<()> {
         println!("DEBUG CRYPTO: set_aws_access_key called with key length: {}", key.len());
@@ -159,43 +356,439 @@ impl SyncConfig {
     }
 }
 
+/// Where `path` sits inside the configured save roots: `Some("")` for a root
+/// itself, `Some("profile1/save.dat")` for a path below one, `None` otherwise.
+/// The deepest matching root wins. Below a glob root the result is relative to
+/// the pattern's literal base, so it also names the match.
+fn path_within_save_roots(save_roots: &[String], path: &str) -> Option<String> {
+    locate_in_save_roots(save_roots, path).map(|(_, _, key, _)| key)
+}
+
+/// Like `path_within_save_roots`, also returning the index of the save root,
+/// the root or glob match holding the path and the path relative to it
+fn locate_in_save_roots(save_roots: &[String], path: &str) -> Option<(usize, PathBuf, String, String)> {
+    let path = Path::new(path);
+    save_roots.iter()
+        .enumerate()
+        .flat_map(|(index, root)| match save_paths::GlobPattern::parse(root) {
+            Some(pattern) => {
+                let within = pattern.match_of(path).and_then(|(matched, rest)| {
+                    let key = path.strip_prefix(pattern.base()).ok()?;
+                    Some((rest.components().count(), index, matched, slash_separated(key), slash_separated(&rest)))
+                });
+                vec![within]
+            }
+            None => [root.clone(), shellexpand::tilde(root).into_owned()]
+                .into_iter()
+                .map(|root| path.strip_prefix(&root).ok().map(|rel| {
+                    let rel_str = slash_separated(rel);
+                    (rel.components().count(), index, PathBuf::from(&root), rel_str.clone(), rel_str)
+                }))
+                .collect(),
+        })
+        .flatten()
+        .min_by_key(|(depth, _, _, _, _)| *depth)
+        .map(|(_, index, root, key, inner)| (index, root, key, inner))
+}
+
+/// Remote name of `key` within the save root at `index` in the game's save
+/// paths. Every root after the first gets its own `@<index>` namespace, so
+/// directories holding files with the same relative names do not share
+/// objects; the first root keeps the names of earlier releases. The index is
+/// used rather than the path because a save lives at different paths on
+/// different devices.
+fn namespaced_key(index: usize, key: &str) -> String {
+    match index {
+        0 => key.to_string(),
+        _ if key.is_empty() => format!("@{}", index),
+        _ => format!("@{}/{}", index, key),
+    }
+}
+
+/// Whether a remote name lies in the namespace of a save root after the first
+fn in_root_namespace(remote_name: &str) -> bool {
+    remote_name.split('/').next()
+        .and_then(|segment| segment.strip_prefix('@'))
+        .is_some_and(|index| !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Prefix of the remote names of files below `key` in the save root at `index`
+fn remote_prefix(index: usize, key: &str) -> String {
+    match namespaced_key(index, key) {
+        prefix if prefix.is_empty() => prefix,
+        prefix => format!("{}/", prefix),
+    }
+}
+
+/// Prefix of the remote names of files below a directory in the save roots
+fn remote_dir_prefix(save_roots: &[String], dir_path: &str) -> String {
+    locate_in_save_roots(save_roots, dir_path)
+        .map(|(index, _, key, _)| remote_prefix(index, &key))
+        .unwrap_or_default()
+}
+
+/// Whether the save root (or glob match) holding `path` is there on this
+/// device. A missing root that saves were synced from before is taken to be
+/// on an unmounted SD card, a wiped Proton prefix or an unplugged drive rather
+/// than deleted, and so is one whose parent directory is missing.
+fn save_root_available(save_roots: &[String], tracked_files: &[String], path: &str) -> bool {
+    let root = locate_in_save_roots(save_roots, path)
+        .map(|(_, root, _, _)| root)
+        .unwrap_or_else(|| PathBuf::from(path));
+    if root.exists() {
+        return true;
+    }
+
+    let synced_below = tracked_files.iter()
+        .map(Path::new)
+        .any(|tracked| tracked != root && tracked.starts_with(&root));
+    !synced_below && root.parent().is_some_and(Path::is_dir)
+}
+
+/// Join a relative path's components with `/`, the separator used in remote names
+fn slash_separated(rel: &Path) -> String {
+    rel.components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Remote name of a save file: its path relative to the save root containing it,
+/// with `/` separators, in the namespace of that root. A file that is itself a
+/// save root (or outside every root) uses its file name, which matches the flat
+/// keys of earlier releases.
+pub fn relative_save_path(save_roots: &[String], file_path: &str) -> Result<String> {
+    match locate_in_save_roots(save_roots, file_path) {
+        Some((index, _, rel, _)) if !rel.is_empty() => Ok(namespaced_key(index, &rel)),
+        _ => Path::new(file_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .context("Invalid file path"),
+    }
+}
+
+/// Whether a watch failed only because the path does not exist
+fn is_missing_path(error: &notify::Error) -> bool {
+    match &error.kind {
+        notify::ErrorKind::PathNotFound => true,
+        notify::ErrorKind::Io(e) => e.kind() == std::io::ErrorKind::NotFound,
+        _ => false,
+    }
+}
+
+/// Flat name a save file had before keys preserved the directory structure,
+/// where that differs from its remote name. Only a file directly in a save
+/// root (or glob match) is looked up by it: a nested file's flat name is the
+/// key of a file in the first root. Neither is it once another save file,
+/// synced before (`tracked_files`) or present locally, owns the name.
+fn legacy_flat_name(save_roots: &[String], tracked_files: &[String], file_path: &str) -> Option<String> {
+    let (index, _, key, inner) = locate_in_save_roots(save_roots, file_path)?;
+    if inner.is_empty() || inner.contains('/') || namespaced_key(index, &key) == inner {
+        return None;
+    }
+
+    let local_files = save_roots.iter()
+        .filter(|root| save_paths::GlobPattern::parse(root).is_none())
+        .map(|root| PathBuf::from(shellexpand::tilde(root).into_owned()))
+        .map(|root| if root.is_dir() { root.join(&inner) } else { root })
+        .filter(|path| path.is_file());
+    let owned = tracked_files.iter()
+        .map(PathBuf::from)
+        .chain(local_files)
+        .filter(|path| path != Path::new(file_path))
+        .any(|path| path.to_str()
+            .and_then(|path| relative_save_path(save_roots, path).ok())
+            .is_some_and(|remote_name| remote_name == inner));
+    (!owned).then_some(inner)
+}
+
+/// Save paths that appeared or went away, as noticed by `FileWatcher::heal`
+#[derive(Debug, Default, PartialEq)]
+pub struct HealedPaths {
+    /// Paths watched again, to be synced in full
+    pub attached: Vec<PathBuf>,
+    /// Watched save paths or glob matches that disappeared, such as with an
+    /// ejected SD card. Changes below them are not deletions.
+    pub vanished: Vec<PathBuf>,
+}
+
 pub struct FileWatcher {
-    watcher: Option<RecommendedWatcher>,
+    watcher: Option<Box<dyn Watcher + Send>>,
+    /// Whether `watcher` is the polling backend
+    polling: bool,
+    settings: settle::WatchSettings,
     event_tx: mpsc::Sender<Event>,
+    /// Glob save paths as configured, re-expanded as matching directories appear
+    patterns: Vec<(String, save_paths::GlobPattern)>,
+    /// Save paths without wildcards, as configured
+    roots: Vec<String>,
+    watched: HashMap<PathBuf, RecursiveMode>,
+    /// Save paths and glob matches that disappeared while watched
+    vanished: HashSet<PathBuf>,
+    states: watcher::WatchStates,
 }
 
 impl FileWatcher {
     pub fn new() -> Result<(Self, mpsc::Receiver<Event>)> {
+        Self::with_settings(settle::WatchSettings::default())
+    }
+
+    /// Watcher using a game's choice of backend
+    pub fn with_settings(settings: settle::WatchSettings) -> Result<(Self, mpsc::Receiver<Event>)> {
         let (tx, rx) = mpsc::channel(100);
         Ok((
             Self {
                 watcher: None,
+                polling: false,
+                settings,
                 event_tx: tx,
+                patterns: Vec::new(),
+                roots: Vec::new(),
+                watched: HashMap::new(),
+                vanished: HashSet::new(),
+                states: watcher::WatchStates::default(),
             },
             rx,
         ))
     }
 
+    /// Whether changes are noticed by polling rather than the OS watcher
+    pub fn is_polling(&self) -> bool {
+        self.polling
+    }
+
+    /// Shared view of the watch state of each configured path, kept up to
+    /// date while the watcher runs
+    pub fn states(&self) -> watcher::WatchStates {
+        self.states.clone()
+    }
+
+    /// Watch a save path. A path that does not exist yet is waited for by
+    /// watching its nearest existing ancestor.
     pub async fn watch_path(&mut self, path: &str) -> Result<()> {
+        if let Some(pattern) = save_paths::GlobPattern::parse(path) {
+            self.patterns.push((path.to_string(), pattern));
+            let matches = self.refresh_glob_watches()?;
+            info!("Started watching glob path: {} ({} matches)", path, matches.len());
+            return Ok(());
+        }
+
+        self.roots.push(path.to_string());
+        if self.attach_root(path)? {
+            info!("Started watching path: {}", path);
+        }
+        Ok(())
+    }
+
+    /// Watch a configured path if it exists, and otherwise the nearest
+    /// existing ancestor it may appear in. Returns whether the path itself
+    /// is now watched.
+    fn attach_root(&mut self, configured: &str) -> Result<bool> {
+        let path = Path::new(configured);
+        if path.exists() {
+            match self.add_watch(path, RecursiveMode::Recursive) {
+                Ok(_) => {
+                    self.states.set(configured, watcher::WatchState::Watching { polling: self.polling });
+                    return Ok(true);
+                }
+                // Deleted again before the watch was added
+                Err(_) if !path.exists() => {}
+                Err(e) => {
+                    self.states.set(configured, watcher::WatchState::Failed { error: e.to_string() });
+                    return Err(e);
+                }
+            }
+        }
+
+        let Some(ancestor) = path.ancestors().skip(1).find(|dir| dir.is_dir()) else {
+            let error = "No existing parent directory to wait in".to_string();
+            self.states.set(configured, watcher::WatchState::Failed { error: error.clone() });
+            anyhow::bail!("{}: {}", error, configured);
+        };
+        let ancestor = ancestor.to_path_buf();
+        if let Err(e) = self.add_watch(&ancestor, RecursiveMode::NonRecursive) {
+            self.states.set(configured, watcher::WatchState::Failed { error: e.to_string() });
+            return Err(e);
+        }
+
+        let waiting = watcher::WatchState::Waiting { ancestor: ancestor.clone() };
+        if self.states.get(configured).as_ref() != Some(&waiting) {
+            info!("Save path {} does not exist yet, waiting for it in {}", configured, ancestor.display());
+            self.states.set(configured, waiting);
+        }
+        Ok(false)
+    }
+
+    /// Follow save paths appearing and disappearing after an event: paths
+    /// that now exist are attached, and watched paths and glob matches that
+    /// were deleted go back to waiting.
+    pub fn heal(&mut self, event: &Event) -> HealedPaths {
+        let mut healed = HealedPaths::default();
+        if !matches!(event.kind, EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(notify::event::ModifyKind::Name(_))) {
+            return healed;
+        }
+
+        for root in self.roots.clone() {
+            let path = Path::new(&root);
+            if matches!(self.states.get(&root), Some(watcher::WatchState::Watching { .. })) {
+                if path.exists() {
+                    continue;
+                }
+                warn!("Watched save path was removed: {}", root);
+                self.remove_watch(path);
+                self.vanished.insert(path.to_path_buf());
+                healed.vanished.push(path.to_path_buf());
+            }
+
+            // Waiting paths are re-attached too, as a deeper ancestor may now exist
+            match self.attach_root(&root) {
+                Ok(true) => {
+                    info!("Save path appeared, now watching: {}", root);
+                    self.vanished.remove(path);
+                    healed.attached.push(path.to_path_buf());
+                }
+                Ok(false) => {}
+                Err(e) => warn!("Failed to watch {}: {}", root, e),
+            }
+        }
+
+        // Glob matches are watched recursively; partial match directories are not
+        for path in &event.paths {
+            if self.watched.get(path) == Some(&RecursiveMode::Recursive)
+                && !self.roots.iter().any(|root| Path::new(root) == path)
+                && !path.exists()
+            {
+                warn!("Watched save directory was removed: {}", path.display());
+                self.remove_watch(path);
+                self.vanished.insert(path.clone());
+                healed.vanished.push(path.clone());
+            }
+        }
+
+        healed
+    }
+
+    /// Whether a path is below a save path or glob match that disappeared
+    /// and has not come back
+    pub fn is_vanished(&self, path: &Path) -> bool {
+        self.vanished.iter().any(|vanished| path.starts_with(vanished))
+    }
+
+    pub fn has_glob_paths(&self) -> bool {
+        !self.patterns.is_empty()
+    }
+
+    /// Re-expand the glob save paths. New matches are watched recursively, and
+    /// the directories further matches could appear in are watched without
+    /// recursion. Returns the matches that were not watched before.
+    pub fn refresh_glob_watches(&mut self) -> Result<Vec<PathBuf>> {
+        let mut new_matches = Vec::new();
+
+        for (configured, pattern) in self.patterns.clone() {
+            // Until the literal part of the pattern exists, wait for it in
+            // its nearest existing ancestor
+            if !pattern.base().is_dir() {
+                if let Some(ancestor) = pattern.base().ancestors().skip(1).find(|dir| dir.is_dir()) {
+                    self.add_watch(ancestor, RecursiveMode::NonRecursive)?;
+                    self.states.set(&configured, watcher::WatchState::Waiting { ancestor: ancestor.to_path_buf() });
+                }
+                continue;
+            }
+
+            for dir in pattern.partial_match_dirs() {
+                self.add_watch(&dir, RecursiveMode::NonRecursive)?;
+            }
+            for matched in pattern.matches() {
+                if self.add_watch(&matched, RecursiveMode::Recursive)? {
+                    debug!("Watching new glob match: {:?}", matched);
+                    self.vanished.remove(&matched);
+                    new_matches.push(matched);
+                }
+            }
+            self.states.set(&configured, watcher::WatchState::Watching { polling: self.polling });
+        }
+
+        Ok(new_matches)
+    }
+
+    /// Drop a deleted path so it is watched again if it reappears
+    pub fn forget_path(&mut self, path: &Path) {
+        self.watched.remove(path);
+    }
+
+    /// Stop watching a path, e.g. after it was deleted
+    fn remove_watch(&mut self, path: &Path) {
+        if self.watched.remove(path).is_some() {
+            if let Some(watcher) = self.watcher.as_mut() {
+                // The OS watcher may already have dropped a deleted path
+                let _ = watcher.unwatch(path);
+            }
+        }
+    }
+
+    fn add_watch(&mut self, path: &Path, mode: RecursiveMode) -> Result<bool> {
+        match self.watched.get(path) {
+            Some(RecursiveMode::Recursive) => return Ok(false),
+            Some(_) if mode == RecursiveMode::NonRecursive => return Ok(false),
+            // Watched as an ancestor before, now needed recursively
+            Some(_) => self.remove_watch(path),
+            None => {}
+        }
+
+        let result = match self.watcher.as_mut() {
+            Some(watcher) => watcher.watch(path, mode),
+            None => self.start_watcher(self.settings.watch_backend == settle::WatchBackend::Polling)
+                .and_then(|watcher| watcher.watch(path, mode)),
+        };
+
+        match result {
+            Ok(()) => {}
+            // A missing path is not a problem with the OS watcher itself
+            Err(e) if self.polling
+                || self.settings.watch_backend != settle::WatchBackend::Auto
+                || is_missing_path(&e) => return Err(e.into()),
+            Err(e) => {
+                warn!("File watching failed for {}, switching to polling: {}", path.display(), e);
+                self.start_watcher(true)?;
+                self.states.mark_polling();
+                let watcher = self.watcher.as_mut().context("Polling watcher not started")?;
+                for (watched, watched_mode) in &self.watched {
+                    if let Err(e) = watcher.watch(watched, *watched_mode) {
+                        warn!("Failed to poll {}: {}", watched.display(), e);
+                    }
+                }
+                watcher.watch(path, mode)?;
+            }
+        }
+
+        self.watched.insert(path.to_path_buf(), mode);
+        Ok(true)
+    }
+
+    /// Replace the current watcher with a new one of the given backend
+    fn start_watcher(&mut self, polling: bool) -> notify::Result<&mut Box<dyn Watcher + Send>> {
         let tx = self.event_tx.clone();
-        let mut watcher: RecommendedWatcher = Watcher::new(
-            move |res: notify::Result<Event>| {
-                match res {
-                    Ok(event) => {
-                        if let Err(e) = tx.blocking_send(event) {
-                            error!("Failed to send file event: {}", e);
-                        }
+        let handler = move |res: notify::Result<Event>| {
+            match res {
+                Ok(event) => {
+                    if let Err(e) = tx.blocking_send(event) {
+                        error!("Failed to send file event: {}", e);
                     }
-                    Err(e) => error!("Watch error: {}", e),
                 }
-            },
-            notify::Config::default(),
-        )?;
+                Err(e) => error!("Watch error: {}", e),
+            }
+        };
 
-        watcher.watch(Path::new(path), RecursiveMode::Recursive)?;
-        self.watcher = Some(watcher);
-        info!("Started watching path: {}", path);
-        Ok(())
+        let watcher: Box<dyn Watcher + Send> = if polling {
+            let config = notify::Config::default().with_poll_interval(self.settings.poll_interval());
+            info!("Polling for save changes every {:?}", self.settings.poll_interval());
+            Box::new(poll_watcher::PollingWatcher::new(handler, config)?)
+        } else {
+            Box::new(RecommendedWatcher::new(handler, notify::Config::default())?)
+        };
+
+        self.polling = polling;
+        Ok(self.watcher.insert(watcher))
     }
 }
 
@@ -208,8 +801,31 @@ impl VersionedGameSaveSync {
     pub async fn new(config: SyncConfig) -> Result<Self> {
         debug!("Creating VersionedGameSaveSync with bucket: {:?}", config.s3_bucket);
         
+        // Remote saves are encrypted end to end whenever a passphrase is configured
+        let encryption_passphrase = config.get_encryption_passphrase()
+            .context("Failed to get encryption passphrase")?;
+        let encryption_enabled = encryption_passphrase.is_some();
+
         // Create storage provider based on configuration
-        let storage_config = if config.use_local_storage || config.s3_bucket.is_none() {
+        let webdav_url = config.webdav_url.as_ref().filter(|url| !url.is_empty());
+        let storage_config = if !config.use_local_storage && config.s3_bucket.is_none() && webdav_url.is_some() {
+            debug!("Setting up WebDAV storage config");
+
+            storage::StorageConfig {
+                backend: storage::StorageBackend::WebDAV {
+                    base_url: webdav_url.cloned().unwrap_or_default(),
+                    username: config.webdav_username.clone().unwrap_or_default(),
+                    password: config.get_webdav_password().context("Failed to get WebDAV password")?,
+                },
+                connection_timeout_seconds: 30,
+                retry_attempts: 3,
+                enable_compression: true,
+                compression_codec: storage::CompressionCodec::Zstd,
+                encryption_enabled,
+                encryption_passphrase: encryption_passphrase.clone(),
+                layout: config.storage_layout,
+            }
+        } else if config.use_local_storage || config.s3_bucket.is_none() {
             debug!("Setting up local storage config");
             
             // Use configured local path or default
@@ -230,7 +846,10 @@ impl VersionedGameSaveSync {
                 connection_timeout_seconds: 30,
                 retry_attempts: 3,
                 enable_compression: true,
+                compression_codec: storage::CompressionCodec::Zstd,
                 encryption_enabled: false,
+                encryption_passphrase: None,
+                layout: config.storage_layout,
             }
         } else if let Some(bucket) = &config.s3_bucket {
             debug!("Setting up S3 storage config");
@@ -239,11 +858,17 @@ impl VersionedGameSaveSync {
                 backend: storage::StorageBackend::S3 {
                     bucket: bucket.clone(),
                     region: config.s3_region.clone().unwrap_or_else(|| "us-east-1".to_string()),
+                    endpoint_url: config.s3_endpoint().endpoint_url,
+                    force_path_style: config.s3_force_path_style,
+                    key_prefix: config.s3_key_prefix.clone(),
                 },
                 connection_timeout_seconds: 30,
                 retry_attempts: 3,
                 enable_compression: true,
-                encryption_enabled: true,
+                compression_codec: storage::CompressionCodec::Zstd,
+                encryption_enabled,
+                encryption_passphrase: encryption_passphrase.clone(),
+                layout: config.storage_layout,
             }
         } else {
             debug!("No storage config found, defaulting to local storage");
@@ -260,7 +885,10 @@ impl VersionedGameSaveSync {
                 connection_timeout_seconds: 30,
                 retry_attempts: 3,
                 enable_compression: true,
+                compression_codec: storage::CompressionCodec::Zstd,
                 encryption_enabled: false,
+                encryption_passphrase: None,
+                layout: config.storage_layout,
             }
         };
         
@@ -291,7 +919,8 @@ impl VersionedGameSaveSync {
             return Ok(());
         }
 
-        let save_paths = game_config.save_paths.clone();
+        // Glob save paths are synced through their current matches
+        let save_paths = save_paths::expand_save_paths(&game_config.save_paths);
         debug!("Processing {} save paths for game: {}", save_paths.len(), game_name);
         
         for save_path in &save_paths {
@@ -334,18 +963,23 @@ impl VersionedGameSaveSync {
 
         // Create a temporary archive of the entire directory
         let temp_dir = std::env::temp_dir();
-        let archive_name = format!("{}_{}.tar.gz", game_name, chrono::Utc::now().timestamp());
+        let archive_name = format!("{}_{}.tar", game_name, chrono::Utc::now().timestamp());
         let archive_path = temp_dir.join(&archive_name);
         
-        // Create tar.gz archive of the directory
-        self.create_directory_archive(dir_path, &archive_path).await?;
+        // Create tar archive of the directory
+        let content_hash = self.create_directory_archive(dir_path, &archive_path, game_name).await?;
         
-        // Store the archive as a single version with the game name as the relative path
-        let relative_path = game_name.to_string();
+        // Store the archive as a single version keyed by the game name (or by the
+        // match, for directories matched by a glob save path)
+        let relative_path = self.snapshot_key(game_name, dir_path);
         
-        match self.versioned_sync.sync_file_to_storage(&archive_path, &relative_path, Some("Directory snapshot".to_string())).await {
-            Ok(_) => {
-                info!("Successfully created directory snapshot for: {}", dir_path);
+        match self.versioned_sync.sync_snapshot_to_storage(&archive_path, &relative_path, Some(content_hash), Some("Directory snapshot".to_string())).await {
+            Ok(added) => {
+                if added.unchanged {
+                    info!("Directory {} is unchanged since snapshot {}", dir_path, added.version.version_id);
+                } else {
+                    info!("Successfully created directory snapshot for: {}", dir_path);
+                }
                 // Clean up temporary archive
                 if archive_path.exists() {
                     std::fs::remove_file(&archive_path).ok();
@@ -363,35 +997,108 @@ impl VersionedGameSaveSync {
         }
     }
 
-    async fn create_directory_archive(&self, source_dir: &str, archive_path: &Path) -> Result<()> {
-        use std::process::Command;
-        
+    /// Archive a save directory, returning the hash of the archived content
+    async fn create_directory_archive(&self, source_dir: &str, archive_path: &Path, game_name: &str) -> Result<String> {
         debug!("Creating archive: {} -> {}", source_dir, archive_path.display());
+
+        let source = PathBuf::from(source_dir);
+        let archive_path = archive_path.to_path_buf();
+        let game = self.config.games.get(game_name).cloned();
+
+        // Snapshots stay gzip so releases that extract with `tar -xzf` can
+        // restore them. Chunked snapshots are stored uncompressed instead:
+        // compression changes the whole stream after the first changed file,
+        // so consecutive snapshots would share no chunks. The chunks
+        // themselves are compressed as they are stored.
+        let codec = match self.config.storage_layout {
+            storage::StorageLayout::Chunked => storage::CompressionCodec::None,
+            _ => storage::CompressionCodec::Gzip,
+        };
+
+        let (summary, content_hash) = tokio::task::spawn_blocking(move || -> Result<_> {
+            let summary = archive::create_archive(
+                &source,
+                &archive_path,
+                codec,
+                |rel, metadata| {
+                    let full_path = source.join(rel);
+                    let allowed = game.as_ref()
+                        .is_none_or(|game| game.should_sync(&full_path.to_string_lossy(), Some(metadata.len())));
+                    if !allowed {
+                        debug!("Leaving filtered file out of snapshot: {}", full_path.display());
+                    }
+                    allowed
+                },
+                |progress| debug!("Archived {} ({} bytes, {} files so far)", progress.path.display(), progress.size, progress.files_done),
+            )?;
+            Ok((summary, archive::content_hash(&archive_path)?))
+        })
+        .await
+        .context("Archive task failed")??;
         
-        // Use tar command to create the archive
-        let output = Command::new("tar")
-            .args(["-czf", &archive_path.to_string_lossy(), "-C", source_dir, "."])
-            .output()
-            .context("Failed to execute tar command")?;
-        
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow::anyhow!("tar command failed: {}", stderr));
+        debug!("Successfully created archive of {} files ({} bytes)", summary.files, summary.bytes);
+        Ok(content_hash)
+    }
+
+    /// Manifest key for a directory snapshot: the game name for the first save
+    /// path, `game/@<index>` for later ones, or `game/<match>` for a directory
+    /// matched by a glob save path
+    fn snapshot_key(&self, game_name: &str, dir_path: &str) -> String {
+        let save_roots = self.config.games.get(game_name)
+            .map(|game| game.save_paths.as_slice())
+            .unwrap_or_default();
+        let matched_by_glob = save_roots.iter()
+            .filter_map(|root| save_paths::GlobPattern::parse(root))
+            .any(|pattern| pattern.match_of(Path::new(dir_path)).is_some_and(|(_, rest)| rest.as_os_str().is_empty()));
+
+        // Later roots snapshot into their own namespace, like their files
+        match locate_in_save_roots(save_roots, dir_path) {
+            Some((index, _, rel, _)) if matched_by_glob && !rel.is_empty() => {
+                format!("{}/{}", game_name, namespaced_key(index, &rel))
+            }
+            Some((index, _, rel, _)) if rel.is_empty() && index > 0 => {
+                format!("{}/{}", game_name, namespaced_key(index, ""))
+            }
+            _ => game_name.to_string(),
+        }
+    }
+
+    /// Manifest key for a single save file: `game/<path relative to its save root>`
+    fn versioned_file_key(&self, game_name: &str, file_path: &str) -> Result<String> {
+        let save_roots = self.config.games.get(game_name)
+            .map(|game| game.save_paths.as_slice())
+            .unwrap_or_default();
+        Ok(format!("{}/{}", game_name, relative_save_path(save_roots, file_path)?))
+    }
+
+    /// Manifest key that holds the versions of a save file, falling back to the
+    /// flat `game/<file name>` key written by earlier releases
+    fn existing_versioned_file_key(&self, game_name: &str, file_path: &str) -> Result<String> {
+        let key = self.versioned_file_key(game_name, file_path)?;
+        if self.versioned_sync.list_versions(&key).is_some() {
+            return Ok(key);
+        }
+
+        let save_roots = self.config.games.get(game_name)
+            .map(|game| game.save_paths.as_slice())
+            .unwrap_or_default();
+        match legacy_flat_name(save_roots, &[], file_path).map(|name| format!("{}/{}", game_name, name)) {
+            Some(flat) if self.versioned_sync.list_versions(&flat).is_some() => Ok(flat),
+            _ => Ok(key),
         }
-        
-        debug!("Successfully created archive: {}", archive_path.display());
-        Ok(())
     }
 
     async fn sync_single_file_with_versioning(&mut self, file_path: &str, game_name: &str) -> Result<()> {
         println!("DEBUG sync_single_file_with_versioning: file_path='{}', game_name='{}'", file_path, game_name);
         let path = Path::new(file_path);
-        let relative_path = game_name.to_string() + "/" + &path.file_name()
-            .context("Invalid file path")?
-            .to_string_lossy();
+        let relative_path = self.versioned_file_key(game_name, file_path)?;
         println!("DEBUG sync_single_file_with_versioning: constructed relative_path='{}'", relative_path);
         
         match self.versioned_sync.sync_file_to_storage(path, &relative_path, Some("Auto-sync".to_string())).await {
+            Ok(added) if added.unchanged => {
+                debug!("File unchanged since version {}: {}", added.version.version_id, file_path);
+                Ok(())
+            }
             Ok(_) => {
                 info!("Successfully synced file with versioning: {}", file_path);
                 Ok(())
@@ -428,9 +1135,7 @@ impl VersionedGameSaveSync {
         debug!("Attempting to download latest version for missing file: {}", file_path);
         
         let path = Path::new(file_path);
-        let relative_path = game_name.to_string() + "/" + &path.file_name()
-            .context("Invalid file path")?
-            .to_string_lossy();
+        let relative_path = self.existing_versioned_file_key(game_name, file_path)?;
         
         // Try to get the latest version and download it
         match self.versioned_sync.download_latest(&relative_path, path).await {
@@ -455,7 +1160,7 @@ impl VersionedGameSaveSync {
             println!("DEBUG get_version_history: path is a directory, looking for game-level versions for '{}'", game_name);
             
             // Try to get versions for the game name directly (directory snapshots)
-            let relative_path = game_name.to_string();
+            let relative_path = self.snapshot_key(game_name, file_path);
             let versions = self.versioned_sync.list_versions(&relative_path)
                 .map(|versions| versions.clone())
                 .unwrap_or_default();
@@ -480,7 +1185,7 @@ impl VersionedGameSaveSync {
             .to_string_lossy();
         
         // Try with the provided game name first
-        let relative_path = game_name.to_string() + "/" + &filename;
+        let relative_path = self.existing_versioned_file_key(game_name, file_path)?;
         println!("DEBUG get_version_history: trying relative_path='{}'", relative_path);
         let mut versions = self.versioned_sync.list_versions(&relative_path)
             .map(|versions| versions.clone())
@@ -503,7 +1208,7 @@ impl VersionedGameSaveSync {
         println!("DEBUG restore_version: game_name='{}', file_path='{}', version_id='{}'", game_name, file_path, version_id);
         
         // Check if we have a directory snapshot for this game
-        let relative_path = game_name.to_string();
+        let relative_path = self.snapshot_key(game_name, file_path);
         
         // Try to restore the directory snapshot
         let temp_dir = std::env::temp_dir();
@@ -556,9 +1261,7 @@ impl VersionedGameSaveSync {
                 }
                 
                 // Handle individual file (original logic)
-                let relative_path = game_name.to_string() + "/" + &path.file_name()
-                    .context("Invalid file path")?
-                    .to_string_lossy();
+                let relative_path = self.existing_versioned_file_key(game_name, file_path)?;
                 
                 self.versioned_sync.download_version(&relative_path, version_id, path).await
             }
@@ -566,25 +1269,19 @@ impl VersionedGameSaveSync {
     }
 
     async fn extract_directory_archive(&self, archive_path: &Path, target_dir: &str) -> Result<()> {
-        use std::process::Command;
-        
         debug!("Extracting archive: {} -> {}", archive_path.display(), target_dir);
+
+        let archive_path = archive_path.to_path_buf();
+        let target = PathBuf::from(target_dir);
+        let summary = tokio::task::spawn_blocking(move || {
+            archive::extract_archive(&archive_path, &target, |progress| {
+                debug!("Restored {} ({} bytes, {} files so far)", progress.path.display(), progress.size, progress.files_done)
+            })
+        })
+        .await
+        .context("Archive task failed")??;
         
-        // Create target directory if it doesn't exist
-        std::fs::create_dir_all(target_dir).context("Failed to create target directory")?;
-        
-        // Use tar command to extract the archive
-        let output = Command::new("tar")
-            .args(["-xzf", &archive_path.to_string_lossy(), "-C", target_dir])
-            .output()
-            .context("Failed to execute tar command")?;
-        
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow::anyhow!("tar extract command failed: {}", stderr));
-        }
-        
-        debug!("Successfully extracted archive to: {}", target_dir);
+        debug!("Successfully extracted {} files to: {}", summary.files, target_dir);
         Ok(())
     }
 
@@ -598,7 +1295,7 @@ impl VersionedGameSaveSync {
             println!("DEBUG pin_version: path is a directory, trying game-level version pinning");
             
             // Try to pin as a game-level version (directory snapshot)
-            let relative_path = game_name.to_string();
+            let relative_path = self.snapshot_key(game_name, file_path);
             match self.versioned_sync.pin_version(&relative_path, version_id).await {
                 Ok(_) => {
                     println!("DEBUG pin_version: successfully pinned game-level version");
@@ -611,10 +1308,7 @@ impl VersionedGameSaveSync {
         }
         
         // Fallback to individual file approach for backward compatibility
-        let filename = path.file_name()
-            .context("Invalid file path")?
-            .to_string_lossy();
-        let relative_path = game_name.to_string() + "/" + &filename;
+        let relative_path = self.existing_versioned_file_key(game_name, file_path)?;
         
         println!("DEBUG pin_version: trying individual file approach with path: {}", relative_path);
         self.versioned_sync.pin_version(&relative_path, version_id).await
@@ -630,7 +1324,7 @@ impl VersionedGameSaveSync {
             println!("DEBUG delete_version: path is a directory, trying game-level version deletion");
             
             // Try to delete as a game-level version (directory snapshot)
-            let relative_path = game_name.to_string();
+            let relative_path = self.snapshot_key(game_name, file_path);
             match self.versioned_sync.delete_version(&relative_path, version_id).await {
                 Ok(_) => {
                     println!("DEBUG delete_version: successfully deleted game-level version");
@@ -678,9 +1372,7 @@ impl VersionedGameSaveSync {
         }
         
         // Handle individual file
-        let relative_path = game_name.to_string() + "/" + &path.file_name()
-            .context("Invalid file path")?
-            .to_string_lossy();
+        let relative_path = self.existing_versioned_file_key(game_name, file_path)?;
         
         self.versioned_sync.delete_version(&relative_path, version_id).await
     }
@@ -693,9 +1385,7 @@ impl VersionedGameSaveSync {
         debug!("Attempting to download latest version for missing file: {}", file_path);
         
         let path = Path::new(file_path);
-        let relative_path = game_name.to_string() + "/" + &path.file_name()
-            .context("Invalid file path")?
-            .to_string_lossy();
+        let relative_path = self.existing_versioned_file_key(game_name, file_path)?;
         
         // Try to get the latest version and download it
         match self.versioned_sync.download_latest(&relative_path, path).await {
@@ -714,11 +1404,28 @@ impl VersionedGameSaveSync {
 pub struct GameSaveSync {
     config: SyncConfig,
     s3_client: Option<Client>,
+    key_prefix: String,
+    sync_state: std::sync::Arc<SyncStateStore>,
+    payload: storage::PayloadCodec,
+    retry: storage::RetryPolicy,
 }
 
 impl GameSaveSync {
     pub async fn new(config: SyncConfig) -> Result<Self> {
         debug!("Creating GameSaveSync with bucket: {:?}", config.s3_bucket);
+
+        // Saves are encrypted end to end whenever a passphrase is configured,
+        // and requests time out and retry, the same as with the versioned
+        // storage providers
+        let encryption_passphrase = config.get_encryption_passphrase()
+            .context("Failed to get encryption passphrase")?;
+        let storage_config = storage::StorageConfig {
+            encryption_enabled: encryption_passphrase.is_some(),
+            encryption_passphrase,
+            ..storage::StorageConfig::default()
+        };
+        let payload = storage::PayloadCodec::from_config(&storage_config)?;
+        let retry = storage::RetryPolicy::from_config(&storage_config);
         
         let s3_client = if config.s3_bucket.is_some() {
             debug!("S3 bucket configured, setting up client...");
@@ -750,18 +1457,56 @@ impl GameSaveSync {
             } else {
                 debug!("Using default AWS credentials (IAM role, environment, etc.)");
                 // Use default credentials (IAM role, environment, etc.)
-                aws_config::defaults(BehaviorVersion::latest())
+                let mut loader = aws_config::defaults(BehaviorVersion::latest());
+                if let Some(region) = &config.s3_region {
+                    loader = loader.region(aws_config::Region::new(region.clone()));
+                }
+                loader
                     .load()
                     .await
             };
             
-            Some(Client::new(&aws_config))
+            let endpoint = config.s3_endpoint();
+            if let Some(endpoint_url) = &endpoint.endpoint_url {
+                debug!("Using custom S3 endpoint: {} (path-style: {})", endpoint_url, endpoint.force_path_style);
+            }
+            Some(endpoint.build_client(&aws_config, storage_config.idle_timeout()))
         } else {
             debug!("No S3 bucket configured, skipping S3 client setup");
             None
         };
 
-        Ok(Self { config, s3_client })
+        let key_prefix = config.s3_endpoint().normalized_prefix();
+        let sync_state = SyncStateStore::shared_default()
+            .context("Failed to load sync state")?;
+
+        Ok(Self { config, s3_client, key_prefix, sync_state, payload, retry })
+    }
+
+    /// Files that changed on both sides and are waiting to be resolved
+    pub fn conflicts(&self) -> Vec<SyncConflict> {
+        self.sync_state.conflicts()
+    }
+
+    /// Object key for a save file, including the configured key prefix
+    fn object_key(&self, game_name: &str, remote_name: &str) -> String {
+        format!("{}{}/{}", self.key_prefix, game_name, remote_name)
+    }
+
+    /// Remote name of a local save file, relative to its configured save root
+    fn remote_name(&self, game_name: &str, file_path: &str) -> Result<String> {
+        let save_roots = self.config.games.get(game_name)
+            .map(|game| game.save_paths.as_slice())
+            .unwrap_or_default();
+        relative_save_path(save_roots, file_path)
+    }
+
+    /// Flat name an earlier release may have uploaded a save file under
+    fn legacy_name(&self, game_name: &str, file_path: &str) -> Option<String> {
+        let save_roots = self.config.games.get(game_name)
+            .map(|game| game.save_paths.as_slice())
+            .unwrap_or_default();
+        legacy_flat_name(save_roots, &self.sync_state.tracked_files(game_name), file_path)
     }
 
     pub async fn sync_game(&self, game_name: &str) -> Result<()> {
@@ -775,7 +1520,13 @@ impl GameSaveSync {
         }
 
         debug!("Processing {} save paths for game: {}", game_config.save_paths.len(), game_name);
-        for save_path in &game_config.save_paths {
+        for (index, save_path) in game_config.save_paths.iter().enumerate() {
+            if let Some(pattern) = save_paths::GlobPattern::parse(save_path) {
+                debug!("Syncing glob save path: {}", save_path);
+                self.sync_glob_save_path(index, &pattern, game_name).await?;
+                continue;
+            }
+
             debug!("Syncing save path: {}", save_path);
             self.sync_file(save_path, game_name).await?;
             debug!("Completed syncing save path: {}", save_path);
@@ -785,15 +1536,66 @@ impl GameSaveSync {
         Ok(())
     }
 
-    async fn sync_file(&self, file_path: &str, game_name: &str) -> Result<()> {
-        debug!("sync_file called for: {} (game: {})", file_path, game_name);
-        
-        let path = Path::new(file_path);
-        
+    /// Whether a path is one of the game's save paths or inside one
+    pub fn is_in_save_paths(&self, game_name: &str, file_path: &str) -> bool {
+        self.config.games.get(game_name)
+            .is_some_and(|game| path_within_save_roots(&game.save_paths, file_path).is_some())
+    }
+
+    /// Whether the game's include/exclude filter lets this file sync
+    pub fn should_sync(&self, game_name: &str, file_path: &str) -> bool {
+        let Some(game) = self.config.games.get(game_name) else {
+            return true;
+        };
+        let size = std::fs::metadata(file_path).ok()
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len());
+        game.should_sync(file_path, size)
+    }
+
+    /// Watcher tuning for a game, with defaults for unknown games
+    pub fn watch_settings(&self, game_name: &str) -> settle::WatchSettings {
+        self.config.games.get(game_name)
+            .map(|game| game.watch.clone())
+            .unwrap_or_default()
+    }
+
+    /// Sync every current match of the glob save path at `index`, then download
+    /// cloud files belonging to matches that do not exist on this device yet
+    async fn sync_glob_save_path(&self, index: usize, pattern: &save_paths::GlobPattern, game_name: &str) -> Result<()> {
+        let matches = pattern.matches();
+        for matched in &matches {
+            let matched = matched.to_str().context("Invalid file path")?;
+            debug!("Syncing glob match: {}", matched);
+            self.sync_file(matched, game_name).await?;
+        }
+
+        for remote_name in self.list_cloud_files(game_name, &remote_prefix(index, "")).await? {
+            let Some(local_path) = pattern.local_path(&remote_name) else {
+                continue;
+            };
+            if matches.iter().any(|matched| local_path.starts_with(matched)) {
+                continue;
+            }
+
+            let local_file_path = local_path.to_str().context("Invalid file path")?;
+            // Matches that do not exist on this device have nothing open
+            if let Err(e) = self.sync_file_bidirectional(local_file_path, game_name, &HashSet::new()).await {
+                warn!("Failed to sync {}: {}", local_file_path, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn sync_file(&self, file_path: &str, game_name: &str) -> Result<()> {
+        debug!("sync_file called for: {} (game: {})", file_path, game_name);
+        
+        let path = Path::new(file_path);
+        
         if !path.exists() {
-            warn!("Save path does not exist: {}", file_path);
-            // Check if file exists in S3 and download it
-            return self.download_missing_file(file_path, game_name).await;
+            debug!("Save path does not exist: {}", file_path);
+            return self.sync_missing_path(file_path, game_name).await;
         }
 
         if path.is_dir() {
@@ -802,44 +1604,105 @@ impl GameSaveSync {
         }
 
         // Handle individual file with bidirectional sync
-        self.sync_file_bidirectional(file_path, game_name).await
+        let held_open = settle::scan_files_held_open(vec![path.to_path_buf()]).await;
+        self.sync_file_bidirectional(file_path, game_name, &held_open).await?;
+        Ok(())
+    }
+
+    /// Sync a file the watcher saw change, once `SettleTracker` considers it
+    /// written. Having waited for that, the file is not checked for other
+    /// processes writing it again.
+    async fn sync_settled_file(&self, file_path: &str, game_name: &str) -> Result<()> {
+        if Path::new(file_path).is_file() {
+            self.sync_file_bidirectional(file_path, game_name, &HashSet::new()).await?;
+            return Ok(());
+        }
+        self.sync_file(file_path, game_name).await
     }
 
-    async fn sync_file_bidirectional(&self, file_path: &str, game_name: &str) -> Result<()> {
+    /// Sync one file in both directions. `held_open` lists the files other
+    /// processes have open, from one scan for the whole batch being synced;
+    /// those still changing are skipped.
+    async fn sync_file_bidirectional(&self, file_path: &str, game_name: &str, held_open: &HashSet<PathBuf>) -> Result<SyncAction> {
         debug!("Starting bidirectional sync for: {}", file_path);
         
         // Check if file is locked
-        if self.is_file_locked(file_path).await? {
+        if Path::new(file_path).exists() && self.is_file_locked(file_path, held_open).await? {
             warn!("File is locked, skipping sync: {}", file_path);
-            return Ok(());
+            return Ok(SyncAction::NoAction);
+        }
+
+        let Some(client) = &self.s3_client else {
+            warn!("No S3 client configured, cannot sync: {}", file_path);
+            return Ok(SyncAction::NoAction);
+        };
+
+        let remote_name = self.remote_name(game_name, file_path)?;
+        let legacy_name = self.legacy_name(game_name, file_path);
+
+        let local_data = match fs::read(file_path).await {
+            Ok(data) => Some(data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e).context("Failed to read local file"),
+        };
+        if local_data.is_none() && !self.is_save_root_available(game_name, file_path) {
+            debug!("Save root is unavailable, skipping: {}", file_path);
+            return Ok(SyncAction::NoAction);
         }
 
-        let local_info = self.get_local_file_info(file_path).await?;
-        let cloud_info = self.get_cloud_file_info(file_path, game_name).await;
+        let local_hash = local_data.as_deref().map(|data| self.calculate_hash(data));
+        let base = self.sync_state.base(game_name, file_path);
+        // Without a definite answer from the cloud the file is left alone: an
+        // unreachable bucket must never look like a deleted save
+        let cloud = self.get_cloud_object(game_name, &remote_name, legacy_name.as_deref()).await?;
+
+        // A tombstone remembers the deleted save, so a device that never synced
+        // it still recognizes its copy as deleted rather than as a new save
+        let base = base.or_else(|| {
+            cloud.as_ref()?.deleted.as_ref().map(|hash| sync_state::SyncedFile {
+                hash: hash.clone(),
+                remote_etag: None,
+                synced_at: chrono::Utc::now(),
+            })
+        });
+
+        // Work out the remote content hash, downloading only when the remote
+        // carries no hash and has changed since the last sync
+        let mut downloaded = None;
+        let remote_hash = match &cloud {
+            None | Some(CloudObject { deleted: Some(_), .. }) => None,
+            Some(CloudObject { content_hash: Some(hash), .. }) => Some(hash.clone()),
+            Some(CloudObject { etag: Some(etag), .. })
+                if base.as_ref().and_then(|b| b.remote_etag.as_ref()) == Some(etag) =>
+            {
+                base.as_ref().map(|b| b.hash.clone())
+            }
+            Some(_) => {
+                let (data, etag) = self.download_object(game_name, &remote_name, legacy_name.as_deref()).await?;
+                let hash = self.calculate_hash(&data);
+                downloaded = Some((data, etag));
+                Some(hash)
+            }
+        };
 
-        let action = self.determine_sync_action(&local_info, &cloud_info);
+        let action = sync_state::decide_sync_action(local_hash.as_deref(), remote_hash.as_deref(), base.as_ref());
         
-        match action {
+        match &action {
             SyncAction::UploadToS3 => {
-                debug!("Local file is newer, uploading to S3: {}", file_path);
-                let data = fs::read(file_path).await
-                    .context("Failed to read local file")?;
-                
-                if let Some(client) = &self.s3_client {
-                    self.upload_to_s3(client, &data, game_name, file_path).await?;
-                    info!("Uploaded newer local file to S3: {}", file_path);
-                } else {
-                    warn!("No S3 client configured, cannot upload: {}", file_path);
-                }
+                debug!("Local file changed since last sync, uploading to S3: {}", file_path);
+                let (Some(local_data), Some(local_hash)) = (&local_data, &local_hash) else {
+                    unreachable!("upload is only decided for files that exist locally");
+                };
+                let etag = self.upload_to_s3(client, file_path, local_data, game_name, &remote_name).await?;
+                self.sync_state.record_synced(game_name, file_path, local_hash, etag)?;
+                info!("Uploaded changed local file to S3: {}", file_path);
             },
             SyncAction::DownloadFromS3 => {
-                debug!("Cloud file is newer, downloading from S3: {}", file_path);
-                let file_name = Path::new(file_path)
-                    .file_name()
-                    .and_then(|n| n.to_str())
-                    .context("Invalid file name")?;
-                
-                let data = self.download_from_s3(game_name, file_name).await?;
+                debug!("Cloud file changed since last sync, downloading from S3: {}", file_path);
+                let (data, etag) = match downloaded {
+                    Some(downloaded) => downloaded,
+                    None => self.download_object(game_name, &remote_name, legacy_name.as_deref()).await?,
+                };
                 
                 // Create directory if it doesn't exist
                 if let Some(parent) = Path::new(file_path).parent() {
@@ -849,163 +1712,230 @@ impl GameSaveSync {
                 
                 fs::write(file_path, &data).await
                     .context("Failed to write downloaded file")?;
+                self.sync_state.record_synced(game_name, file_path, &self.calculate_hash(&data), etag)?;
                 
-                info!("Downloaded newer cloud file from S3: {}", file_path);
+                info!("Downloaded changed cloud file from S3: {}", file_path);
             },
             SyncAction::NoAction => {
                 debug!("Files are in sync, no action needed: {}", file_path);
+                match local_hash {
+                    Some(local_hash) => {
+                        let etag = cloud.and_then(|c| c.etag);
+                        if base.as_ref().map(|b| (&b.hash, &b.remote_etag)) != Some((&local_hash, &etag)) {
+                            self.sync_state.record_synced(game_name, file_path, &local_hash, etag)?;
+                        }
+                    }
+                    None => {
+                        self.sync_state.forget(game_name, file_path)?;
+                        if let Some(tombstone) = cloud.as_ref().filter(|object| object.deleted.is_some()) {
+                            self.expire_tombstone(client, tombstone).await?;
+                        }
+                    }
+                }
+            },
+            SyncAction::DeleteLocal => {
+                debug!("Cloud file was deleted since last sync, deleting local copy: {}", file_path);
+                fs::remove_file(file_path).await
+                    .context("Failed to delete local file")?;
+                self.sync_state.forget(game_name, file_path)?;
+                info!("Deleted local file removed from the cloud: {}", file_path);
+            },
+            SyncAction::DeleteRemote if !Path::new(file_path).parent().is_some_and(Path::is_dir) => {
+                // The directory holding the save is gone as well, which is as
+                // likely to be a missing mount as a deletion
+                warn!("Folder of deleted save is missing, not deleting the cloud copy: {}", file_path);
+                return Ok(SyncAction::NoAction);
+            },
+            SyncAction::DeleteRemote => {
+                let key = cloud.as_ref().map(|c| c.key.clone())
+                    .unwrap_or_else(|| self.object_key(game_name, &remote_name));
+                let Some(remote_hash) = &remote_hash else {
+                    unreachable!("remote deletion is only decided for files that exist remotely");
+                };
+                debug!("Local file was deleted since last sync, replacing cloud copy with a tombstone: {}", key);
+                self.write_tombstone(client, &key, remote_hash).await?;
+                self.sync_state.forget(game_name, file_path)?;
+                info!("Deleted cloud file removed locally: {}", file_path);
+            },
+            SyncAction::Conflict { local_hash, remote_hash, base_hash } => {
+                let policy = self.config.games.get(game_name)
+                    .map(|game| game.conflict_policy)
+                    .unwrap_or_default();
+
+                if let Some(resolution) = policy.resolution() {
+                    info!("Save changed on this device and in the cloud, resolving with {:?}: {}", resolution, file_path);
+                    self.settle_conflict(game_name, file_path, resolution).await?;
+                    return Ok(action);
+                }
+
+                warn!("Save changed on this device and in the cloud, not overwriting either: {}", file_path);
+                self.sync_state.record_conflict(SyncConflict {
+                    game_name: game_name.to_string(),
+                    file_path: file_path.to_string(),
+                    local_hash: local_hash.clone(),
+                    remote_hash: remote_hash.clone(),
+                    base_hash: base_hash.clone(),
+                    detected_at: chrono::Utc::now(),
+                })?;
+            }
+        }
+
+        Ok(action)
+    }
+
+    /// Settle a pending conflict for one file
+    pub async fn resolve_conflict(&self, game_name: &str, file_path: &str, resolution: ConflictResolution) -> Result<()> {
+        if self.sync_state.conflict(game_name, file_path).is_none() {
+            return Err(anyhow::anyhow!("No pending conflict for {} in {}", file_path, game_name));
+        }
+        self.settle_conflict(game_name, file_path, resolution).await
+    }
+
+    /// Keep one side of a file that changed on both sides, for `resolve_conflict`
+    /// and for conflicts the game's policy resolves automatically during sync
+    async fn settle_conflict(&self, game_name: &str, file_path: &str, resolution: ConflictResolution) -> Result<()> {
+        let client = self.s3_client.as_ref()
+            .context("S3 client not configured")?;
+        let remote_name = self.remote_name(game_name, file_path)?;
+        let legacy_name = self.legacy_name(game_name, file_path);
+
+        let keep_local = match resolution {
+            ConflictResolution::KeepLocal => true,
+            ConflictResolution::KeepRemote => false,
+            ConflictResolution::KeepNewest | ConflictResolution::KeepBoth => {
+                let local_modified: chrono::DateTime<chrono::Utc> = fs::metadata(file_path).await
+                    .and_then(|m| m.modified())
+                    .context("Failed to get local modification time")?
+                    .into();
+                let remote_modified = self.get_cloud_object(game_name, &remote_name, legacy_name.as_deref()).await?
+                    .and_then(|c| c.save_modified.or(c.last_modified));
+                remote_modified.is_none_or(|remote| local_modified >= remote)
+            }
+        };
+
+        if resolution == ConflictResolution::KeepBoth {
+            // The older copy survives next to the save under a conflict name
+            let copy_path = sync_state::conflict_copy_path(Path::new(file_path), chrono::Utc::now());
+            if keep_local {
+                let (data, _) = self.download_object(game_name, &remote_name, legacy_name.as_deref()).await?;
+                fs::write(&copy_path, &data).await
+                    .context("Failed to write conflict copy")?;
+            } else {
+                fs::copy(file_path, &copy_path).await
+                    .context("Failed to write conflict copy")?;
             }
+            info!("Kept the other copy of {} as {}", file_path, copy_path.display());
+        }
+
+        if keep_local {
+            let data = fs::read(file_path).await
+                .context("Failed to read local file")?;
+            let etag = self.upload_to_s3(client, file_path, &data, game_name, &remote_name).await?;
+            self.sync_state.record_synced(game_name, file_path, &self.calculate_hash(&data), etag)?;
+            info!("Resolved conflict for {} by keeping the local copy", file_path);
+        } else {
+            let (data, etag) = self.download_object(game_name, &remote_name, legacy_name.as_deref()).await?;
+            fs::write(file_path, &data).await
+                .context("Failed to write downloaded file")?;
+            self.sync_state.record_synced(game_name, file_path, &self.calculate_hash(&data), etag)?;
+            info!("Resolved conflict for {} by keeping the cloud copy", file_path);
         }
 
         Ok(())
     }
 
-    async fn download_missing_file(&self, file_path: &str, game_name: &str) -> Result<()> {
-        debug!("Checking if missing local file exists in S3: {}", file_path);
-        
-        let file_name = Path::new(file_path)
-            .file_name()
-            .and_then(|n| n.to_str())
-            .context("Invalid file name")?;
-        
-        match self.download_from_s3(game_name, file_name).await {
-            Ok(data) => {
-                // Create directory if it doesn't exist
-                if let Some(parent) = Path::new(file_path).parent() {
-                    fs::create_dir_all(parent).await
-                        .context("Failed to create directory")?;
+    /// Whether the save root holding a path is there on this device, so
+    /// missing files below it can be told apart from an unmounted drive
+    fn is_save_root_available(&self, game_name: &str, file_path: &str) -> bool {
+        let save_roots = self.config.games.get(game_name)
+            .map(|game| game.save_paths.as_slice())
+            .unwrap_or_default();
+        save_root_available(save_roots, &self.sync_state.tracked_files(game_name), file_path)
+    }
+
+    /// Sync a save path that does not exist locally. Saves deleted on this
+    /// device since the last sync are deleted in the cloud as long as the
+    /// folder holding them still exists; anything else is downloaded. Nothing
+    /// happens while the save root itself is unavailable.
+    async fn sync_missing_path(&self, file_path: &str, game_name: &str) -> Result<()> {
+        if !self.is_save_root_available(game_name, file_path) {
+            info!("Save path is unavailable on this device, skipping: {}", file_path);
+            return Ok(());
+        }
+
+        let path = Path::new(file_path);
+        for tracked in self.sync_state.tracked_files(game_name) {
+            if tracked != file_path && Path::new(&tracked).starts_with(path) {
+                if let Err(e) = self.sync_file_bidirectional(&tracked, game_name, &HashSet::new()).await {
+                    warn!("Failed to sync {}: {}", tracked, e);
                 }
-                
-                fs::write(file_path, &data).await
-                    .context("Failed to write downloaded file")?;
-                
-                info!("Downloaded missing file from S3: {}", file_path);
-                Ok(())
-            },
-            Err(_) => {
-                debug!("File does not exist in S3 either: {}", file_path);
-                Ok(())
             }
         }
+
+        match self.sync_file_bidirectional(file_path, game_name, &HashSet::new()).await? {
+            SyncAction::NoAction => debug!("File does not exist in S3 either: {}", file_path),
+            SyncAction::DownloadFromS3 => info!("Downloaded missing file from S3: {}", file_path),
+            _ => {}
+        }
+        Ok(())
     }
 
-    async fn get_local_file_info(&self, file_path: &str) -> Result<FileInfo> {
-        let metadata = fs::metadata(file_path).await
-            .context("Failed to get file metadata")?;
-        
-        let modified_time = metadata.modified()
-            .context("Failed to get file modification time")?;
-        
-        let size = metadata.len();
-        
-        Ok(FileInfo {
-            path: file_path.to_string(),
-            modified_time,
-            size,
-            hash: None, // We'll calculate hash only when needed
-        })
+    /// Look up a save in the cloud, falling back to the flat key used
+    /// before directory structure was preserved
+    async fn get_cloud_object(&self, game_name: &str, remote_name: &str, legacy_name: Option<&str>) -> Result<Option<CloudObject>> {
+        let key = self.object_key(game_name, remote_name);
+        if let Some(object) = self.head_key(&key).await? {
+            return Ok(Some(object));
+        }
+
+        match legacy_name {
+            Some(flat_name) => self.head_key(&self.object_key(game_name, flat_name)).await,
+            None => Ok(None),
+        }
     }
 
-    async fn get_cloud_file_info(&self, file_path: &str, game_name: &str) -> Option<FileInfo> {
-        let client = self.s3_client.as_ref()?;
-        let bucket = self.config.s3_bucket.as_ref()?;
-        
-        let file_name = Path::new(file_path)
-            .file_name()
-            .and_then(|n| n.to_str())?;
-        
-        let key = format!("{}/{}", game_name, file_name);
+    /// Metadata of an object. `Ok(None)` only when S3 confirms the object does
+    /// not exist; network, permission and configuration errors are errors.
+    async fn head_key(&self, key: &str) -> Result<Option<CloudObject>> {
+        let client = self.s3_client.as_ref()
+            .context("S3 client not configured")?;
+        let bucket = self.config.s3_bucket.as_ref()
+            .context("S3 bucket not configured")?;
         
-        match client.head_object()
-            .bucket(bucket)
-            .key(&key)
-            .send()
-            .await 
-        {
-            Ok(response) => {
-                let modified_time = response.last_modified()
-                    .and_then(|dt| {
-                        SystemTime::UNIX_EPOCH.checked_add(
-                            std::time::Duration::from_secs(dt.secs() as u64)
-                        )
-                    })
-                    .unwrap_or(UNIX_EPOCH);
-                
-                let size = response.content_length().unwrap_or(0) as u64;
-                
-                // Get ETag as hash (remove quotes if present)
-                let etag_hash = response.e_tag()
+        let response = storage::retry_transient(&self.retry, "head_object", || async {
+            match client.head_object().bucket(bucket).key(key).send().await {
+                Ok(response) => Ok(Some(response)),
+                Err(e) if e.as_service_error().is_some_and(|se| se.is_not_found()) => Ok(None),
+                Err(e) => Err(e.into()),
+            }
+        })
+        .await
+        .context(format!("Failed to look up {} in S3", key))?;
+
+        match response {
+            Some(response) => {
+                // Get ETag (remove quotes if present)
+                let etag = response.e_tag()
                     .map(|etag| etag.trim_matches('"').to_string());
+                let content_hash = self.hash_metadata(response.metadata(), CONTENT_HASH_METADATA_KEY)
+                    .context(format!("Failed to read the content hash of {}", key))?;
+                let deleted = self.hash_metadata(response.metadata(), TOMBSTONE_METADATA_KEY)
+                    .context(format!("Failed to read the content hash of {}", key))?;
+                let last_modified = response.last_modified()
+                    .and_then(|dt| chrono::DateTime::from_timestamp(dt.secs(), dt.subsec_nanos()));
+                let save_modified = response.metadata()
+                    .and_then(|metadata| metadata.get(SAVE_MODIFIED_METADATA_KEY))
+                    .and_then(|value| chrono::DateTime::parse_from_rfc3339(value).ok())
+                    .map(|dt| dt.with_timezone(&chrono::Utc));
                 
-                debug!("Found cloud file: {} (size: {}, modified: {:?}, etag: {:?})", 
-                       key, size, modified_time, etag_hash);
+                debug!("Found cloud file: {} (size: {:?}, etag: {:?}, sha256: {:?})", 
+                       key, response.content_length(), etag, content_hash);
                 
-                Some(FileInfo {
-                    path: file_path.to_string(),
-                    modified_time,
-                    size,
-                    hash: etag_hash,
-                })
+                Ok(Some(CloudObject { key: key.to_string(), etag, last_modified, save_modified, content_hash, deleted }))
             },
-            Err(e) => {
-                debug!("Cloud file not found or error accessing: {} - {}", key, e);
-                None
-            }
-        }
-    }
-
-    fn determine_sync_action(&self, local_info: &FileInfo, cloud_info: &Option<FileInfo>) -> SyncAction {
-        match cloud_info {
             None => {
-                debug!("No cloud file found, uploading local file");
-                SyncAction::UploadToS3
-            },
-            Some(cloud) => {
-                // Calculate time difference in seconds
-                let time_diff = if local_info.modified_time > cloud.modified_time {
-                    local_info.modified_time.duration_since(cloud.modified_time)
-                        .unwrap_or(std::time::Duration::from_secs(0))
-                        .as_secs()
-                } else {
-                    cloud.modified_time.duration_since(local_info.modified_time)
-                        .unwrap_or(std::time::Duration::from_secs(0))
-                        .as_secs()
-                };
-                
-                // Consider files with timestamp difference <= 2 seconds as "same time" 
-                // to account for filesystem timestamp precision differences
-                if time_diff <= 2 {
-                    // Timestamps are very close, compare sizes
-                    if local_info.size != cloud.size {
-                        debug!("Similar timestamps but different sizes (local: {}, cloud: {}), files are different - preferring local", 
-                               local_info.size, cloud.size);
-                        SyncAction::UploadToS3
-                    } else {
-                        // Same size and similar timestamp - files are almost certainly identical
-                        debug!("Files have similar timestamps (diff: {}s) and same size ({} bytes) - skipping sync to avoid unnecessary S3 operations", 
-                               time_diff, local_info.size);
-                        SyncAction::NoAction
-                    }
-                } else {
-                    // Significant time difference, use the newer file
-                    match local_info.modified_time.cmp(&cloud.modified_time) {
-                        std::cmp::Ordering::Greater => {
-                            debug!("Local file is significantly newer (local: {:?}, cloud: {:?}, diff: {}s)", 
-                                   local_info.modified_time, cloud.modified_time, time_diff);
-                            SyncAction::UploadToS3
-                        },
-                        std::cmp::Ordering::Less => {
-                            debug!("Cloud file is significantly newer (local: {:?}, cloud: {:?}, diff: {}s)", 
-                                   local_info.modified_time, cloud.modified_time, time_diff);
-                            SyncAction::DownloadFromS3
-                        },
-                        std::cmp::Ordering::Equal => {
-                            // This shouldn't happen given our time_diff check, but handle it
-                            debug!("Timestamps are equal, no sync needed");
-                            SyncAction::NoAction
-                        }
-                    }
-                }
+                debug!("Cloud file not found: {}", key);
+                Ok(None)
             }
         }
     }
@@ -1028,110 +1958,167 @@ impl GameSaveSync {
     async fn sync_directory(&self, dir_path: &str, game_name: &str) -> Result<()> {
         debug!("Syncing directory: {}", dir_path);
         
-        // Get all local files
+        // Get all local files below the directory, relative to it
+        let mut all_files = std::collections::BTreeSet::new();
         let mut local_files = Vec::new();
-        let mut entries = fs::read_dir(dir_path).await
-            .context("Failed to read directory")?;
+        let mut pending_dirs = vec![std::path::PathBuf::from(dir_path)];
 
-        while let Some(entry) = entries.next_entry().await
-            .context("Failed to read directory entry")? {
-            
-            let path = entry.path();
-            
-            if path.is_file() {
-                let file_path = path.to_str()
-                    .context("Invalid file path")?;
-                local_files.push(file_path.to_string());
-            } else if path.is_dir() {
-                // Optionally handle subdirectories recursively
-                debug!("Skipping subdirectory: {:?}", path);
+        while let Some(current_dir) = pending_dirs.pop() {
+            let mut entries = fs::read_dir(&current_dir).await
+                .context("Failed to read directory")?;
+
+            while let Some(entry) = entries.next_entry().await
+                .context("Failed to read directory entry")? {
+                
+                let path = entry.path();
+                let file_type = entry.file_type().await
+                    .context("Failed to read directory entry type")?;
+                
+                if file_type.is_dir() {
+                    pending_dirs.push(path);
+                } else if file_type.is_file() {
+                    if let Ok(rel) = path.strip_prefix(dir_path) {
+                        all_files.insert(slash_separated(rel));
+                    }
+                    local_files.push(path);
+                }
             }
         }
 
-        // Get all cloud files for this game
-        let cloud_files = self.list_cloud_files(game_name).await?;
-        
-        // Create a set of all unique file names (local and cloud)
-        let mut all_files = std::collections::HashSet::new();
-        
-        // Add local file names
-        for file_path in &local_files {
-            if let Some(file_name) = Path::new(file_path).file_name().and_then(|n| n.to_str()) {
-                all_files.insert(file_name.to_string());
+        // Add the cloud files below this directory. Cloud names are relative to
+        // the save root, which may be an ancestor of `dir_path`, within the
+        // namespace of that root.
+        let save_roots = self.config.games.get(game_name)
+            .map(|game| game.save_paths.as_slice())
+            .unwrap_or_default();
+        let dir_prefix = remote_dir_prefix(save_roots, dir_path);
+        all_files.extend(self.list_cloud_files(game_name, &dir_prefix).await?);
+
+        // Files deleted on both sides since the last sync only remain in the
+        // sync state; visiting them clears their stale base
+        let dir = Path::new(dir_path);
+        for tracked in self.sync_state.tracked_files(game_name) {
+            if let Ok(rel) = Path::new(&tracked).strip_prefix(dir) {
+                all_files.insert(slash_separated(rel));
             }
         }
-        
-        // Add cloud file names
-        for cloud_file in &cloud_files {
-            all_files.insert(cloud_file.clone());
-        }
-        
-        // Sync each unique file
-        for file_name in all_files {
-            let local_file_path = format!("{}/{}", dir_path.trim_end_matches('/'), file_name);
-            
-            if Path::new(&local_file_path).exists() {
-                // File exists locally, do bidirectional sync
-                self.sync_file_bidirectional(&local_file_path, game_name).await?;
-            } else {
-                // File only exists in cloud, download it
-                debug!("File only exists in cloud, downloading: {}", file_name);
-                match self.download_from_s3(game_name, &file_name).await {
-                    Ok(data) => {
-                        fs::write(&local_file_path, &data).await
-                            .context("Failed to write downloaded file")?;
-                        info!("Downloaded cloud-only file: {}", local_file_path);
-                    },
-                    Err(e) => {
-                        warn!("Failed to download cloud file {}: {}", file_name, e);
-                    }
-                }
+
+        // Listing open files walks every process, so it is done once for the directory
+        let held_open = settle::scan_files_held_open(local_files).await;
+
+        for rel in all_files {
+            let local_path = rel.split('/').fold(dir.to_path_buf(), |path, part| path.join(part));
+            let local_file_path = local_path.to_str()
+                .context("Invalid file path")?;
+
+            if !self.should_sync(game_name, local_file_path) {
+                debug!("Skipping filtered file: {}", local_file_path);
+                continue;
+            }
+
+            // Missing local subdirectories are created when a file is downloaded
+            if let Err(e) = self.sync_file_bidirectional(local_file_path, game_name, &held_open).await {
+                warn!("Failed to sync {}: {}", local_file_path, e);
             }
         }
 
         Ok(())
     }
 
-    async fn list_cloud_files(&self, game_name: &str) -> Result<Vec<String>> {
+    /// Remote names of the game's cloud saves starting with `remote_prefix`,
+    /// relative to it. Listing the first save root's names leaves out the
+    /// namespaces of the other roots.
+    ///
+    /// Tombstones are left out, since there is nothing to download; a local
+    /// copy of a deleted save is found by walking the save directory. Tombstones
+    /// past `TOMBSTONE_RETENTION_DAYS` are removed along the way.
+    async fn list_cloud_files(&self, game_name: &str, remote_prefix: &str) -> Result<Vec<String>> {
         let client = self.s3_client.as_ref()
             .context("S3 client not configured")?;
         let bucket = self.config.s3_bucket.as_ref()
             .context("S3 bucket not configured")?;
 
-        let prefix = format!("{}/", game_name);
-        
-        match client.list_objects_v2()
-            .bucket(bucket)
-            .prefix(&prefix)
-            .send()
-            .await 
-        {
-            Ok(response) => {
-                let mut files = Vec::new();
-                
-                let contents = response.contents();
-                for object in contents {
-                    if let Some(key) = object.key() {
-                        // Remove the prefix to get just the file name
-                        if let Some(file_name) = key.strip_prefix(&prefix) {
-                            if !file_name.is_empty() && !file_name.ends_with('/') {
-                                files.push(file_name.to_string());
-                            }
-                        }
+        let prefix = self.object_key(game_name, remote_prefix);
+        let mut files = Vec::new();
+        let mut continuation_token: Option<String> = None;
+
+        loop {
+            let response = storage::retry_transient(&self.retry, "list_objects_v2", || async {
+                Ok(client.list_objects_v2()
+                    .bucket(bucket)
+                    .prefix(&prefix)
+                    .set_continuation_token(continuation_token.clone())
+                    .send()
+                    .await?)
+            })
+            .await
+            .context(format!("Failed to list cloud files for game {}", game_name))?;
+
+            for object in response.contents() {
+                let Some(key) = object.key() else {
+                    continue;
+                };
+                // Remove the prefix to get just the file name
+                let Some(file_name) = key.strip_prefix(&prefix) else {
+                    continue;
+                };
+                if remote_prefix.is_empty() && in_root_namespace(file_name) {
+                    continue;
+                }
+                if file_name.is_empty() || file_name.ends_with('/') {
+                    continue;
+                }
+
+                // Tombstones are empty, so only empty objects need a closer look
+                if object.size() == Some(0) {
+                    if let Some(tombstone) = self.head_key(key).await?.filter(|object| object.deleted.is_some()) {
+                        self.expire_tombstone(client, &tombstone).await?;
+                        continue;
                     }
                 }
-                
-                debug!("Found {} cloud files for game {}", files.len(), game_name);
-                Ok(files)
-            },
-            Err(e) => {
-                debug!("Failed to list cloud files for game {}: {}", game_name, e);
-                Ok(Vec::new()) // Return empty list on error
+
+                files.push(file_name.to_string());
+            }
+
+            match response.next_continuation_token() {
+                Some(token) => continuation_token = Some(token.to_string()),
+                None => break,
             }
         }
+
+        debug!("Found {} cloud files for game {}", files.len(), game_name);
+        Ok(files)
+    }
+
+    /// Delete a tombstone once it is older than `TOMBSTONE_RETENTION_DAYS`.
+    /// A device that was offline for longer no longer learns of the deletion
+    /// and uploads its copy of the save again.
+    async fn expire_tombstone(&self, client: &Client, tombstone: &CloudObject) -> Result<()> {
+        let expired = tombstone.last_modified
+            .is_some_and(|written| chrono::Utc::now() - written > chrono::Duration::days(TOMBSTONE_RETENTION_DAYS));
+        if !expired {
+            return Ok(());
+        }
+
+        let bucket = self.config.s3_bucket.as_ref()
+            .context("S3 bucket not configured")?;
+        storage::retry_transient(&self.retry, "delete_object", || async {
+            Ok(client.delete_object().bucket(bucket).key(&tombstone.key).send().await?)
+        })
+        .await
+        .context(format!("Failed to delete expired tombstone {}", tombstone.key))?;
+
+        debug!("Deleted expired tombstone: {}", tombstone.key);
+        Ok(())
     }
 
-    async fn is_file_locked(&self, file_path: &str) -> Result<bool> {
+    async fn is_file_locked(&self, file_path: &str, held_open: &HashSet<PathBuf>) -> Result<bool> {
+        // Files are not locked while written on Linux, so look for another
+        // process holding the file open and changing it
+        if settle::is_being_written(Path::new(file_path), held_open).await {
+            return Ok(true);
+        }
+
         // Try to open the file in append mode to check if it's locked
         match fs::OpenOptions::new()
             .append(true)
@@ -1144,104 +2131,263 @@ impl GameSaveSync {
         }
     }
 
-    #[allow(dead_code)]
     fn calculate_hash(&self, data: &[u8]) -> String {
         let mut hasher = Sha256::new();
         hasher.update(data);
         format!("{:x}", hasher.finalize())
     }
 
+    /// Object metadata recording a content hash under `name`, sealed when
+    /// encrypting so the bucket does not reveal which save a file holds
+    fn object_metadata(&self, name: &str, hash: &str) -> Result<HashMap<String, String>> {
+        let mut metadata = HashMap::new();
+        if self.payload.is_encrypting() {
+            metadata.insert(name.to_string(), self.payload.seal_value(hash)?);
+            metadata.insert(PAYLOAD_METADATA_KEY.to_string(), SEALED_PAYLOAD.to_string());
+        } else {
+            metadata.insert(name.to_string(), hash.to_string());
+        }
+        Ok(metadata)
+    }
+
+    /// A content hash recorded by `object_metadata` on any device
+    fn hash_metadata(&self, metadata: Option<&HashMap<String, String>>, name: &str) -> Result<Option<String>> {
+        let Some(metadata) = metadata else {
+            return Ok(None);
+        };
+        match metadata.get(name) {
+            Some(sealed) if metadata.contains_key(PAYLOAD_METADATA_KEY) => {
+                self.payload.open_value(sealed).map(Some)
+            }
+            value => Ok(value.cloned()),
+        }
+    }
+
+    /// Upload a save file, recording its content hash and modification time as
+    /// object metadata. The save is encrypted when a passphrase is configured.
+    /// Returns the new ETag.
     async fn upload_to_s3(
         &self,
         client: &Client,
+        file_path: &str,
         data: &[u8],
         game_name: &str,
-        file_path: &str,
-    ) -> Result<()> {
+        remote_name: &str,
+    ) -> Result<Option<String>> {
         let bucket = self.config.s3_bucket.as_ref()
             .context("S3 bucket not configured")?;
 
         debug!("Uploading to S3 bucket: {}", bucket);
 
-        let file_name = Path::new(file_path)
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("unknown");
-
-        let key = format!("{}/{}", game_name, file_name);
+        let key = self.object_key(game_name, remote_name);
         debug!("S3 key: {}, data size: {} bytes", key, data.len());
 
-        let result = client
-            .put_object()
-            .bucket(bucket)
-            .key(&key)
-            .body(ByteStream::from(data.to_vec()))
-            .send()
-            .await;
+        let mut metadata = self.object_metadata(CONTENT_HASH_METADATA_KEY, &self.calculate_hash(data))?;
+        if let Ok(modified) = fs::metadata(file_path).await.and_then(|m| m.modified()) {
+            let modified: chrono::DateTime<chrono::Utc> = modified.into();
+            metadata.insert(SAVE_MODIFIED_METADATA_KEY.to_string(), modified.to_rfc3339());
+        }
+        let body = if self.payload.is_encrypting() {
+            self.payload.encode_blob(data)?
+        } else {
+            data.to_vec()
+        };
+
+        let result = storage::retry_transient(&self.retry, "put_object", || async {
+            Ok(client
+                .put_object()
+                .bucket(bucket)
+                .key(&key)
+                .set_metadata(Some(metadata.clone()))
+                .body(ByteStream::from(body.clone()))
+                .send()
+                .await?)
+        })
+        .await;
 
         match result {
-            Ok(_) => {
-                info!("Successfully uploaded {} to S3: {}", file_path, key);
-                Ok(())
+            Ok(output) => {
+                info!("Successfully uploaded {} to S3: {}", remote_name, key);
+                Ok(output.e_tag().map(|etag| etag.trim_matches('"').to_string()))
             }
             Err(e) => {
-                error!("Failed to upload {} to S3: {}", file_path, e);
-                Err(e.into())
+                error!("Failed to upload {} to S3: {}", remote_name, e);
+                Err(e)
             }
         }
     }
 
-    pub async fn download_from_s3(&self, game_name: &str, file_name: &str) -> Result<Vec<u8>> {
+    /// Replace a deleted save with an empty tombstone object. Unlike deleting
+    /// the object, this tells devices that never synced the save that their
+    /// copy was deleted rather than never uploaded.
+    async fn write_tombstone(&self, client: &Client, key: &str, deleted_hash: &str) -> Result<()> {
+        let bucket = self.config.s3_bucket.as_ref()
+            .context("S3 bucket not configured")?;
+
+        let metadata = self.object_metadata(TOMBSTONE_METADATA_KEY, deleted_hash)?;
+        storage::retry_transient(&self.retry, "put_object", || async {
+            Ok(client
+                .put_object()
+                .bucket(bucket)
+                .key(key)
+                .set_metadata(Some(metadata.clone()))
+                .body(ByteStream::from(Vec::new()))
+                .send()
+                .await?)
+        })
+        .await
+        .context(format!("Failed to write tombstone for {} to S3", key))?;
+
+        Ok(())
+    }
+
+    pub async fn download_from_s3(&self, game_name: &str, remote_name: &str) -> Result<Vec<u8>> {
+        Ok(self.download_object(game_name, remote_name, None).await?.0)
+    }
+
+    /// Download a save file together with its ETag, falling back to the flat
+    /// key used before directory structure was preserved
+    async fn download_object(&self, game_name: &str, remote_name: &str, legacy_name: Option<&str>) -> Result<(Vec<u8>, Option<String>)> {
+        let key = self.object_key(game_name, remote_name);
+        match self.download_key(&key).await {
+            Ok(downloaded) => Ok(downloaded),
+            Err(e) => match legacy_name {
+                Some(flat_name) => {
+                    debug!("{} not found, trying legacy flat key", key);
+                    self.download_key(&self.object_key(game_name, flat_name)).await
+                        .map_err(|_| e)
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    async fn download_key(&self, key: &str) -> Result<(Vec<u8>, Option<String>)> {
         let client = self.s3_client.as_ref()
             .context("S3 client not configured")?;
         let bucket = self.config.s3_bucket.as_ref()
             .context("S3 bucket not configured")?;
 
-        let key = format!("{}/{}", game_name, file_name);
-
         debug!("Downloading from S3: bucket={}, key={}", bucket, key);
 
-        let response = client
-            .get_object()
-            .bucket(bucket)
-            .key(&key)
-            .send()
-            .await
-            .context(format!("Failed to download {} from S3", key))?;
-
-        let data = response.body.collect().await?.into_bytes().to_vec();
+        // The body is read within the retried call, so a transfer that
+        // stalls halfway is started over
+        let (data, etag, sealed) = storage::retry_transient(&self.retry, "get_object", || async {
+            let response = client.get_object().bucket(bucket).key(key).send().await?;
+            let etag = response.e_tag().map(|etag| etag.trim_matches('"').to_string());
+            let sealed = response.metadata()
+                .is_some_and(|metadata| metadata.contains_key(PAYLOAD_METADATA_KEY));
+            let data = response.body.collect().await?.into_bytes().to_vec();
+            Ok((data, etag, sealed))
+        })
+        .await
+        .context(format!("Failed to download {} from S3", key))?;
         debug!("Downloaded {} bytes from S3: {}", data.len(), key);
-        Ok(data)
+
+        // Saves uploaded before encryption was enabled are stored as-is
+        let data = if sealed {
+            self.payload.decode_blob(&data)
+                .context(format!("Failed to decrypt {}", key))?
+        } else {
+            data
+        };
+        Ok((data, etag))
+    }
+}
+
+/// Paths whose saves a file event may have changed, without temporaries.
+///
+/// Deleted paths are included: syncing a missing save deletes it in the cloud.
+/// A rename reports its source and its final path, so with write-then-rename
+/// saving only the final path is synced, and renaming a save away deletes it.
+fn changed_paths(event: &Event) -> Vec<&Path> {
+    match event.kind {
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => event.paths.iter()
+            .map(PathBuf::as_path)
+            .filter(|path| !save_paths::is_temp_file(path))
+            .collect(),
+        _ => Vec::new(),
     }
 }
 
 pub async fn process_file_events(
+    mut file_watcher: FileWatcher,
     mut event_rx: mpsc::Receiver<Event>,
     sync_handler: GameSaveSync,
     game_name: String,
 ) -> Result<()> {
-    let mut pending_events: HashMap<String, Event> = HashMap::new();
+    let settings = sync_handler.watch_settings(&game_name);
+    let mut pending = settle::SettleTracker::new(settings.settle_polls());
     let mut last_batch_time = std::time::Instant::now();
 
     loop {
         tokio::select! {
             Some(event) = event_rx.recv() => {
-                // Only process write events
-                if matches!(event.kind, EventKind::Modify(_)) {
+                // New directories may be new matches of a glob save path
+                if file_watcher.has_glob_paths()
+                    && matches!(event.kind, EventKind::Create(_))
+                    && event.paths.iter().any(|path| path.is_dir())
+                {
+                    match file_watcher.refresh_glob_watches() {
+                        Ok(new_matches) => {
+                            for matched in new_matches {
+                                info!("New save directory matched: {}", matched.display());
+                                pending.touch(&matched);
+                            }
+                            last_batch_time = std::time::Instant::now();
+                        }
+                        Err(e) => warn!("Failed to re-expand glob save paths: {}", e),
+                    }
+                }
+
+                // Save paths that appeared are synced in full. Files below
+                // ones that went away are left alone: an unmounted SD card
+                // is not a deletion.
+                let healed = file_watcher.heal(&event);
+                for path in &healed.vanished {
+                    pending.forget_below(path);
+                }
+                if !healed.attached.is_empty() {
+                    for path in healed.attached {
+                        pending.touch(&path);
+                    }
+                    last_batch_time = std::time::Instant::now();
+                }
+
+                if matches!(event.kind, EventKind::Remove(_)) {
                     for path in &event.paths {
+                        file_watcher.forget_path(path);
+                    }
+                }
+
+                let paths = changed_paths(&event);
+                if !paths.is_empty() {
+                    for path in paths {
                         if let Some(path_str) = path.to_str() {
-                            pending_events.insert(path_str.to_string(), event.clone());
+                            // Ancestors watched while waiting for a save path report unrelated files
+                            if !sync_handler.is_in_save_paths(&game_name, path_str) || file_watcher.is_vanished(path) {
+                                continue;
+                            }
+                            if !sync_handler.should_sync(&game_name, path_str) {
+                                debug!("Ignoring change to filtered file: {}", path_str);
+                                continue;
+                            }
+                            pending.touch(path);
                         }
                     }
                     last_batch_time = std::time::Instant::now();
                 }
             }
-            _ = sleep(Duration::from_millis(500)) => {
-                // Process batched events after 500ms of inactivity
-                if !pending_events.is_empty() && last_batch_time.elapsed() > Duration::from_millis(500) {
-                    for (path, _) in pending_events.drain() {
+            _ = sleep(settle::SETTLE_POLL_INTERVAL) => {
+                // Once changes have been quiet for the debounce time, sync
+                // each changed file as soon as it has finished being written
+                if !pending.is_empty() && last_batch_time.elapsed() > settings.debounce() {
+                    for path in pending.poll().await {
+                        let Some(path) = path.to_str() else {
+                            continue;
+                        };
                         debug!("Processing file change: {}", path);
-                        if let Err(e) = sync_handler.sync_file(&path, &game_name).await {
+                        if let Err(e) = sync_handler.sync_settled_file(path, &game_name).await {
                             error!("Failed to sync file {}: {}", path, e);
                         }
                     }
@@ -1250,3 +2396,225 @@ pub async fn process_file_events(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_paths_follow_renames_and_skip_temporaries() {
+        use notify::event::{AccessKind, CreateKind, ModifyKind, RemoveKind, RenameMode};
+
+        let event = |kind, paths: &[&str]| Event {
+            kind,
+            paths: paths.iter().map(PathBuf::from).collect(),
+            attrs: Default::default(),
+        };
+
+        let renamed = event(EventKind::Modify(ModifyKind::Name(RenameMode::Both)), &["/saves/slot1.sav.tmp", "/saves/slot1.sav"]);
+        assert_eq!(changed_paths(&renamed), [Path::new("/saves/slot1.sav")]);
+
+        let moved = event(EventKind::Modify(ModifyKind::Name(RenameMode::Both)), &["/saves/slot1.sav", "/saves/slot2.sav"]);
+        assert_eq!(changed_paths(&moved), [Path::new("/saves/slot1.sav"), Path::new("/saves/slot2.sav")]);
+
+        let created = event(EventKind::Create(CreateKind::File), &["/saves/slot3.sav"]);
+        assert_eq!(changed_paths(&created), [Path::new("/saves/slot3.sav")]);
+
+        let removed = event(EventKind::Remove(RemoveKind::File), &["/saves/slot1.sav"]);
+        assert_eq!(changed_paths(&removed), [Path::new("/saves/slot1.sav")]);
+
+        assert!(changed_paths(&event(EventKind::Create(CreateKind::File), &["/saves/slot1.sav.tmp"])).is_empty());
+        assert!(changed_paths(&event(EventKind::Access(AccessKind::Any), &["/saves/slot1.sav"])).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_watcher_waits_for_missing_save_path_and_reattaches() {
+        use notify::event::{CreateKind, RemoveKind};
+        use watcher::WatchState;
+
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().to_path_buf();
+        let save_dir = root.join("compatdata/saves");
+        let configured = save_dir.to_str().unwrap().to_string();
+
+        let (mut file_watcher, _event_rx) = FileWatcher::new().unwrap();
+        file_watcher.watch_path(&configured).await.unwrap();
+        let states = file_watcher.states();
+        assert_eq!(states.get(&configured), Some(WatchState::Waiting { ancestor: root.clone() }));
+
+        let created = |path: &Path| Event::new(EventKind::Create(CreateKind::Folder)).add_path(path.to_path_buf());
+        std::fs::create_dir(root.join("compatdata")).unwrap();
+        assert_eq!(file_watcher.heal(&created(&root.join("compatdata"))), HealedPaths::default());
+        assert_eq!(states.get(&configured), Some(WatchState::Waiting { ancestor: root.join("compatdata") }));
+
+        std::fs::create_dir(&save_dir).unwrap();
+        assert_eq!(file_watcher.heal(&created(&save_dir)).attached, [save_dir.clone()]);
+        assert_eq!(states.get(&configured), Some(WatchState::Watching { polling: false }));
+
+        // Removed and recreated, as when an SD card is ejected and reinserted
+        std::fs::remove_dir(&save_dir).unwrap();
+        let healed = file_watcher.heal(&Event::new(EventKind::Remove(RemoveKind::Folder)).add_path(save_dir.clone()));
+        assert_eq!(healed, HealedPaths { attached: Vec::new(), vanished: vec![save_dir.clone()] });
+        assert!(file_watcher.is_vanished(&save_dir.join("slot1.sav")));
+        assert_eq!(states.get(&configured), Some(WatchState::Waiting { ancestor: root.join("compatdata") }));
+
+        std::fs::create_dir(&save_dir).unwrap();
+        assert_eq!(file_watcher.heal(&created(&save_dir)).attached, [save_dir.clone()]);
+        assert!(!file_watcher.is_vanished(&save_dir.join("slot1.sav")));
+        assert_eq!(states.snapshot().len(), 1);
+    }
+
+    #[test]
+    fn test_missing_save_root_is_unavailable_once_synced_from() {
+        let temp = tempfile::tempdir().unwrap();
+        let sd_card = temp.path().join("mmcblk0p1");
+        let save_dir = sd_card.join("compatdata/saves");
+        let roots = vec![save_dir.to_str().unwrap().to_string(), sd_card.join("settings.ini").to_str().unwrap().to_string()];
+        let slot = save_dir.join("slot1.sav").to_str().unwrap().to_string();
+        let tracked = vec![slot.clone()];
+
+        // A missing ancestor means the card is not mounted
+        assert!(!save_root_available(&roots, &[], &slot));
+        // Never synced here: the save folder may simply not exist yet
+        std::fs::create_dir_all(sd_card.join("compatdata")).unwrap();
+        assert!(save_root_available(&roots, &[], &slot));
+        // Synced before, so the folder is missing rather than empty
+        assert!(!save_root_available(&roots, &tracked, &slot));
+        std::fs::create_dir_all(&save_dir).unwrap();
+        assert!(save_root_available(&roots, &tracked, &slot));
+        assert!(save_root_available(&roots, &tracked, save_dir.join("profile/slot2.sav").to_str().unwrap()));
+
+        // A file root is there while its folder is, like the card being mounted
+        assert!(save_root_available(&roots, &[], &roots[1]));
+        std::fs::remove_dir_all(&sd_card).unwrap();
+        assert!(!save_root_available(&roots, &[], &roots[1]));
+        assert!(!save_root_available(&roots, &[], &slot));
+    }
+
+    #[test]
+    fn test_undecryptable_credentials_are_kept_during_migration() {
+        use crypto::KeySource;
+
+        let temp = tempfile::tempdir().unwrap();
+        let crypto = CredentialCrypto::with_key_source(KeySource::KeyFile(temp.path().join("credentials.key"))).unwrap();
+        let other = CredentialCrypto::with_key_source(KeySource::Passphrase("hunter2".to_string())).unwrap();
+
+        let unreadable = other.encrypt("secret").unwrap();
+        let mut config = SyncConfig {
+            aws_access_key_id: Some(crypto.encrypt("AKIAEXAMPLE").unwrap()),
+            aws_secret_access_key: Some(unreadable.clone()),
+            ..SyncConfig::default()
+        };
+
+        assert!(!config.migrate_credentials_with(&crypto));
+        assert_eq!(config.aws_secret_access_key, Some(unreadable));
+        assert_eq!(crypto.decrypt(config.aws_access_key_id.as_ref().unwrap()).unwrap(), "AKIAEXAMPLE");
+    }
+
+    #[test]
+    fn test_relative_save_path_keeps_subfolders() {
+        let roots = vec!["/games/hk/saves".to_string(), "/games/hk/settings.ini".to_string()];
+
+        assert_eq!(relative_save_path(&roots, "/games/hk/saves/profile1/save.dat").unwrap(), "profile1/save.dat");
+        assert_eq!(relative_save_path(&roots, "/games/hk/saves/profile2/save.dat").unwrap(), "profile2/save.dat");
+        assert_eq!(relative_save_path(&roots, "/games/hk/saves/global.dat").unwrap(), "global.dat");
+        // A file configured as its own root keeps the flat name
+        assert_eq!(relative_save_path(&roots, "/games/hk/settings.ini").unwrap(), "settings.ini");
+        // Outside every root falls back to the file name
+        assert_eq!(relative_save_path(&roots, "/elsewhere/save.dat").unwrap(), "save.dat");
+    }
+
+    #[test]
+    fn test_directory_roots_get_separate_namespaces() {
+        let roots = vec!["/games/hk/saves".to_string(), "/games/hk/config".to_string(), "/steam/userdata/*/1145360".to_string()];
+
+        assert_eq!(relative_save_path(&roots, "/games/hk/saves/settings.ini").unwrap(), "settings.ini");
+        assert_eq!(relative_save_path(&roots, "/games/hk/config/settings.ini").unwrap(), "@1/settings.ini");
+        assert_eq!(
+            relative_save_path(&roots, "/steam/userdata/111/1145360/remote/slot1.sav").unwrap(),
+            "@2/111/1145360/remote/slot1.sav"
+        );
+
+        assert_eq!(remote_dir_prefix(&roots, "/games/hk/saves"), "");
+        assert_eq!(remote_dir_prefix(&roots, "/games/hk/saves/profile1"), "profile1/");
+        assert_eq!(remote_dir_prefix(&roots, "/games/hk/config"), "@1/");
+        assert!(in_root_namespace("@1/settings.ini"));
+        assert!(!in_root_namespace("settings.ini"));
+        assert!(!in_root_namespace("@home/settings.ini"));
+    }
+
+    #[test]
+    fn test_path_within_nested_roots_prefers_deepest() {
+        let roots = vec!["/saves".to_string(), "/saves/cloud".to_string()];
+        assert_eq!(path_within_save_roots(&roots, "/saves/cloud/a/b.sav").as_deref(), Some("a/b.sav"));
+        assert_eq!(path_within_save_roots(&roots, "/saves/cloud").as_deref(), Some(""));
+        assert_eq!(path_within_save_roots(&roots, "/other"), None);
+    }
+
+    #[test]
+    fn test_legacy_flat_name_only_for_unowned_root_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let first = temp.path().join("saves");
+        let second = temp.path().join("config");
+        std::fs::create_dir_all(first.join("profile1")).unwrap();
+        std::fs::create_dir_all(second.join("profile1")).unwrap();
+        let roots = vec![first.to_string_lossy().into_owned(), second.to_string_lossy().into_owned()];
+        let path = |dir: &Path, rel: &str| dir.join(rel).to_string_lossy().into_owned();
+
+        // Files of the first root already use their flat names, and nested
+        // files' flat names belong to files of the first root
+        assert_eq!(legacy_flat_name(&roots, &[], &path(&first, "save.dat")), None);
+        assert_eq!(legacy_flat_name(&roots, &[], &path(&first, "profile1/save.dat")), None);
+        assert_eq!(legacy_flat_name(&roots, &[], &path(&second, "profile1/save.dat")), None);
+        assert_eq!(legacy_flat_name(&roots, &[], &path(&second, "save.dat")).as_deref(), Some("save.dat"));
+
+        // A save of that name in the first root, synced or on disk, owns the flat key
+        let owner = path(&first, "save.dat");
+        assert_eq!(legacy_flat_name(&roots, std::slice::from_ref(&owner), &path(&second, "save.dat")), None);
+        std::fs::write(&owner, b"slot").unwrap();
+        assert_eq!(legacy_flat_name(&roots, &[], &path(&second, "save.dat")), None);
+    }
+
+    #[test]
+    fn test_glob_root_matches_keep_distinct_stable_keys() {
+        let roots = vec!["/steam/userdata/*/1145360".to_string()];
+        assert_eq!(
+            relative_save_path(&roots, "/steam/userdata/111/1145360/remote/slot1.sav").unwrap(),
+            "111/1145360/remote/slot1.sav"
+        );
+        assert_eq!(
+            relative_save_path(&roots, "/steam/userdata/222/1145360/remote/slot1.sav").unwrap(),
+            "222/1145360/remote/slot1.sav"
+        );
+        assert_eq!(path_within_save_roots(&roots, "/steam/userdata/111/1145360").as_deref(), Some("111/1145360"));
+        assert_eq!(path_within_save_roots(&roots, "/steam/userdata/111/999/save.sav"), None);
+    }
+
+    #[test]
+    fn test_game_filter_reads_from_config_and_applies_inside_roots() {
+        let game: GameConfig = toml::from_str(r#"
+            name = "Hades"
+            save_paths = ["/saves/hades", "/saves/hades.cfg"]
+            sync_enabled = true
+            exclude = ["*.log", "ShaderCache"]
+            max_file_size = 1024
+        "#).unwrap();
+
+        assert_eq!(game.filter.exclude, ["*.log", "ShaderCache"]);
+        assert!(game.should_sync("/saves/hades/Profile1.sav", Some(100)));
+        assert!(!game.should_sync("/saves/hades/logs/run.log", Some(100)));
+        assert!(!game.should_sync("/saves/hades/ShaderCache/0.bin", None));
+        assert!(!game.should_sync("/saves/hades/Profile1.sav", Some(4096)));
+        // A configured save path is synced even if it would not pass the filter
+        assert!(game.should_sync("/saves/hades.cfg", Some(4096)));
+        // Copies kept when resolving a conflict stay local, filter or not
+        assert!(!game.should_sync("/saves/hades/Profile1.conflict-20240102-030405.sav", Some(100)));
+        let unfiltered = GameConfig { filter: SaveFilter::default(), ..game.clone() };
+        assert!(!unfiltered.should_sync("/saves/hades/Profile1.conflict-20240102-030405.sav", Some(100)));
+        assert!(unfiltered.should_sync("/saves/hades/logs/run.log", Some(100)));
+
+        let serialized = toml::to_string(&game).unwrap();
+        assert!(serialized.contains("exclude = ["));
+        assert!(!serialized.contains("include"));
+    }
+}
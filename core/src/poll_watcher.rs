@@ -0,0 +1,308 @@
+use notify::event::{CreateKind, DataChange, ModifyKind, RemoveKind};
+use notify::{Config, Event, EventHandler, EventKind, RecursiveMode, Watcher, WatcherKind};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tracing::debug;
+
+/// Coarsest modification time resolution among the filesystems saves live on
+/// (FAT and exFAT round to two seconds)
+const MTIME_RESOLUTION: Duration = Duration::from_secs(2);
+
+/// State of one path at the last poll
+#[derive(Debug, Clone, PartialEq)]
+struct Snapshot {
+    is_dir: bool,
+    len: u64,
+    modified: Option<SystemTime>,
+    /// Content hash, taken while the modification time is too recent to tell
+    /// a second write apart from the first
+    hash: Option<String>,
+    /// Whether the modification time was old enough to trust
+    settled: bool,
+}
+
+#[derive(Debug, Default)]
+struct PollState {
+    roots: HashMap<PathBuf, RecursiveMode>,
+    snapshots: BTreeMap<PathBuf, Snapshot>,
+}
+
+/// Watcher for filesystems where inotify misses changes, such as SMB shares,
+/// FUSE mounts like the Flatpak document portal, or where inotify cannot be
+/// set up at all.
+///
+/// Every poll interval it snapshots the modification time and size of each
+/// watched file and reports differences as notify events, so it can stand in
+/// for the native watcher. Files modified within the filesystem's timestamp
+/// resolution are also hashed, which catches rewrites that keep both.
+pub struct PollingWatcher {
+    state: Arc<Mutex<PollState>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Watcher for PollingWatcher {
+    fn new<F: EventHandler>(mut event_handler: F, config: Config) -> notify::Result<Self> {
+        let state = Arc::new(Mutex::new(PollState::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let interval = config.poll_interval();
+
+        let thread_state = state.clone();
+        let thread_stop = stop.clone();
+        std::thread::Builder::new()
+            .name("save-poll-watcher".to_string())
+            .spawn(move || {
+                while sleep_unless_stopped(interval, &thread_stop) {
+                    for event in Self::poll(&thread_state) {
+                        event_handler.handle_event(Ok(event));
+                    }
+                }
+            })
+            .map_err(notify::Error::io)?;
+
+        Ok(Self { state, stop })
+    }
+
+    /// Watch a path, taking its first snapshot right away so only later
+    /// changes are reported
+    fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> notify::Result<()> {
+        if !path.exists() {
+            return Err(notify::Error::path_not_found().add_path(path.to_path_buf()));
+        }
+
+        let mut state = self.state.lock()
+            .map_err(|_| notify::Error::generic("Polling watcher state poisoned"))?;
+        let snapshots = Self::snapshot_root(path, recursive_mode, &state.snapshots);
+        debug!("Polling {} paths under {}", snapshots.len(), path.display());
+        state.snapshots.extend(snapshots);
+        state.roots.insert(path.to_path_buf(), recursive_mode);
+        Ok(())
+    }
+
+    fn unwatch(&mut self, path: &Path) -> notify::Result<()> {
+        let mut state = self.state.lock()
+            .map_err(|_| notify::Error::generic("Polling watcher state poisoned"))?;
+        if state.roots.remove(path).is_none() {
+            return Err(notify::Error::watch_not_found().add_path(path.to_path_buf()));
+        }
+
+        // Keep snapshots still covered by another root
+        let roots: Vec<PathBuf> = state.roots.keys().cloned().collect();
+        state.snapshots.retain(|snapshot_path, _| {
+            !snapshot_path.starts_with(path) || roots.iter().any(|root| snapshot_path.starts_with(root))
+        });
+        Ok(())
+    }
+
+    fn kind() -> WatcherKind {
+        WatcherKind::PollWatcher
+    }
+}
+
+impl Drop for PollingWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Sleep for `duration` in short steps, returning false as soon as `stop` is set
+fn sleep_unless_stopped(duration: Duration, stop: &AtomicBool) -> bool {
+    const STEP: Duration = Duration::from_millis(100);
+    let mut remaining = duration;
+    while !remaining.is_zero() {
+        if stop.load(Ordering::Relaxed) {
+            return false;
+        }
+        let step = remaining.min(STEP);
+        std::thread::sleep(step);
+        remaining -= step;
+    }
+    !stop.load(Ordering::Relaxed)
+}
+
+impl PollingWatcher {
+    fn snapshot_root(root: &Path, mode: RecursiveMode, previous: &BTreeMap<PathBuf, Snapshot>) -> BTreeMap<PathBuf, Snapshot> {
+        let mut snapshots = BTreeMap::new();
+        let Ok(metadata) = std::fs::metadata(root) else {
+            return snapshots;
+        };
+
+        let is_dir = metadata.is_dir();
+        snapshots.insert(root.to_path_buf(), snapshot(root, &metadata, previous.get(root)));
+        if !is_dir {
+            return snapshots;
+        }
+
+        let mut pending_dirs = vec![root.to_path_buf()];
+        while let Some(dir) = pending_dirs.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                // Symlinked directories are not followed, which also avoids loops
+                let Ok(file_type) = entry.file_type() else {
+                    continue;
+                };
+                let Ok(metadata) = std::fs::metadata(&path) else {
+                    continue;
+                };
+                if file_type.is_dir() && mode == RecursiveMode::Recursive {
+                    pending_dirs.push(path.clone());
+                }
+                let entry_snapshot = snapshot(&path, &metadata, previous.get(&path));
+                snapshots.insert(path, entry_snapshot);
+            }
+        }
+
+        snapshots
+    }
+
+    /// Take a new snapshot of every root, returning the events for what changed
+    fn poll(state: &Mutex<PollState>) -> Vec<Event> {
+        let Ok(mut state) = state.lock() else {
+            return Vec::new();
+        };
+
+        let mut current = BTreeMap::new();
+        for (root, mode) in &state.roots {
+            current.extend(Self::snapshot_root(root, *mode, &state.snapshots));
+        }
+
+        let mut events = Vec::new();
+        for (path, before) in &state.snapshots {
+            match current.get(path) {
+                None => {
+                    let kind = if before.is_dir { RemoveKind::Folder } else { RemoveKind::File };
+                    events.push(Event::new(EventKind::Remove(kind)).add_path(path.clone()));
+                }
+                Some(after) if !before.is_dir && !after.is_dir && changed(before, after) => {
+                    events.push(Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Any))).add_path(path.clone()));
+                }
+                Some(_) => {}
+            }
+        }
+        for (path, after) in &current {
+            if !state.snapshots.contains_key(path) {
+                let kind = if after.is_dir { CreateKind::Folder } else { CreateKind::File };
+                events.push(Event::new(EventKind::Create(kind)).add_path(path.clone()));
+            }
+        }
+
+        state.snapshots = current;
+        events
+    }
+}
+
+/// Snapshot of a path. Files are hashed while their modification time is
+/// too recent to trust, and once more when it no longer is, so a rewrite
+/// within the timestamp resolution is still noticed.
+fn snapshot(path: &Path, metadata: &std::fs::Metadata, previous: Option<&Snapshot>) -> Snapshot {
+    let is_dir = metadata.is_dir();
+    let len = metadata.len();
+    let modified = metadata.modified().ok();
+
+    let settled = modified.is_some_and(|modified| {
+        SystemTime::now().duration_since(modified).is_ok_and(|age| age >= MTIME_RESOLUTION)
+    });
+    let recheck = previous.is_some_and(|previous| {
+        !previous.settled && previous.hash.is_some() && previous.len == len && previous.modified == modified
+    });
+    let hash = if !is_dir && (!settled || recheck) {
+        hash_file(path)
+    } else {
+        None
+    };
+
+    Snapshot { is_dir, len, modified, hash, settled }
+}
+
+/// Whether a file's content changed between two snapshots
+fn changed(before: &Snapshot, after: &Snapshot) -> bool {
+    if before.len != after.len || before.modified != after.modified {
+        return true;
+    }
+    matches!((&before.hash, &after.hash), (Some(a), Some(b)) if a != b)
+}
+
+fn hash_file(path: &Path) -> Option<String> {
+    let data = std::fs::read(path).ok()?;
+    Some(format!("{:x}", Sha256::digest(&data)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn watcher_on(dir: &Path) -> PollingWatcher {
+        // Polled by hand below; the background thread never gets to run
+        let mut watcher = PollingWatcher::new(|_: notify::Result<Event>| {}, Config::default().with_poll_interval(Duration::from_secs(3600))).unwrap();
+        watcher.watch(dir, RecursiveMode::Recursive).unwrap();
+        watcher
+    }
+
+    fn kinds(events: Vec<Event>) -> Vec<(EventKind, PathBuf)> {
+        let mut kinds: Vec<_> = events.into_iter().map(|event| (event.kind, event.paths[0].clone())).collect();
+        kinds.sort_by(|a, b| a.1.cmp(&b.1));
+        kinds
+    }
+
+    #[test]
+    fn test_reports_creates_changes_and_removals() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::write(dir.join("slot1.sav"), b"one").unwrap();
+        std::fs::write(dir.join("slot2.sav"), b"two").unwrap();
+        let watcher = watcher_on(&dir);
+
+        // Existing files are not reported
+        assert!(PollingWatcher::poll(&watcher.state).is_empty());
+
+        std::fs::write(dir.join("slot1.sav"), b"one, longer").unwrap();
+        std::fs::remove_file(dir.join("slot2.sav")).unwrap();
+        std::fs::create_dir(dir.join("profile")).unwrap();
+        std::fs::write(dir.join("profile/slot3.sav"), b"three").unwrap();
+
+        assert_eq!(kinds(PollingWatcher::poll(&watcher.state)), [
+            (EventKind::Create(CreateKind::Folder), dir.join("profile")),
+            (EventKind::Create(CreateKind::File), dir.join("profile/slot3.sav")),
+            (EventKind::Modify(ModifyKind::Data(DataChange::Any)), dir.join("slot1.sav")),
+            (EventKind::Remove(RemoveKind::File), dir.join("slot2.sav")),
+        ]);
+        assert!(PollingWatcher::poll(&watcher.state).is_empty());
+    }
+
+    #[test]
+    fn test_rewrite_keeping_size_and_mtime_is_noticed_by_hash() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("slot1.sav");
+        std::fs::write(&path, b"aaaa").unwrap();
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        let watcher = watcher_on(&dir);
+
+        // Same size and timestamp, as on a filesystem with coarse timestamps
+        std::fs::write(&path, b"bbbb").unwrap();
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+
+        assert_eq!(kinds(PollingWatcher::poll(&watcher.state)), [
+            (EventKind::Modify(ModifyKind::Data(DataChange::Any)), path.clone()),
+        ]);
+    }
+
+    #[test]
+    fn test_unwatch_and_missing_paths() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let mut watcher = watcher_on(&dir);
+        assert!(watcher.watch(&dir.join("missing"), RecursiveMode::Recursive).is_err());
+
+        watcher.unwatch(&dir).unwrap();
+        std::fs::write(dir.join("slot1.sav"), b"one").unwrap();
+        assert!(PollingWatcher::poll(&watcher.state).is_empty());
+        assert!(watcher.unwatch(&dir).is_err());
+    }
+}
@@ -0,0 +1,292 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Whether a configured save path contains glob wildcards
+pub fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+/// A save path with wildcards, such as `~/.steam/steam/userdata/*/1145360`.
+///
+/// Matches are keyed remotely by their path below the literal part of the
+/// pattern (`12345/1145360/...` for the example), so each match keeps the same
+/// key on every device and matches never collide.
+#[derive(Debug, Clone)]
+pub struct GlobPattern {
+    pattern: String,
+    base: PathBuf,
+    /// One matcher per component after `base`
+    components: Vec<glob::Pattern>,
+}
+
+impl GlobPattern {
+    /// Parse a configured save path; `None` when it has no wildcards or is invalid
+    pub fn parse(path: &str) -> Option<Self> {
+        if !is_glob(path) {
+            return None;
+        }
+
+        let pattern = shellexpand::tilde(path).into_owned();
+        let mut base = PathBuf::new();
+        let mut components = Vec::new();
+
+        for component in Path::new(&pattern).components() {
+            let text = component.as_os_str().to_string_lossy();
+            // Everything from the first wildcard component on is matched
+            if components.is_empty() && !is_glob(&text) {
+                base.push(component);
+            } else {
+                components.push(glob::Pattern::new(&text).ok()?);
+            }
+        }
+
+        if components.is_empty() {
+            return None;
+        }
+        Some(Self { pattern, base, components })
+    }
+
+    /// Deepest directory of the pattern without wildcards
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    /// Existing paths matching the whole pattern, sorted
+    pub fn matches(&self) -> Vec<PathBuf> {
+        let mut matches: Vec<PathBuf> = glob::glob(&self.pattern)
+            .map(|paths| paths.filter_map(|path| path.ok()).collect())
+            .unwrap_or_default();
+        matches.sort();
+        matches
+    }
+
+    /// Existing directories matching a leading part of the pattern, from `base`
+    /// down to the parents of full matches. Watching these is how new matches
+    /// are noticed when their directories are created.
+    pub fn partial_match_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = vec![self.base.clone()];
+        let mut current = vec![self.base.clone()];
+
+        for component in &self.components[..self.components.len() - 1] {
+            let mut next = Vec::new();
+            for dir in &current {
+                let Ok(entries) = std::fs::read_dir(dir) else {
+                    continue;
+                };
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    if path.is_dir() && component.matches(&entry.file_name().to_string_lossy()) {
+                        next.push(path);
+                    }
+                }
+            }
+            dirs.extend(next.iter().cloned());
+            current = next;
+        }
+
+        dirs.retain(|dir| dir.is_dir());
+        dirs
+    }
+
+    /// Split a path below a match into the match itself and the rest
+    pub fn match_of(&self, path: &Path) -> Option<(PathBuf, PathBuf)> {
+        let rel = path.strip_prefix(&self.base).ok()?;
+        let mut parts = rel.components();
+
+        let mut matched = self.base.clone();
+        for component in &self.components {
+            let part = parts.next()?;
+            if !component.matches(&part.as_os_str().to_string_lossy()) {
+                return None;
+            }
+            matched.push(part);
+        }
+
+        Some((matched, parts.as_path().to_path_buf()))
+    }
+
+    /// Local path of a remote name relative to `base`, if it falls under a match
+    pub fn local_path(&self, remote_name: &str) -> Option<PathBuf> {
+        let path = remote_name.split('/').fold(self.base.clone(), |path, part| path.join(part));
+        self.match_of(&path).map(|_| path)
+    }
+}
+
+/// Per-game filter deciding which files inside the save paths are synced.
+///
+/// Patterns are globs matched against the path relative to the save path (or
+/// glob match) containing the file, so `*.log` and `ShaderCache` both work at
+/// any depth. A pattern matching a directory applies to everything inside it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SaveFilter {
+    /// When non-empty, only matching files are synced
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// Larger files are skipped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<u64>,
+}
+
+impl SaveFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty() && self.max_file_size.is_none()
+    }
+
+    /// Whether a file, given by its `/`-separated path inside its save root, is synced.
+    /// `size` is only checked when known.
+    pub fn allows(&self, rel_path: &str, size: Option<u64>) -> bool {
+        if let (Some(max), Some(size)) = (self.max_file_size, size) {
+            if size > max {
+                return false;
+            }
+        }
+
+        if Self::matches_any(&self.exclude, rel_path) {
+            return false;
+        }
+
+        self.include.is_empty() || Self::matches_any(&self.include, rel_path)
+    }
+
+    /// Whether any pattern matches the path or one of its parent directories
+    fn matches_any(patterns: &[String], rel_path: &str) -> bool {
+        let prefixes: Vec<&str> = rel_path.match_indices('/')
+            .map(|(i, _)| &rel_path[..i])
+            .chain(std::iter::once(rel_path))
+            .collect();
+
+        patterns.iter().any(|pattern| match glob::Pattern::new(pattern) {
+            Ok(pattern) => prefixes.iter().any(|prefix| pattern.matches(prefix)),
+            Err(e) => {
+                warn!("Ignoring invalid filter pattern {:?}: {}", pattern, e);
+                false
+            }
+        })
+    }
+}
+
+/// Whether a file looks like a temporary written while saving, such as the
+/// `save.dat.tmp` of a write-then-rename or an editor swap file. Changes to
+/// these are not synced; the rename onto the real save is.
+pub fn is_temp_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().map(|name| name.to_string_lossy().to_lowercase()) else {
+        return false;
+    };
+
+    const SUFFIXES: [&str; 8] = [".tmp", ".temp", ".part", ".partial", ".swp", ".swx", ".crdownload", "~"];
+    const PREFIXES: [&str; 3] = [".#", "~$", ".goutputstream-"];
+
+    SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+        || PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+}
+
+/// Configured save paths with every glob replaced by its current matches
+pub fn expand_save_paths(save_paths: &[String]) -> Vec<String> {
+    save_paths.iter()
+        .flat_map(|save_path| match GlobPattern::parse(save_path) {
+            Some(pattern) => pattern.matches()
+                .into_iter()
+                .map(|path| path.to_string_lossy().to_string())
+                .collect(),
+            None => vec![save_path.clone()],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_excludes_noise_at_any_depth() {
+        let filter = SaveFilter {
+            include: Vec::new(),
+            exclude: vec!["*.log".to_string(), "*.tmp".to_string(), "ShaderCache".to_string()],
+            max_file_size: Some(1024),
+        };
+
+        assert!(filter.allows("slot1.sav", Some(100)));
+        assert!(filter.allows("profiles/1/slot1.sav", None));
+        assert!(!filter.allows("output.log", Some(10)));
+        assert!(!filter.allows("logs/2024/output.log", Some(10)));
+        assert!(!filter.allows("ShaderCache/abc/def.bin", Some(10)));
+        assert!(!filter.allows("slot1.sav.tmp", Some(10)));
+        assert!(!filter.allows("crash.dmp", Some(4096)));
+    }
+
+    #[test]
+    fn test_include_list_limits_to_matches() {
+        let filter = SaveFilter {
+            include: vec!["saves".to_string(), "*.cfg".to_string()],
+            exclude: vec!["saves/backup".to_string()],
+            max_file_size: None,
+        };
+
+        assert!(filter.allows("saves/slot1.sav", None));
+        assert!(filter.allows("settings.cfg", None));
+        assert!(!filter.allows("screenshots/1.png", None));
+        assert!(!filter.allows("saves/backup/slot1.sav", None));
+        assert!(SaveFilter::default().allows("anything/at/all", Some(u64::MAX)));
+    }
+
+    #[test]
+    fn test_recognizes_temp_files() {
+        assert!(is_temp_file(Path::new("/saves/slot1.sav.tmp")));
+        assert!(is_temp_file(Path::new("/saves/SLOT1.TMP")));
+        assert!(is_temp_file(Path::new("/saves/slot1.sav~")));
+        assert!(is_temp_file(Path::new("/saves/.slot1.sav.swp")));
+        assert!(is_temp_file(Path::new("/saves/.goutputstream-3XK1Z2")));
+        assert!(!is_temp_file(Path::new("/saves/slot1.sav")));
+        assert!(!is_temp_file(Path::new("/saves/template.dat")));
+        assert!(!is_temp_file(Path::new("/")));
+    }
+
+    #[test]
+    fn test_pattern_splits_literal_base() {
+        let pattern = GlobPattern::parse("/steam/userdata/*/1145360/remote").unwrap();
+        assert_eq!(pattern.base(), Path::new("/steam/userdata"));
+
+        let (matched, rest) = pattern.match_of(Path::new("/steam/userdata/12345/1145360/remote/slot1.sav")).unwrap();
+        assert_eq!(matched, Path::new("/steam/userdata/12345/1145360/remote"));
+        assert_eq!(rest, Path::new("slot1.sav"));
+
+        assert!(pattern.match_of(Path::new("/steam/userdata/12345/999/remote")).is_none());
+        assert!(pattern.match_of(Path::new("/steam/userdata/12345")).is_none());
+        assert_eq!(
+            pattern.local_path("12345/1145360/remote/slot1.sav"),
+            Some(PathBuf::from("/steam/userdata/12345/1145360/remote/slot1.sav"))
+        );
+        assert!(GlobPattern::parse("/steam/userdata/12345").is_none());
+    }
+
+    #[test]
+    fn test_expands_matches_and_partial_dirs() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        for user in ["111", "222"] {
+            std::fs::create_dir_all(root.join("userdata").join(user).join("1145360")).unwrap();
+        }
+        std::fs::create_dir_all(root.join("userdata/333")).unwrap();
+
+        let configured = format!("{}/userdata/*/1145360", root.display());
+        let expanded = expand_save_paths(&[configured.clone(), "/literal/path".to_string()]);
+        assert_eq!(expanded, [
+            root.join("userdata/111/1145360").to_string_lossy().to_string(),
+            root.join("userdata/222/1145360").to_string_lossy().to_string(),
+            "/literal/path".to_string(),
+        ]);
+
+        // The user directory without the game yet is watched for it to appear
+        let mut partial = GlobPattern::parse(&configured).unwrap().partial_match_dirs();
+        partial.sort();
+        assert_eq!(partial, [
+            root.join("userdata"),
+            root.join("userdata/111"),
+            root.join("userdata/222"),
+            root.join("userdata/333"),
+        ]);
+    }
+}
@@ -0,0 +1,311 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::time::sleep;
+use tracing::warn;
+
+/// Time between the polls that check whether a changed file is still being written
+pub const SETTLE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How the file watcher notices changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchBackend {
+    /// The OS watcher (inotify on Linux), switching to polling if it cannot
+    /// be set up, e.g. when the inotify watch limit is reached
+    #[default]
+    Auto,
+    /// Only the OS watcher
+    Native,
+    /// Compare snapshots of the save files at `poll_interval_ms`, for SMB
+    /// shares, FUSE mounts and other filesystems the OS watcher misses
+    /// changes on
+    Polling,
+}
+
+impl std::str::FromStr for WatchBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "auto" => Ok(WatchBackend::Auto),
+            "native" | "inotify" => Ok(WatchBackend::Native),
+            "polling" | "poll" => Ok(WatchBackend::Polling),
+            other => Err(anyhow::anyhow!(
+                "Unknown watch backend '{}' (expected auto, native or polling)", other
+            )),
+        }
+    }
+}
+
+/// Per-game tuning of how the watcher notices changes and when it considers
+/// a changed save ready to sync
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WatchSettings {
+    #[serde(default)]
+    pub watch_backend: WatchBackend,
+    /// Time between polls with the polling backend, in milliseconds.
+    /// Defaults to 5000.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll_interval_ms: Option<u64>,
+    /// Quiet time after the last change before changed files are checked, in
+    /// milliseconds. Defaults to 500.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debounce_ms: Option<u64>,
+    /// Polls in a row a file held open by another process must keep the same
+    /// size and modification time before it is synced. Defaults to 3.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settle_polls: Option<u32>,
+}
+
+impl WatchSettings {
+    pub fn debounce(&self) -> Duration {
+        Duration::from_millis(self.debounce_ms.unwrap_or(500))
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms.unwrap_or(5000).max(100))
+    }
+
+    pub fn settle_polls(&self) -> u32 {
+        self.settle_polls.unwrap_or(3).max(1)
+    }
+}
+
+/// Size and modification time of a file, compared across polls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    /// `None` when the path is not a regular file
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok().filter(|metadata| metadata.is_file())?;
+        Some(Self { len: metadata.len(), modified: metadata.modified().ok() })
+    }
+}
+
+/// Decides when changed files have finished being written.
+///
+/// A file is ready once no other process has it open, or, when it is held
+/// open or open files cannot be listed, once its size and modification time
+/// stay the same for the required number of polls in a row. Missing files
+/// and directories are ready straight away.
+#[derive(Debug)]
+pub struct SettleTracker {
+    required_polls: u32,
+    /// Last stamp seen and how many polls in a row it has not changed
+    pending: HashMap<PathBuf, (Option<FileStamp>, u32)>,
+}
+
+impl SettleTracker {
+    pub fn new(required_polls: u32) -> Self {
+        Self { required_polls: required_polls.max(1), pending: HashMap::new() }
+    }
+
+    /// Start or restart waiting for a file that just changed
+    pub fn touch(&mut self, path: &Path) {
+        self.pending.insert(path.to_path_buf(), (None, 0));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Stop waiting for every file at or below a path
+    pub fn forget_below(&mut self, path: &Path) {
+        self.pending.retain(|pending, _| !pending.starts_with(path));
+    }
+
+    /// Poll every waiting file, returning the ones that are ready to sync.
+    /// Open files are listed once for all of them, off the async runtime.
+    pub async fn poll(&mut self) -> Vec<PathBuf> {
+        let paths: Vec<PathBuf> = self.pending.keys().cloned().collect();
+        let scan = tokio::task::spawn_blocking(move || {
+            let held_open = files_held_open(&paths);
+            let stamps: Vec<_> = paths.iter().map(|path| FileStamp::of(path)).collect();
+            (paths, stamps, held_open)
+        });
+        let (paths, stamps, held_open) = match scan.await {
+            Ok(scan) => scan,
+            Err(e) => {
+                warn!("Failed to check changed files: {}", e);
+                return Vec::new();
+            }
+        };
+
+        paths.into_iter()
+            .zip(stamps)
+            .filter(|(path, stamp)| {
+                let held = held_open.as_ref().map(|held| held.contains(path));
+                self.observe(path, *stamp, held)
+            })
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    /// Record one poll of a file, given its stamp and whether another process
+    /// holds it open (`None` when unknown). Returns whether it is ready, in
+    /// which case it stops being tracked.
+    pub fn observe(&mut self, path: &Path, stamp: Option<FileStamp>, held_open: Option<bool>) -> bool {
+        let Some(entry) = self.pending.get_mut(path) else {
+            return false;
+        };
+
+        let ready = match stamp {
+            None => true,
+            Some(_) if held_open == Some(false) => true,
+            Some(stamp) => {
+                let (last, stable_polls) = entry;
+                if *last == Some(stamp) {
+                    *stable_polls += 1;
+                } else {
+                    *last = Some(stamp);
+                    *stable_polls = 0;
+                }
+                *stable_polls >= self.required_polls
+            }
+        };
+
+        if ready {
+            self.pending.remove(path);
+        }
+        ready
+    }
+}
+
+/// Which of the given files another process has open, from `/proc/<pid>/fd`.
+/// `None` where `/proc` cannot be read, such as outside Linux.
+pub fn files_held_open(paths: &[PathBuf]) -> Option<HashSet<PathBuf>> {
+    let wanted: HashMap<PathBuf, &PathBuf> = paths.iter()
+        .filter_map(|path| Some((std::fs::canonicalize(path).ok()?, path)))
+        .collect();
+    let mut held = HashSet::new();
+    if wanted.is_empty() {
+        return Some(held);
+    }
+
+    let own_pid = std::process::id().to_string();
+    for process in std::fs::read_dir("/proc").ok()?.flatten() {
+        let pid = process.file_name();
+        let pid = pid.to_string_lossy();
+        if pid == own_pid || !pid.bytes().all(|b| b.is_ascii_digit()) {
+            continue;
+        }
+
+        // Processes of other users, or ones that just exited, are skipped
+        let Ok(fds) = std::fs::read_dir(process.path().join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            if let Some(path) = std::fs::read_link(fd.path()).ok().and_then(|target| wanted.get(&target)) {
+                held.insert(path.to_path_buf());
+            }
+        }
+    }
+
+    Some(held)
+}
+
+/// `files_held_open` on the blocking thread pool, since it reads the open
+/// files of every process. Open files that cannot be listed count as none.
+pub async fn scan_files_held_open(paths: Vec<PathBuf>) -> HashSet<PathBuf> {
+    if paths.is_empty() {
+        return HashSet::new();
+    }
+    tokio::task::spawn_blocking(move || files_held_open(&paths))
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+/// Whether another process is writing to a file right now: it is among the
+/// files `held_open`, from one `scan_files_held_open` for a whole batch, and
+/// its size or modification time changes within one poll interval
+pub async fn is_being_written(path: &Path, held_open: &HashSet<PathBuf>) -> bool {
+    if !held_open.contains(path) {
+        return false;
+    }
+
+    let stamp = || async {
+        let metadata = tokio::fs::metadata(path).await.ok().filter(|metadata| metadata.is_file())?;
+        Some(FileStamp { len: metadata.len(), modified: metadata.modified().ok() })
+    };
+    let before = stamp().await;
+    sleep(SETTLE_POLL_INTERVAL).await;
+    stamp().await != before
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stamp(len: u64) -> Option<FileStamp> {
+        Some(FileStamp { len, modified: Some(SystemTime::UNIX_EPOCH) })
+    }
+
+    #[test]
+    fn test_file_held_open_must_stop_changing() {
+        let path = Path::new("/saves/slot1.sav");
+        let mut tracker = SettleTracker::new(2);
+        tracker.touch(path);
+
+        assert!(!tracker.observe(path, stamp(100), Some(true)));
+        assert!(!tracker.observe(path, stamp(200), Some(true)));
+        assert!(!tracker.observe(path, stamp(200), None));
+        assert!(tracker.observe(path, stamp(200), Some(true)));
+        assert!(tracker.is_empty());
+
+        // A new change restarts the wait
+        tracker.touch(path);
+        assert!(!tracker.observe(path, stamp(200), Some(true)));
+        tracker.touch(path);
+        assert!(!tracker.observe(path, stamp(200), Some(true)));
+
+        tracker.forget_below(Path::new("/saves"));
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn test_closed_and_missing_files_are_ready() {
+        let mut tracker = SettleTracker::new(3);
+        let closed = Path::new("/saves/slot1.sav");
+        let deleted = Path::new("/saves/slot2.sav");
+        tracker.touch(closed);
+        tracker.touch(deleted);
+
+        assert!(tracker.observe(closed, stamp(100), Some(false)));
+        assert!(tracker.observe(deleted, None, None));
+        assert!(!tracker.observe(Path::new("/saves/untracked.sav"), stamp(1), Some(false)));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_detects_files_other_processes_hold_open() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("slot1.sav");
+        let file = std::fs::File::create(&path).unwrap();
+
+        // Files this process has open do not count
+        let held = files_held_open(std::slice::from_ref(&path)).unwrap();
+        assert!(held.is_empty());
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .stdin(file)
+            .spawn()
+            .unwrap();
+        let held = files_held_open(std::slice::from_ref(&path)).unwrap();
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(held.contains(&path));
+
+        let mut tracker = SettleTracker::new(3);
+        tracker.touch(&path);
+        assert_eq!(tracker.poll().await, [path.clone()]);
+    }
+}
@@ -0,0 +1,339 @@
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use super::{proton, SteamGame};
+
+/// Offset between a SteamID64 and the account ID used for `userdata/<id>`
+const STEAM_ID64_BASE: u64 = 76561197960265728;
+
+/// A Ludusavi-format save location database (`manifest.yaml`), keyed by game title
+#[derive(Debug, Default)]
+pub struct LudusaviManifest {
+    games: HashMap<String, ManifestGame>,
+    by_steam_id: HashMap<u64, String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestGame {
+    /// Title this entry redirects to
+    #[serde(default)]
+    pub alias: Option<String>,
+    #[serde(default)]
+    pub files: HashMap<String, Option<ManifestFile>>,
+    #[serde(default)]
+    pub registry: HashMap<String, Option<serde_yaml::Value>>,
+    #[serde(default)]
+    pub install_dir: HashMap<String, Option<serde_yaml::Value>>,
+    #[serde(default)]
+    pub steam: Option<ManifestSteam>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ManifestFile {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub when: Vec<ManifestConstraint>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ManifestConstraint {
+    #[serde(default)]
+    pub os: Option<String>,
+    #[serde(default)]
+    pub store: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ManifestSteam {
+    #[serde(default)]
+    pub id: Option<u64>,
+}
+
+/// Save locations the manifest suggests for one detected game
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestSavePaths {
+    pub title: String,
+    /// Expanded paths; may contain `*` where a value is unknown
+    pub paths: Vec<String>,
+    /// Saves live only in the Windows registry, so there are no files to sync
+    pub registry_only: bool,
+}
+
+impl LudusaviManifest {
+    /// Default location: `manifest.yaml` next to the config file
+    pub fn default_path() -> Result<PathBuf> {
+        let project_dirs = ProjectDirs::from("com", "decksaves", "game-sync")
+            .context("Failed to get project directories")?;
+        Ok(project_dirs.config_dir().join("manifest.yaml"))
+    }
+
+    /// Load the manifest from its default location, if one has been placed there
+    pub fn load_default() -> Result<Option<Self>> {
+        let path = Self::default_path()?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        Self::load(&path).map(Some)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read Ludusavi manifest {:?}", path))?;
+        Self::parse(&content)
+            .with_context(|| format!("Failed to parse Ludusavi manifest {:?}", path))
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let games: HashMap<String, ManifestGame> = serde_yaml::from_str(content)?;
+        let by_steam_id = games.iter()
+            .filter_map(|(title, game)| Some((game.steam.as_ref()?.id?, title.clone())))
+            .collect();
+
+        debug!("Loaded Ludusavi manifest with {} games", games.len());
+        Ok(Self { games, by_steam_id })
+    }
+
+    pub fn len(&self) -> usize {
+        self.games.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.games.is_empty()
+    }
+
+    /// Entry for a Steam game, by app ID and then by title, following aliases
+    pub fn find(&self, steam_game: &SteamGame) -> Option<(&str, &ManifestGame)> {
+        let title = steam_game.app_id.parse::<u64>().ok()
+            .and_then(|id| self.by_steam_id.get(&id))
+            .map(String::as_str)
+            .or_else(|| self.games.get_key_value(&steam_game.name).map(|(title, _)| title.as_str()))?;
+
+        let (title, game) = self.games.get_key_value(title)?;
+        match game.alias.as_deref().and_then(|alias| self.games.get_key_value(alias)) {
+            Some((alias, target)) => Some((alias.as_str(), target)),
+            None => Some((title.as_str(), game)),
+        }
+    }
+
+    /// Expand the manifest's save paths for a detected Steam game.
+    ///
+    /// `steam_root` is the Steam install (for `<storeUserId>` lookups) and
+    /// `prefix` the game's Proton prefix, which hosts the Windows placeholders.
+    pub fn save_paths(&self, steam_game: &SteamGame, prefix: Option<&Path>) -> Option<ManifestSavePaths> {
+        let (title, game) = self.find(steam_game)?;
+        let placeholders = Placeholders::for_steam_game(steam_game, prefix);
+
+        let mut paths: Vec<String> = game.files.iter()
+            .filter(|(_, file)| file.as_ref().is_none_or(|file| placeholders.applies(&file.when)))
+            .filter_map(|(template, _)| placeholders.expand(template))
+            .collect();
+        paths.sort();
+        paths.dedup();
+
+        Some(ManifestSavePaths {
+            title: title.to_string(),
+            paths,
+            registry_only: game.files.is_empty() && !game.registry.is_empty(),
+        })
+    }
+}
+
+/// Values for the path placeholders of one game
+struct Placeholders {
+    values: HashMap<&'static str, String>,
+    operating_systems: Vec<&'static str>,
+}
+
+impl Placeholders {
+    fn for_steam_game(steam_game: &SteamGame, prefix: Option<&Path>) -> Self {
+        let mut values = HashMap::new();
+        let mut operating_systems = vec![std::env::consts::OS];
+
+        // SteamGame.library_path is the steamapps directory of the library
+        let library = Path::new(&steam_game.library_path);
+        let root = library.parent().unwrap_or(library);
+        values.insert("root", slash_path(root));
+        values.insert("game", steam_game.install_dir.clone());
+        values.insert("base", slash_path(&library.join("common").join(&steam_game.install_dir)));
+        values.insert("storeGameId", steam_game.app_id.clone());
+        values.insert("storeUserId", steam_game.last_owner.as_deref()
+            .and_then(|owner| owner.parse::<u64>().ok())
+            .and_then(|id64| id64.checked_sub(STEAM_ID64_BASE))
+            .map(|account_id| account_id.to_string())
+            .unwrap_or_else(|| "*".to_string()));
+
+        if let Some(home) = dirs::home_dir() {
+            values.insert("home", slash_path(&home));
+        }
+        if let Some(data) = dirs::data_dir() {
+            values.insert("xdgData", slash_path(&data));
+        }
+        if let Some(config) = dirs::config_dir() {
+            values.insert("xdgConfig", slash_path(&config));
+        }
+        let user_name = std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_default();
+        values.insert("osUserName", user_name);
+
+        let windows_root = match prefix {
+            Some(prefix) => Some((prefix.join("drive_c"), prefix.join(proton::PREFIX_USER_DIR))),
+            None if cfg!(windows) => dirs::home_dir()
+                .map(|home| (PathBuf::from(r"C:\"), home)),
+            None => None,
+        };
+        if let Some((drive, profile)) = windows_root {
+            if prefix.is_some() {
+                // Windows paths under Proton resolve inside the prefix
+                operating_systems.push("windows");
+                values.insert("home", slash_path(&profile));
+                values.insert("osUserName", "steamuser".to_string());
+            }
+            values.insert("winAppData", slash_path(&profile.join("AppData").join("Roaming")));
+            values.insert("winLocalAppData", slash_path(&profile.join("AppData").join("Local")));
+            values.insert("winLocalAppDataLow", slash_path(&profile.join("AppData").join("LocalLow")));
+            values.insert("winDocuments", slash_path(&profile.join("Documents")));
+            values.insert("winPublic", slash_path(&drive.join("users").join("Public")));
+            values.insert("winProgramData", slash_path(&drive.join("ProgramData")));
+            values.insert("winDir", slash_path(&drive.join("windows")));
+        }
+
+        Self { values, operating_systems }
+    }
+
+    /// Whether an entry's `when` constraints match this game on this machine
+    fn applies(&self, constraints: &[ManifestConstraint]) -> bool {
+        constraints.is_empty() || constraints.iter().any(|constraint| {
+            constraint.os.as_deref().is_none_or(|os| self.operating_systems.contains(&os))
+                && constraint.store.as_deref().is_none_or(|store| store == "steam")
+        })
+    }
+
+    /// Substitute every `<placeholder>`; `None` if one has no value here
+    fn expand(&self, template: &str) -> Option<String> {
+        let mut expanded = String::with_capacity(template.len());
+        let mut rest = template;
+
+        while let Some(start) = rest.find('<') {
+            expanded.push_str(&rest[..start]);
+            let end = rest[start..].find('>')? + start;
+            expanded.push_str(self.values.get(&rest[start + 1..end])?);
+            rest = &rest[end + 1..];
+        }
+        expanded.push_str(rest);
+
+        Some(expanded)
+    }
+}
+
+fn slash_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Entries in the shape of the upstream Ludusavi manifest
+    const MANIFEST: &str = r#"
+ELDEN RING:
+  files:
+    <winAppData>/EldenRing:
+      tags:
+        - save
+      when:
+        - os: windows
+  installDir:
+    ELDEN RING: {}
+  steam:
+    id: 1245620
+Hades:
+  files:
+    <base>/Saves:
+      when:
+        - os: windows
+          store: epic
+    <home>/Saved Games/Hades:
+      tags:
+        - save
+      when:
+        - os: windows
+    <root>/userdata/<storeUserId>/<storeGameId>/remote:
+      tags:
+        - save
+      when:
+        - store: steam
+  steam:
+    id: 1145360
+Old Registry Game:
+  registry:
+    HKEY_CURRENT_USER/Software/Old Game:
+      tags:
+        - save
+  steam:
+    id: 4000
+Elden Ring:
+  alias: ELDEN RING
+"#;
+
+    fn steam_game(app_id: &str, name: &str, install_dir: &str) -> SteamGame {
+        SteamGame {
+            app_id: app_id.to_string(),
+            name: name.to_string(),
+            install_dir: install_dir.to_string(),
+            library_path: "/run/media/mmcblk0p1/steamapps".to_string(),
+            last_updated: None,
+            size_on_disk: None,
+            state_flags: Some(4),
+            last_owner: Some("76561198012345678".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_expands_windows_placeholders_inside_proton_prefix() {
+        let manifest = LudusaviManifest::parse(MANIFEST).unwrap();
+        let prefix = Path::new("/run/media/mmcblk0p1/steamapps/compatdata/1245620/pfx");
+
+        let suggestions = manifest.save_paths(&steam_game("1245620", "Elden Ring Renamed", "ELDEN RING"), Some(prefix)).unwrap();
+        assert_eq!(suggestions.title, "ELDEN RING");
+        assert_eq!(
+            suggestions.paths,
+            ["/run/media/mmcblk0p1/steamapps/compatdata/1245620/pfx/drive_c/users/steamuser/AppData/Roaming/EldenRing"]
+        );
+        assert!(!suggestions.registry_only);
+
+        // Without a prefix there is nowhere for a Windows-only path to live on Linux
+        if !cfg!(windows) {
+            let native = manifest.save_paths(&steam_game("1245620", "ELDEN RING", "ELDEN RING"), None).unwrap();
+            assert!(native.paths.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_store_constraints_and_steam_user_id() {
+        let manifest = LudusaviManifest::parse(MANIFEST).unwrap();
+        let prefix = Path::new("/pfx");
+        let suggestions = manifest.save_paths(&steam_game("1145360", "Hades", "Hades"), Some(prefix)).unwrap();
+
+        assert_eq!(suggestions.paths, [
+            "/pfx/drive_c/users/steamuser/Saved Games/Hades",
+            "/run/media/mmcblk0p1/userdata/52079950/1145360/remote",
+        ]);
+    }
+
+    #[test]
+    fn test_registry_only_games_are_flagged_and_aliases_resolve() {
+        let manifest = LudusaviManifest::parse(MANIFEST).unwrap();
+        let registry = manifest.save_paths(&steam_game("4000", "Old Registry Game", "Old"), None).unwrap();
+        assert!(registry.registry_only);
+        assert!(registry.paths.is_empty());
+
+        let (title, _) = manifest.find(&steam_game("1", "Elden Ring", "ELDEN RING")).unwrap();
+        assert_eq!(title, "ELDEN RING");
+        assert!(manifest.find(&steam_game("2", "Unknown", "Unknown")).is_none());
+    }
+}
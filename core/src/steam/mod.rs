@@ -0,0 +1,610 @@
+use anyhow::{Result, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::fs;
+use tracing::{info, warn, debug, error};
+
+pub mod ludusavi;
+pub mod proton;
+pub mod vdf;
+
+pub use ludusavi::{LudusaviManifest, ManifestSavePaths};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SteamGame {
+    pub app_id: String,
+    pub name: String,
+    pub install_dir: String,
+    pub library_path: String,
+    pub last_updated: Option<u64>,
+    pub size_on_disk: Option<u64>,
+    /// Install state bit flags from the app manifest; 4 means fully installed
+    #[serde(default)]
+    pub state_flags: Option<u32>,
+    /// SteamID64 of the account that owns the install
+    #[serde(default)]
+    pub last_owner: Option<String>,
+}
+
+impl SteamGame {
+    pub fn is_fully_installed(&self) -> bool {
+        self.state_flags.is_none_or(|flags| flags & 4 != 0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SteamLibrary {
+    pub path: String,
+    pub label: String,
+    pub mounted: bool,
+    pub tool: bool,
+    /// App IDs installed in this library, as recorded by libraryfolders.vdf
+    #[serde(default)]
+    pub apps: Vec<String>,
+}
+
+pub struct SteamDetector {
+    steam_path: Option<PathBuf>,
+    libraries: Vec<SteamLibrary>,
+    manifest: Option<LudusaviManifest>,
+}
+
+impl SteamDetector {
+    pub fn new() -> Result<Self> {
+        debug!("Creating new SteamDetector");
+        let steam_path = Self::find_steam_installation()?;
+        debug!("Found Steam installation at: {:?}", steam_path);
+        
+        Ok(Self::with_steam_path(steam_path))
+    }
+
+    /// Detector for a known Steam root, skipping installation discovery
+    pub fn with_steam_path(steam_path: impl Into<PathBuf>) -> Self {
+        Self {
+            steam_path: Some(steam_path.into()),
+            libraries: Vec::new(),
+            manifest: None,
+        }
+    }
+
+    /// Use a Ludusavi manifest as the save location database
+    pub fn with_manifest(mut self, manifest: LudusaviManifest) -> Self {
+        self.manifest = Some(manifest);
+        self
+    }
+
+    pub fn steam_path(&self) -> Option<&Path> {
+        self.steam_path.as_deref()
+    }
+
+    pub fn libraries(&self) -> &[SteamLibrary] {
+        &self.libraries
+    }
+
+    fn find_steam_installation() -> Result<PathBuf> {
+        debug!("Starting Steam installation search");
+
+        // Check registry for Steam path (Windows)
+        #[cfg(windows)]
+        {
+            debug!("Checking Windows registry for Steam path");
+            if let Ok(steam_path) = Self::get_steam_path_from_registry() {
+                debug!("Found Steam path in registry: {:?}", steam_path);
+                if steam_path.exists() {
+                    debug!("Registry Steam path exists, using it");
+                    return Ok(steam_path);
+                } else {
+                    debug!("Registry Steam path does not exist: {:?}", steam_path);
+                }
+            } else {
+                debug!("Failed to get Steam path from registry");
+            }
+        }
+
+        let home_dir = dirs::home_dir();
+        Self::find_steam_installation_in(home_dir.as_deref()).ok_or_else(|| {
+            error!("Steam installation not found in any common locations");
+            anyhow::anyhow!("Steam installation not found")
+        })
+    }
+
+    /// Probe the common install locations, with Linux ones resolved against `home_dir`
+    pub fn find_steam_installation_in(home_dir: Option<&Path>) -> Option<PathBuf> {
+        let potential_paths = Self::potential_steam_paths(home_dir);
+        debug!("Checking potential Steam paths: {:?}", potential_paths);
+
+        for path in potential_paths {
+            debug!("Checking path: {:?}", path);
+            if Self::is_steam_root(&path) {
+                // ~/.steam/steam is usually a symlink to the real install
+                let path = path.canonicalize().unwrap_or(path);
+                debug!("Found Steam installation at: {:?}", path);
+                return Some(path);
+            }
+        }
+
+        None
+    }
+
+    fn potential_steam_paths(home_dir: Option<&Path>) -> Vec<PathBuf> {
+        // Common Steam installation paths on Windows
+        let mut potential_paths = vec![
+            PathBuf::from(r"C:\Program Files (x86)\Steam"),
+            PathBuf::from(r"C:\Program Files\Steam"),
+            PathBuf::from(r"D:\Steam"),
+            PathBuf::from(r"E:\Steam"),
+        ];
+
+        // Native, distro-packaged and Flatpak installs on Linux and the Steam Deck
+        if let Some(home) = home_dir {
+            potential_paths.extend([
+                home.join(".steam").join("steam"),
+                home.join(".local").join("share").join("Steam"),
+                home.join(".steam").join("root"),
+                home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"),
+                home.join(".var/app/com.valvesoftware.Steam/data/Steam"),
+            ]);
+        }
+
+        potential_paths
+    }
+
+    fn is_steam_root(path: &Path) -> bool {
+        path.join("steam.exe").exists()
+            || path.join("steam.sh").exists()
+            || path.join("steamapps").is_dir()
+    }
+
+    #[cfg(windows)]
+    fn get_steam_path_from_registry() -> Result<PathBuf> {
+        use winreg::enums::*;
+        use winreg::RegKey;
+
+        let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
+        let steam_key = hklm.open_subkey(r"SOFTWARE\WOW6432Node\Valve\Steam")?;
+        let install_path: String = steam_key.get_value("InstallPath")?;
+        Ok(PathBuf::from(install_path))
+    }
+
+    #[cfg(not(windows))]
+    fn _get_steam_path_from_registry() -> Result<PathBuf> {
+        Err(anyhow::anyhow!("Registry access not available on this platform"))
+    }
+
+    pub async fn discover_games(&mut self) -> Result<Vec<SteamGame>> {
+        debug!("Starting Steam game discovery");
+        
+        self.load_library_folders().await?;
+        debug!("Found {} Steam libraries to scan", self.libraries.len());
+        
+        let mut all_games = Vec::new();
+
+        for library in self.libraries.iter().filter(|library| library.mounted) {
+            debug!("Scanning library: {}", library.path);
+            let games = self.scan_library_for_games(&library.path).await?;
+            debug!("Found {} games in library: {}", games.len(), library.path);
+            all_games.extend(games);
+        }
+
+        info!("Discovered {} Steam games total", all_games.len());
+        debug!("All discovered games: {:?}", all_games.iter().map(|g| &g.name).collect::<Vec<_>>());
+        Ok(all_games)
+    }
+
+    async fn load_library_folders(&mut self) -> Result<()> {
+        debug!("Loading Steam library folders");
+        
+        let steam_path = self.steam_path.as_ref()
+            .context("Steam path not found")?;
+
+        // Current clients keep the list under steamapps/, older ones under config/
+        let config_path = [
+            steam_path.join("steamapps").join("libraryfolders.vdf"),
+            steam_path.join("config").join("libraryfolders.vdf"),
+        ]
+        .into_iter()
+        .find(|path| path.exists());
+        debug!("Using libraryfolders.vdf at: {:?}", config_path);
+
+        let Some(config_path) = config_path else {
+            warn!("Steam libraryfolders.vdf not found under: {:?}", steam_path);
+            // Add default library
+            let default_library = SteamLibrary {
+                path: steam_path.join("steamapps").to_string_lossy().to_string(),
+                label: "Main".to_string(),
+                mounted: true,
+                tool: false,
+                apps: Vec::new(),
+            };
+            debug!("Adding default library: {:?}", default_library);
+            self.libraries = vec![default_library];
+            return Ok(());
+        };
+
+        debug!("Reading libraryfolders.vdf file");
+        let content = fs::read_to_string(&config_path)
+            .context("Failed to read libraryfolders.vdf")?;
+
+        debug!("Parsing library folders from VDF content (length: {})", content.len());
+        self.libraries = self.parse_library_folders(&content)?;
+        debug!("Found {} Steam libraries", self.libraries.len());
+        for lib in &self.libraries {
+            debug!("Library: {} at path: {}", lib.label, lib.path);
+        }
+        Ok(())
+    }
+
+    fn parse_library_folders(&self, content: &str) -> Result<Vec<SteamLibrary>> {
+        let mut libraries: Vec<SteamLibrary> = Vec::new();
+
+        for folder in vdf::parse_library_folders(content)? {
+            let library_path = PathBuf::from(&folder.path).join("steamapps");
+
+            // Libraries on SD cards or external drives are listed even while unmounted
+            let mounted = library_path.is_dir();
+            if !mounted {
+                debug!("Steam library not currently mounted: {:?}", library_path);
+            }
+
+            let mut apps: Vec<String> = folder.apps.into_keys().collect();
+            apps.sort();
+
+            libraries.push(SteamLibrary {
+                path: library_path.to_string_lossy().to_string(),
+                label: if folder.label.is_empty() { "Steam Library".to_string() } else { folder.label },
+                mounted,
+                tool: false,
+                apps,
+            });
+        }
+
+        // The main library is implied even when the file does not list it
+        if let Some(steam_path) = &self.steam_path {
+            let main_library = steam_path.join("steamapps");
+            let listed = libraries.iter()
+                .any(|library| Self::same_path(Path::new(&library.path), &main_library));
+            if !listed {
+                libraries.insert(0, SteamLibrary {
+                    path: main_library.to_string_lossy().to_string(),
+                    label: "Main".to_string(),
+                    mounted: main_library.is_dir(),
+                    tool: false,
+                    apps: Vec::new(),
+                });
+            }
+        }
+
+        // The same library can be listed through a symlinked Steam root
+        let mut unique: Vec<SteamLibrary> = Vec::new();
+        for library in libraries {
+            if !unique.iter().any(|seen| Self::same_path(Path::new(&seen.path), Path::new(&library.path))) {
+                unique.push(library);
+            }
+        }
+
+        Ok(unique)
+    }
+
+    /// Library that libraryfolders.vdf says holds an app
+    pub fn library_for_app(&self, app_id: &str) -> Option<&SteamLibrary> {
+        self.libraries.iter().find(|library| library.apps.iter().any(|id| id == app_id))
+    }
+
+    fn same_path(a: &Path, b: &Path) -> bool {
+        match (a.canonicalize(), b.canonicalize()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => a == b,
+        }
+    }
+
+    async fn scan_library_for_games(&self, library_path: &str) -> Result<Vec<SteamGame>> {
+        let library_dir = Path::new(library_path);
+        let common_dir = library_dir.join("common");
+        
+        if !common_dir.exists() {
+            debug!("Common directory not found: {:?}", common_dir);
+            return Ok(Vec::new());
+        }
+
+        let mut games = Vec::new();
+        
+        // Read appmanifest files to get game information
+        let appmanifest_pattern = library_dir.join("appmanifest_*.acf");
+        let manifest_files = glob::glob(&appmanifest_pattern.to_string_lossy())
+            .map_err(|e| anyhow::anyhow!("Glob pattern error: {}", e))?;
+
+        for manifest_path in manifest_files {
+            match manifest_path {
+                Ok(path) => {
+                    match self.parse_app_manifest(&path, library_path).await {
+                        Ok(game) if game.is_fully_installed() => games.push(game),
+                        Ok(game) => debug!("Skipping {} (state flags {:?}), not fully installed", game.name, game.state_flags),
+                        Err(e) => warn!("Skipping unreadable manifest: {:#}", e),
+                    }
+                }
+                Err(e) => {
+                    warn!("Error reading manifest file: {}", e);
+                }
+            }
+        }
+
+        Ok(games)
+    }
+
+    async fn parse_app_manifest(&self, manifest_path: &Path, library_path: &str) -> Result<SteamGame> {
+        let content = fs::read_to_string(manifest_path)
+            .context("Failed to read app manifest")?;
+
+        let manifest = vdf::parse_app_manifest(&content)
+            .with_context(|| format!("Failed to parse app manifest {:?}", manifest_path))?;
+
+        Ok(SteamGame {
+            app_id: manifest.app_id,
+            name: manifest.name,
+            install_dir: manifest.install_dir,
+            library_path: library_path.to_string(),
+            last_updated: manifest.last_updated,
+            size_on_disk: manifest.size_on_disk,
+            state_flags: manifest.state_flags,
+            last_owner: manifest.last_owner,
+        })
+    }
+
+    /// Suggested save paths that exist on this machine, with wildcards such as
+    /// an unknown Steam user ID expanded
+    pub fn existing_save_paths(&self, steam_game: &SteamGame) -> Vec<String> {
+        let mut existing = Vec::new();
+        for path in self.get_common_save_paths(steam_game) {
+            let matches: Vec<PathBuf> = match glob::glob(&path) {
+                Ok(matches) => matches.filter_map(|m| m.ok()).collect(),
+                Err(_) => vec![PathBuf::from(&path)],
+            };
+            for path in matches.into_iter().filter(|path| path.exists()) {
+                let path = path.to_string_lossy().to_string();
+                if !existing.contains(&path) {
+                    existing.push(path);
+                }
+            }
+        }
+        existing
+    }
+
+    /// Proton prefix of a game, if it has been run through Proton.
+    /// Steam usually creates it in the game's own library, but older installs
+    /// can keep it in the main library.
+    pub fn proton_prefix(&self, steam_game: &SteamGame) -> Option<PathBuf> {
+        let mut libraries = vec![PathBuf::from(&steam_game.library_path)];
+        if let Some(steam_path) = &self.steam_path {
+            libraries.push(steam_path.join("steamapps"));
+        }
+
+        libraries.into_iter()
+            .map(|library| proton::prefix_path(&library, &steam_game.app_id))
+            .find(|prefix| prefix.is_dir())
+    }
+
+    /// Save locations the Ludusavi manifest lists for a game, if one is loaded
+    /// and knows the game
+    pub fn manifest_save_paths(&self, steam_game: &SteamGame) -> Option<ManifestSavePaths> {
+        let manifest = self.manifest.as_ref()?;
+        manifest.save_paths(steam_game, self.proton_prefix(steam_game).as_deref())
+    }
+
+    pub fn get_common_save_paths(&self, steam_game: &SteamGame) -> Vec<String> {
+        let mut save_paths = Vec::new();
+
+        // Manifest entries are curated, so they come first
+        let manifest_paths = self.manifest_save_paths(steam_game);
+        if let Some(manifest_paths) = &manifest_paths {
+            if manifest_paths.registry_only {
+                warn!("{} keeps its saves in the Windows registry only; there are no save files to sync", steam_game.name);
+            }
+            save_paths.extend(manifest_paths.paths.iter().cloned());
+        }
+        
+        // Steam userdata directory (Steam Cloud saves)
+        if let Some(steam_path) = &self.steam_path {
+            let userdata_pattern = steam_path.join("userdata").join("*").join(&steam_game.app_id);
+            save_paths.push(userdata_pattern.to_string_lossy().to_string());
+        }
+
+        // Game installation directory
+        let install_path = Path::new(&steam_game.library_path)
+            .join("common")
+            .join(&steam_game.install_dir);
+        save_paths.push(install_path.to_string_lossy().to_string());
+
+        let prefix = self.proton_prefix(steam_game);
+        if let Some(prefix) = &prefix {
+            // Windows games under Proton keep their saves in the prefix's user profile
+            debug!("Using Proton prefix for {}: {:?}", steam_game.name, prefix);
+            for pattern in [
+                r"%USERPROFILE%\Documents\My Games",
+                r"%USERPROFILE%\Documents",
+                r"%USERPROFILE%\Saved Games",
+                r"%APPDATA%",
+                r"%LOCALAPPDATA%",
+                r"%USERPROFILE%\AppData\LocalLow",
+            ] {
+                if let Some(path) = proton::translate_windows_path(pattern, prefix) {
+                    save_paths.push(path.join(&steam_game.name).to_string_lossy().to_string());
+                }
+            }
+        } else {
+            // Common save file locations for Steam games
+            let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+            let documents_dir = dirs::document_dir().unwrap_or_else(|| home_dir.join("Documents"));
+
+            // Documents folder variations
+            save_paths.push(documents_dir.join("My Games").join(&steam_game.name).to_string_lossy().to_string());
+            save_paths.push(documents_dir.join(&steam_game.name).to_string_lossy().to_string());
+            
+            // AppData locations
+            if let Some(appdata) = dirs::data_dir() {
+                save_paths.push(appdata.join(&steam_game.name).to_string_lossy().to_string());
+            }
+            
+            if let Some(local_appdata) = dirs::data_local_dir() {
+                save_paths.push(local_appdata.join(&steam_game.name).to_string_lossy().to_string());
+            }
+        }
+
+        // Known locations for this game, translated the same way, for games the
+        // manifest does not cover
+        if manifest_paths.is_none() {
+            save_paths.extend(
+                get_known_save_paths(&steam_game.name, prefix.as_deref())
+                    .into_iter()
+                    .map(|path| path.to_string_lossy().to_string()),
+            );
+        }
+
+        save_paths
+    }
+}
+
+// Known save game patterns for popular games
+pub fn get_known_save_patterns() -> HashMap<String, Vec<String>> {
+    let mut patterns = HashMap::new();
+    
+    // Add some common games with known save locations
+    patterns.insert("Elden Ring".to_string(), vec![
+        r"%APPDATA%\EldenRing".to_string(),
+    ]);
+    
+    patterns.insert("Cyberpunk 2077".to_string(), vec![
+        r"%USERPROFILE%\Saved Games\CD Projekt Red\Cyberpunk 2077".to_string(),
+    ]);
+    
+    patterns.insert("The Witcher 3".to_string(), vec![
+        r"%USERPROFILE%\Documents\The Witcher 3\gamesaves".to_string(),
+    ]);
+    
+    patterns.insert("Baldur's Gate 3".to_string(), vec![
+        r"%LOCALAPPDATA%\Larian Studios\Baldur's Gate 3\PlayerProfiles".to_string(),
+    ]);
+
+    patterns.insert("Valheim".to_string(), vec![
+        r"%USERPROFILE%\AppData\LocalLow\IronGate\Valheim".to_string(),
+    ]);
+
+    patterns
+}
+
+/// Known save locations of a game, resolved inside its Proton prefix when it
+/// has one and against the host environment on Windows
+pub fn get_known_save_paths(game_name: &str, prefix: Option<&Path>) -> Vec<PathBuf> {
+    get_known_save_patterns()
+        .into_iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case(game_name))
+        .flat_map(|(_, patterns)| patterns)
+        .filter_map(|pattern| proton::resolve_save_pattern(&pattern, prefix))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_manifest(library: &Path, app_id: &str, name: &str, install_dir: &str) {
+        fs::create_dir_all(library.join("common").join(install_dir)).unwrap();
+        fs::write(
+            library.join(format!("appmanifest_{}.acf", app_id)),
+            format!(
+                "\"AppState\"\n{{\n\t\"appid\"\t\t\"{}\"\n\t\"name\"\t\t\"{}\"\n\t\"installdir\"\t\t\"{}\"\n\t\"SizeOnDisk\"\t\t\"1024\"\n}}\n",
+                app_id, name, install_dir
+            ),
+        ).unwrap();
+    }
+
+    #[test]
+    fn test_finds_native_and_flatpak_installs() {
+        let temp = tempfile::tempdir().unwrap();
+        let home = temp.path().to_path_buf();
+        assert_eq!(SteamDetector::find_steam_installation_in(Some(&home)), None);
+
+        let flatpak = home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam");
+        fs::create_dir_all(flatpak.join("steamapps")).unwrap();
+        assert_eq!(
+            SteamDetector::find_steam_installation_in(Some(&home)),
+            Some(flatpak.canonicalize().unwrap())
+        );
+
+        // A native install is preferred, and the ~/.steam/steam symlink resolves to it
+        let native = home.join(".local/share/Steam");
+        fs::create_dir_all(native.join("steamapps")).unwrap();
+        fs::create_dir_all(home.join(".steam")).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&native, home.join(".steam").join("steam")).unwrap();
+        assert_eq!(
+            SteamDetector::find_steam_installation_in(Some(&home)),
+            Some(native.canonicalize().unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_discovers_games_on_sd_card_library() {
+        let temp = tempfile::tempdir().unwrap();
+        let home = temp.path().to_path_buf();
+        let steam_root = home.join(".local/share/Steam");
+        let sd_card = home.join("run/media/mmcblk0p1");
+        let missing_card = home.join("run/media/unplugged");
+
+        write_manifest(&steam_root.join("steamapps"), "1145360", "Hades", "Hades");
+        write_manifest(&sd_card.join("steamapps"), "1091500", "Cyberpunk 2077", "Cyberpunk 2077");
+        fs::write(
+            steam_root.join("steamapps").join("libraryfolders.vdf"),
+            format!(
+                "\"libraryfolders\"\n{{\n\t\"0\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t\t\"label\"\t\t\"\"\n\t\t\"apps\"\n\t\t{{\n\t\t\t\"1145360\"\t\t\"123456\"\n\t\t}}\n\t}}\n\t\"1\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t\t\"label\"\t\t\"SD Card\"\n\t}}\n\t\"2\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t}}\n}}\n",
+                steam_root.display(), sd_card.display(), missing_card.display()
+            ),
+        ).unwrap();
+
+        let mut detector = SteamDetector::with_steam_path(&steam_root);
+        let mut games = detector.discover_games().await.unwrap();
+        games.sort_by(|a, b| a.name.cmp(&b.name));
+
+        assert_eq!(detector.libraries().len(), 3);
+        assert_eq!(detector.libraries()[1].label, "SD Card");
+        assert!(!detector.libraries()[2].mounted);
+        assert_eq!(
+            detector.library_for_app("1145360").map(|library| library.path.as_str()),
+            Some(detector.libraries()[0].path.as_str())
+        );
+
+        let names: Vec<_> = games.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["Cyberpunk 2077", "Hades"]);
+        assert_eq!(games[0].library_path, sd_card.join("steamapps").to_string_lossy());
+    }
+
+    #[test]
+    fn test_save_paths_resolve_inside_proton_prefix() {
+        let temp = tempfile::tempdir().unwrap();
+        let home = temp.path().to_path_buf();
+        let steam_root = home.join(".local/share/Steam");
+        let library = home.join("run/media/mmcblk0p1/steamapps");
+        let prefix = proton::prefix_path(&library, "1245620");
+        fs::create_dir_all(&prefix).unwrap();
+
+        let detector = SteamDetector::with_steam_path(&steam_root);
+        let game = SteamGame {
+            app_id: "1245620".to_string(),
+            name: "Elden Ring".to_string(),
+            install_dir: "ELDEN RING".to_string(),
+            library_path: library.to_string_lossy().to_string(),
+            last_updated: None,
+            size_on_disk: None,
+            state_flags: Some(4),
+            last_owner: None,
+        };
+        assert_eq!(detector.proton_prefix(&game), Some(prefix.clone()));
+
+        let user = prefix.join(proton::PREFIX_USER_DIR);
+        let paths = detector.get_common_save_paths(&game);
+        assert!(paths.contains(&user.join("AppData/Roaming/EldenRing").to_string_lossy().to_string()));
+        assert!(paths.contains(&user.join("Documents/My Games/Elden Ring").to_string_lossy().to_string()));
+        // Nothing points at Windows-style folders in the Linux home
+        assert!(paths.iter().all(|path| path.starts_with(&*steam_root.to_string_lossy()) || path.starts_with(&*library.to_string_lossy())));
+    }
+}
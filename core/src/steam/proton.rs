@@ -0,0 +1,107 @@
+use std::path::{Path, PathBuf};
+
+/// Windows user profile inside a Proton prefix, relative to the `pfx` directory
+pub const PREFIX_USER_DIR: &str = "drive_c/users/steamuser";
+
+/// Proton prefix Steam creates for an app in a library (`<library>/steamapps`)
+pub fn prefix_path(library_path: &Path, app_id: &str) -> PathBuf {
+    library_path.join("compatdata").join(app_id).join("pfx")
+}
+
+/// Windows profile folders as they appear inside the prefix's user directory
+fn profile_folder(variable: &str) -> Option<&'static [&'static str]> {
+    match variable.to_ascii_uppercase().as_str() {
+        "USERPROFILE" => Some(&[]),
+        "APPDATA" => Some(&["AppData", "Roaming"]),
+        "LOCALAPPDATA" => Some(&["AppData", "Local"]),
+        _ => None,
+    }
+}
+
+/// Translate a Windows save path pattern such as `%APPDATA%\EldenRing` into the
+/// matching path inside a Proton prefix.
+///
+/// Returns `None` for patterns that reference an unsupported variable or are
+/// not rooted in the user profile or the C: drive.
+pub fn translate_windows_path(pattern: &str, prefix: &Path) -> Option<PathBuf> {
+    let mut parts = pattern.split(['\\', '/']).filter(|part| !part.is_empty());
+    let first = parts.next()?;
+
+    let mut path = if let Some(variable) = first.strip_prefix('%').and_then(|v| v.strip_suffix('%')) {
+        profile_folder(variable)?
+            .iter()
+            .fold(prefix.join(PREFIX_USER_DIR), |path, part| path.join(part))
+    } else if first.eq_ignore_ascii_case("C:") {
+        prefix.join("drive_c")
+    } else {
+        return None;
+    };
+
+    for part in parts {
+        path.push(part);
+    }
+    Some(path)
+}
+
+/// Expand a Windows save path pattern against this machine's environment
+pub fn expand_windows_path(pattern: &str) -> Option<PathBuf> {
+    let mut parts = pattern.split(['\\', '/']).filter(|part| !part.is_empty());
+    let first = parts.next()?;
+
+    let mut path = match first.strip_prefix('%').and_then(|v| v.strip_suffix('%')) {
+        Some(variable) => PathBuf::from(std::env::var_os(variable)?),
+        None => PathBuf::from(format!("{}\\", first)),
+    };
+
+    for part in parts {
+        path.push(part);
+    }
+    Some(path)
+}
+
+/// Resolve a Windows save path pattern, inside `prefix` when the game runs
+/// under Proton and against the host environment otherwise
+pub fn resolve_save_pattern(pattern: &str, prefix: Option<&Path>) -> Option<PathBuf> {
+    match prefix {
+        Some(prefix) => translate_windows_path(pattern, prefix),
+        None if cfg!(windows) => expand_windows_path(pattern),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translates_profile_variables_into_prefix() {
+        let prefix = prefix_path(Path::new("/run/media/mmcblk0p1/steamapps"), "1245620");
+        assert_eq!(prefix, Path::new("/run/media/mmcblk0p1/steamapps/compatdata/1245620/pfx"));
+
+        let user = prefix.join(PREFIX_USER_DIR);
+        assert_eq!(
+            translate_windows_path(r"%APPDATA%\EldenRing", &prefix),
+            Some(user.join("AppData/Roaming/EldenRing"))
+        );
+        assert_eq!(
+            translate_windows_path(r"%localappdata%\Larian Studios\Baldur's Gate 3", &prefix),
+            Some(user.join("AppData/Local/Larian Studios/Baldur's Gate 3"))
+        );
+        assert_eq!(
+            translate_windows_path(r"%USERPROFILE%\Saved Games\CD Projekt Red\Cyberpunk 2077", &prefix),
+            Some(user.join("Saved Games/CD Projekt Red/Cyberpunk 2077"))
+        );
+        assert_eq!(
+            translate_windows_path(r"C:\ProgramData\Game", &prefix),
+            Some(prefix.join("drive_c/ProgramData/Game"))
+        );
+    }
+
+    #[test]
+    fn test_unsupported_patterns_are_not_translated() {
+        let prefix = Path::new("/pfx");
+        assert_eq!(translate_windows_path(r"%PROGRAMFILES%\Game", prefix), None);
+        assert_eq!(translate_windows_path(r"D:\Saves", prefix), None);
+        assert_eq!(translate_windows_path("", prefix), None);
+    }
+}
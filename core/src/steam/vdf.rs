@@ -0,0 +1,456 @@
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+/// A value in a Valve KeyValues (VDF/ACF) document
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyValue {
+    String(String),
+    Table(KeyValues),
+}
+
+impl KeyValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            KeyValue::String(value) => Some(value),
+            KeyValue::Table(_) => None,
+        }
+    }
+
+    pub fn as_table(&self) -> Option<&KeyValues> {
+        match self {
+            KeyValue::Table(table) => Some(table),
+            KeyValue::String(_) => None,
+        }
+    }
+}
+
+/// An ordered KeyValues table. Keys may repeat and are matched
+/// case-insensitively, as Steam does.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyValues {
+    entries: Vec<(String, KeyValue)>,
+}
+
+impl KeyValues {
+    pub fn get(&self, key: &str) -> Option<&KeyValue> {
+        self.entries.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(KeyValue::as_str)
+    }
+
+    pub fn get_table(&self, key: &str) -> Option<&KeyValues> {
+        self.get(key).and_then(KeyValue::as_table)
+    }
+
+    /// Parse a numeric field, ignoring values that are missing or malformed
+    pub fn get_u64(&self, key: &str) -> Option<u64> {
+        self.get_str(key).and_then(|value| value.trim().parse().ok())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &KeyValue)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Parse a KeyValues text document into its root table
+pub fn parse(text: &str) -> Result<KeyValues> {
+    let mut parser = Parser { tokens: Lexer::new(text), peeked: None };
+    let root = parser.parse_table(false)?;
+    Ok(root)
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Text(String),
+    /// A platform conditional such as `[$WIN32]`, which we do not evaluate
+    Conditional,
+    Open,
+    Close,
+}
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str) -> Self {
+        // Some tools write a UTF-8 byte order mark
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+        Self { chars: text.chars().peekable(), line: 1 }
+    }
+
+    fn next_token(&mut self) -> Result<Option<(Token, usize)>> {
+        loop {
+            match self.chars.peek() {
+                None => return Ok(None),
+                Some('\n') => {
+                    self.line += 1;
+                    self.chars.next();
+                }
+                Some(c) if c.is_whitespace() => {
+                    self.chars.next();
+                }
+                Some('/') => {
+                    self.chars.next();
+                    if self.chars.peek() != Some(&'/') {
+                        return Ok(Some((Token::Text(self.read_bare("/")), self.line)));
+                    }
+                    while self.chars.peek().is_some_and(|c| *c != '\n') {
+                        self.chars.next();
+                    }
+                }
+                Some('{') => {
+                    self.chars.next();
+                    return Ok(Some((Token::Open, self.line)));
+                }
+                Some('}') => {
+                    self.chars.next();
+                    return Ok(Some((Token::Close, self.line)));
+                }
+                Some('"') => {
+                    self.chars.next();
+                    let line = self.line;
+                    return Ok(Some((Token::Text(self.read_quoted(line)?), line)));
+                }
+                Some('[') => {
+                    let line = self.line;
+                    for c in self.chars.by_ref() {
+                        if c == ']' {
+                            return Ok(Some((Token::Conditional, line)));
+                        }
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+                    return Err(anyhow!("Unterminated conditional on line {}", line));
+                }
+                Some(_) => return Ok(Some((Token::Text(self.read_bare("")), self.line))),
+            }
+        }
+    }
+
+    fn read_quoted(&mut self, start_line: usize) -> Result<String> {
+        let mut value = String::new();
+        while let Some(c) = self.chars.next() {
+            match c {
+                '"' => return Ok(value),
+                '\\' => match self.chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(escaped @ ('\\' | '"')) => value.push(escaped),
+                    // Unknown escapes are kept verbatim
+                    Some(other) => {
+                        value.push('\\');
+                        value.push(other);
+                    }
+                    None => break,
+                },
+                '\n' => {
+                    self.line += 1;
+                    value.push(c);
+                }
+                _ => value.push(c),
+            }
+        }
+        Err(anyhow!("Unterminated string starting on line {}", start_line))
+    }
+
+    fn read_bare(&mut self, prefix: &str) -> String {
+        let mut value = prefix.to_string();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() || matches!(c, '{' | '}' | '"') {
+                break;
+            }
+            value.push(c);
+            self.chars.next();
+        }
+        value
+    }
+}
+
+struct Parser<'a> {
+    tokens: Lexer<'a>,
+    peeked: Option<(Token, usize)>,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Result<Option<(Token, usize)>> {
+        match self.peeked.take() {
+            Some(token) => Ok(Some(token)),
+            None => self.tokens.next_token(),
+        }
+    }
+
+    /// Skip a conditional trailing the previous key or value
+    fn skip_conditional(&mut self) -> Result<()> {
+        match self.next()? {
+            Some((Token::Conditional, _)) | None => {}
+            Some(other) => self.peeked = Some(other),
+        }
+        Ok(())
+    }
+
+    fn parse_table(&mut self, nested: bool) -> Result<KeyValues> {
+        let mut table = KeyValues::default();
+
+        loop {
+            let key = match self.next()? {
+                Some((Token::Text(key), _)) => key,
+                Some((Token::Close, _)) if nested => return Ok(table),
+                Some((Token::Close, line)) => return Err(anyhow!("Unexpected '}}' on line {}", line)),
+                Some((Token::Open, line)) => return Err(anyhow!("Expected a key before '{{' on line {}", line)),
+                Some((Token::Conditional, _)) => continue,
+                None if nested => return Err(anyhow!("Unexpected end of file inside a table")),
+                None => return Ok(table),
+            };
+            self.skip_conditional()?;
+
+            let value = match self.next()? {
+                Some((Token::Text(value), _)) => {
+                    self.skip_conditional()?;
+                    KeyValue::String(value)
+                }
+                Some((Token::Open, _)) => KeyValue::Table(self.parse_table(true)?),
+                Some((_, line)) => return Err(anyhow!("Missing value for key '{}' on line {}", key, line)),
+                None => return Err(anyhow!("Missing value for key '{}' at end of file", key)),
+            };
+            table.entries.push((key, value));
+        }
+    }
+}
+
+/// One entry of `libraryfolders.vdf`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryFolder {
+    pub path: String,
+    pub label: String,
+    /// Installed app IDs and their size on disk, when the file lists them
+    pub apps: HashMap<String, u64>,
+}
+
+/// Read the libraries listed in `libraryfolders.vdf`.
+///
+/// Handles both the current layout, where each numbered entry is a table with a
+/// `path` and an `apps` table, and the old one mapping numbers straight to paths.
+pub fn parse_library_folders(text: &str) -> Result<Vec<LibraryFolder>> {
+    let root = parse(text)?;
+    let folders = root.get_table("libraryfolders")
+        .or_else(|| root.get_table("LibraryFolders"))
+        .ok_or_else(|| anyhow!("libraryfolders.vdf has no libraryfolders table"))?;
+
+    let mut libraries = Vec::new();
+    for (key, value) in folders.iter() {
+        // Other top-level keys such as "contentstatsid" are not libraries
+        if key.parse::<u32>().is_err() {
+            continue;
+        }
+
+        match value {
+            KeyValue::String(path) => libraries.push(LibraryFolder {
+                path: path.clone(),
+                label: String::new(),
+                apps: HashMap::new(),
+            }),
+            KeyValue::Table(entry) => {
+                let Some(path) = entry.get_str("path") else {
+                    continue;
+                };
+                let apps = entry.get_table("apps")
+                    .map(|apps| apps.iter()
+                        .map(|(app_id, size)| (app_id.to_string(), size.as_str().and_then(|s| s.parse().ok()).unwrap_or(0)))
+                        .collect())
+                    .unwrap_or_default();
+
+                libraries.push(LibraryFolder {
+                    path: path.to_string(),
+                    label: entry.get_str("label").unwrap_or_default().to_string(),
+                    apps,
+                });
+            }
+        }
+    }
+
+    Ok(libraries)
+}
+
+/// Fields of an `appmanifest_<appid>.acf` file
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppManifest {
+    pub app_id: String,
+    pub name: String,
+    pub install_dir: String,
+    /// Bit flags; 4 means fully installed
+    pub state_flags: Option<u32>,
+    /// SteamID64 of the account that last owned the install
+    pub last_owner: Option<String>,
+    pub size_on_disk: Option<u64>,
+    pub last_updated: Option<u64>,
+}
+
+pub fn parse_app_manifest(text: &str) -> Result<AppManifest> {
+    let root = parse(text)?;
+    let state = root.get_table("AppState")
+        .ok_or_else(|| anyhow!("App manifest has no AppState table"))?;
+
+    let required = |key: &str| {
+        state.get_str(key)
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("{} not found in manifest", key))
+    };
+
+    Ok(AppManifest {
+        app_id: required("appid")?,
+        name: required("name")?,
+        install_dir: required("installdir")?,
+        state_flags: state.get_u64("StateFlags").and_then(|flags| u32::try_from(flags).ok()),
+        last_owner: state.get_str("LastOwner").filter(|owner| !owner.is_empty() && *owner != "0").map(str::to_string),
+        size_on_disk: state.get_u64("SizeOnDisk"),
+        last_updated: state.get_u64("LastUpdated"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Trimmed from a Steam Deck with an SD card library
+    const LIBRARY_FOLDERS: &str = r#""libraryfolders"
+{
+	"0"
+	{
+		"path"		"/home/deck/.local/share/Steam"
+		"label"		""
+		"contentid"		"4218318281342598426"
+		"totalsize"		"0"
+		"update_clean_bytes_tally"		"31583752"
+		"time_last_update_corruption"		"0"
+		"apps"
+		{
+			"228980"		"451244497"
+			"1145360"		"11861271305"
+			"1493710"		"1203413542"
+		}
+	}
+	"1"
+	{
+		"path"		"/run/media/mmcblk0p1"
+		"label"		"SD \"Card\""
+		"contentid"		"8127640294518839452"
+		"totalsize"		"511023325184"
+		"apps"
+		{
+			"1091500"		"70128541696"
+		}
+	}
+}
+"#;
+
+    // Format written by Steam clients before mid-2021, from Windows
+    const LEGACY_LIBRARY_FOLDERS: &str = r#""LibraryFolders"
+{
+	"TimeNextStatsReport"		"1623456789"
+	"ContentStatsID"		"-4218318281342598426"
+	"1"		"D:\\SteamLibrary"
+}
+"#;
+
+    const APP_MANIFEST: &str = r#""AppState"
+{
+	"appid"		"1145360"
+	"Universe"		"1"
+	"LauncherPath"		"C:\\Program Files (x86)\\Steam\\steam.exe"
+	"name"		"Hades"
+	"StateFlags"		"4"
+	"installdir"		"Hades"
+	"LastUpdated"		"1698186346"
+	"SizeOnDisk"		"11861271305"
+	"StagingSize"		"0"
+	"buildid"		"12451345"
+	"LastOwner"		"76561198012345678"
+	"AutoUpdateBehavior"		"0"
+	"AllowOtherDownloadsWhileRunning"		"0"
+	"ScheduledAutoUpdate"		"0"
+	"InstalledDepots"
+	{
+		"1145361"
+		{
+			"manifest"		"2940381834951726102"
+			"size"		"11861271305"
+		}
+	}
+	"UserConfig"
+	{
+		"language"		"english"
+	}
+	"MountedConfig"
+	{
+		"language"		"english"
+	}
+}
+"#;
+
+    #[test]
+    fn test_parses_current_library_folders_with_apps() {
+        let libraries = parse_library_folders(LIBRARY_FOLDERS).unwrap();
+        assert_eq!(libraries.len(), 2);
+
+        assert_eq!(libraries[0].path, "/home/deck/.local/share/Steam");
+        assert_eq!(libraries[0].apps.get("1145360"), Some(&11861271305));
+        assert_eq!(libraries[0].apps.len(), 3);
+
+        assert_eq!(libraries[1].path, "/run/media/mmcblk0p1");
+        assert_eq!(libraries[1].label, "SD \"Card\"");
+        assert!(libraries[1].apps.contains_key("1091500"));
+    }
+
+    #[test]
+    fn test_parses_legacy_library_folders() {
+        let libraries = parse_library_folders(LEGACY_LIBRARY_FOLDERS).unwrap();
+        assert_eq!(libraries.len(), 1);
+        assert_eq!(libraries[0].path, r"D:\SteamLibrary");
+    }
+
+    #[test]
+    fn test_parses_app_manifest_fields() {
+        let manifest = parse_app_manifest(APP_MANIFEST).unwrap();
+        assert_eq!(manifest.app_id, "1145360");
+        assert_eq!(manifest.name, "Hades");
+        assert_eq!(manifest.install_dir, "Hades");
+        assert_eq!(manifest.state_flags, Some(4));
+        assert_eq!(manifest.last_owner.as_deref(), Some("76561198012345678"));
+        assert_eq!(manifest.size_on_disk, Some(11861271305));
+        assert_eq!(manifest.last_updated, Some(1698186346));
+    }
+
+    #[test]
+    fn test_nested_tables_comments_and_conditionals() {
+        let root = parse(
+            "// exported\n\"root\" { \"a\" \"1\" [$WIN32]\n  \"A\" \"shadowed\"\n  child { key value } }",
+        ).unwrap();
+        let table = root.get_table("root").unwrap();
+
+        assert_eq!(table.get_str("a"), Some("1"));
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.get_table("child").and_then(|c| c.get_str("key")), Some("value"));
+    }
+
+    #[test]
+    fn test_malformed_input_is_an_error() {
+        assert!(parse("\"root\" { \"key\" \"value\"").is_err());
+        assert!(parse("\"root\" { \"key\" }").is_err());
+        assert!(parse("\"unterminated").is_err());
+    }
+}
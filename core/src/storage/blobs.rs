@@ -0,0 +1,216 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::chunking::Chunker;
+use super::providers::{StorageProvider, StorageResult};
+use crate::versioning::{calculate_hash, FileVersion, GameVersionManifest};
+
+/// `FileVersion.storage_metadata` key naming the blob that holds a version's content
+pub const BLOB_METADATA_KEY: &str = "blob";
+
+/// `FileVersion.storage_metadata` key listing, comma-separated and in order,
+/// the blobs holding the chunks of a version's content
+pub const CHUNKS_METADATA_KEY: &str = "chunks";
+
+/// Directory (or key prefix) blobs are stored under, next to `games/`
+pub const BLOB_DIR: &str = "blobs";
+
+/// How long an unreferenced blob is kept after it was last uploaded or reused.
+/// A device reusing a stored blob only writes the manifest referencing it
+/// after the upload, so another device collecting garbage in between must not
+/// take the blob for an orphan.
+pub const BLOB_GRACE_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How new versions are laid out in storage.
+///
+/// The layouts coexist: each version records where it was stored, so
+/// switching layout only affects versions uploaded afterwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageLayout {
+    /// `games/<game>/files/<path>/versions/<id>`, one object per version
+    #[default]
+    PerVersion,
+    /// `blobs/<sha256>`, one object per distinct content shared by every
+    /// version, file and game that has it. With encryption, blobs are named by
+    /// a hash keyed from the passphrase instead, which does not reveal content.
+    ContentAddressed,
+    /// Like `ContentAddressed`, but content is split into content-defined
+    /// chunks stored as separate blobs, so a new version of a large file only
+    /// uploads the chunks that changed
+    Chunked,
+}
+
+/// Blob a version is stored in, if it was uploaded with the content-addressed layout
+pub fn version_blob(version: &FileVersion) -> Option<&str> {
+    version.storage_metadata.get(BLOB_METADATA_KEY)
+        .map(String::as_str)
+        .filter(|hash| is_blob_name(hash))
+}
+
+/// Whether a version's content is stored in blobs rather than in an object of
+/// its own. An empty chunked version is, although it lists no chunks.
+pub fn is_stored_in_blobs(version: &FileVersion) -> bool {
+    version.storage_metadata.contains_key(CHUNKS_METADATA_KEY) || version_blob(version).is_some()
+}
+
+/// Every blob a version's content is stored in, in order: its single blob or
+/// its chunks. Empty for versions stored with the per-version layout and for
+/// empty chunked versions.
+pub fn version_blobs(version: &FileVersion) -> Vec<&str> {
+    match version.storage_metadata.get(CHUNKS_METADATA_KEY) {
+        Some(chunks) => chunks.split(',').filter(|hash| !hash.is_empty()).collect(),
+        None => version_blob(version).into_iter().collect(),
+    }
+}
+
+/// Whether a name is a valid blob name (a lowercase hex SHA256), which also
+/// keeps names taken from manifests from escaping the blob directory
+pub fn is_blob_name(name: &str) -> bool {
+    name.len() == 64 && name.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Whether a stored blob that a new version reuses should have its
+/// modification time refreshed to restart its grace period
+pub fn needs_refresh(last_modified: DateTime<Utc>) -> bool {
+    blob_age(last_modified) > BLOB_GRACE_PERIOD / 2
+}
+
+/// Whether an unreferenced blob last modified at the given time may be deleted
+pub fn is_collectable(last_modified: DateTime<Utc>) -> bool {
+    blob_age(last_modified) > BLOB_GRACE_PERIOD
+}
+
+fn blob_age(last_modified: DateTime<Utc>) -> Duration {
+    // Clocks of other devices may be ahead; a timestamp in the future is brand new
+    (Utc::now() - last_modified).to_std().unwrap_or_default()
+}
+
+/// Number of versions referencing each blob across the given manifests
+pub fn count_references<'a>(manifests: impl IntoIterator<Item = &'a GameVersionManifest>) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for manifest in manifests {
+        for file in manifest.files.values() {
+            for hash in file.versions.iter().flat_map(version_blobs).filter(|hash| is_blob_name(hash)) {
+                *counts.entry(hash.to_string()).or_insert(0) += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// Upload data as content-defined chunks, skipping chunks already stored.
+/// Returns the metadata to record on the version.
+pub async fn upload_chunked<P: StorageProvider + ?Sized>(provider: &P, data: &[u8]) -> Result<StorageResult> {
+    let mut hashes = Vec::new();
+    for range in Chunker::default().chunks(data) {
+        let result = provider.upload_blob(&data[range]).await?;
+        if !result.success {
+            return Ok(result);
+        }
+        let hash = result.metadata.get(BLOB_METADATA_KEY)
+            .ok_or_else(|| anyhow!("Blob upload did not report the blob name"))?;
+        hashes.push(hash.clone());
+    }
+
+    let mut metadata = HashMap::new();
+    metadata.insert(CHUNKS_METADATA_KEY.to_string(), hashes.join(","));
+
+    Ok(StorageResult {
+        success: true,
+        metadata,
+        error: None,
+    })
+}
+
+/// Download and reassemble the blobs of a content-addressed or chunked version,
+/// verifying the result against `FileVersion.hash`. Providers verify each blob
+/// against its name as they download it.
+/// Returns `None` for versions stored with the per-version layout.
+pub async fn download_blobs<P: StorageProvider + ?Sized>(provider: &P, version: &FileVersion) -> Result<Option<Vec<u8>>> {
+    if !is_stored_in_blobs(version) {
+        return Ok(None);
+    }
+
+    let hashes = version_blobs(version);
+    let mut data = Vec::with_capacity(version.size as usize);
+    for hash in hashes {
+        if !is_blob_name(hash) {
+            return Err(anyhow!("Invalid blob name in version {}: {}", version.version_id, hash));
+        }
+
+        let blob = provider.download_blob(hash).await
+            .with_context(|| format!("Failed to download blob {} of version {}", hash, version.version_id))?;
+        data.extend_from_slice(&blob);
+    }
+
+    if !version.hash.is_empty() && calculate_hash(&data) != version.hash {
+        return Err(anyhow!(
+            "Reassembled version {} does not match its hash {}",
+            version.version_id, version.hash
+        ));
+    }
+
+    Ok(Some(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::versioning::FileVersionManifest;
+
+    fn version(id: &str, blob: Option<&str>) -> FileVersion {
+        FileVersion {
+            version_id: id.to_string(),
+            timestamp: chrono::Utc::now(),
+            size: 0,
+            hash: String::new(),
+            content_hash: None,
+            storage_metadata: blob.map(|hash| (BLOB_METADATA_KEY.to_string(), hash.to_string())).into_iter().collect(),
+            description: None,
+            is_pinned: false,
+        }
+    }
+
+    fn manifest(game: &str, versions: Vec<FileVersion>) -> GameVersionManifest {
+        let file = FileVersionManifest {
+            file_path: format!("{}/save.dat", game),
+            versions,
+            current_version: None,
+            max_versions: None,
+        };
+        GameVersionManifest {
+            game_name: game.to_string(),
+            manifest_version: 1,
+            last_updated: chrono::Utc::now(),
+            files: HashMap::from([(file.file_path.clone(), file)]),
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_counts_references_across_games() {
+        let shared = "a".repeat(64);
+        let only_celeste = "b".repeat(64);
+        let mut chunked = version("v1", None);
+        chunked.storage_metadata.insert(CHUNKS_METADATA_KEY.to_string(), format!("{},{}", shared, "c".repeat(64)));
+        let manifests = [
+            manifest("Celeste", vec![
+                version("v1", Some(&shared)),
+                version("v2", Some(&only_celeste)),
+                version("v3", None),
+            ]),
+            manifest("Hades", vec![version("v1", Some(&shared)), version("v2", Some("../../escape"))]),
+            manifest("Celeste II", vec![chunked]),
+        ];
+
+        let counts = count_references(&manifests);
+        assert_eq!(counts.get(&shared), Some(&3));
+        assert_eq!(counts.get(&only_celeste), Some(&1));
+        assert_eq!(counts.get(&"c".repeat(64)), Some(&1));
+        assert_eq!(counts.len(), 3);
+    }
+}
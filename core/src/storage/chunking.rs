@@ -0,0 +1,154 @@
+use std::ops::Range;
+
+/// Content-defined chunker in the style of FastCDC.
+///
+/// Chunk boundaries are picked from a rolling gear hash of the data itself, so
+/// an edit only changes the chunks around it: inserting a few bytes into a
+/// large save shifts the following data without moving the later boundaries.
+#[derive(Debug, Clone, Copy)]
+pub struct Chunker {
+    min_size: usize,
+    avg_size: usize,
+    max_size: usize,
+    /// Stricter mask used before `avg_size`, making early cuts unlikely
+    mask_small: u64,
+    /// Looser mask used after `avg_size`, making late cuts likely
+    mask_large: u64,
+}
+
+impl Default for Chunker {
+    /// 256 KiB to 4 MiB chunks averaging 1 MiB
+    fn default() -> Self {
+        Self::new(256 * 1024, 1024 * 1024, 4 * 1024 * 1024)
+    }
+}
+
+impl Chunker {
+    /// `avg_size` is rounded down to a power of two
+    pub fn new(min_size: usize, avg_size: usize, max_size: usize) -> Self {
+        assert!(min_size > 0 && min_size <= avg_size && avg_size <= max_size, "invalid chunk sizes");
+        let bits = avg_size.ilog2();
+
+        Self {
+            min_size,
+            avg_size,
+            max_size,
+            // Normalized chunking, level 2
+            mask_small: high_bits_mask(bits + 2),
+            mask_large: high_bits_mask(bits.saturating_sub(2).max(1)),
+        }
+    }
+
+    /// Split data into consecutive chunks covering all of it
+    pub fn chunks(&self, data: &[u8]) -> Vec<Range<usize>> {
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        while start < data.len() {
+            let end = start + self.cut_point(&data[start..]);
+            chunks.push(start..end);
+            start = end;
+        }
+        chunks
+    }
+
+    /// Length of the chunk at the start of `data`
+    fn cut_point(&self, data: &[u8]) -> usize {
+        if data.len() <= self.min_size {
+            return data.len();
+        }
+
+        let end = data.len().min(self.max_size);
+        let normal = end.min(self.avg_size);
+        let mut hash = 0u64;
+
+        for (i, &byte) in data.iter().enumerate().take(end).skip(self.min_size) {
+            hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+            let mask = if i < normal { self.mask_small } else { self.mask_large };
+            if hash & mask == 0 {
+                return i + 1;
+            }
+        }
+        end
+    }
+}
+
+/// Mask of the `bits` most significant bits. The gear hash shifts left, so its
+/// high bits depend on the most bytes of the rolling window.
+fn high_bits_mask(bits: u32) -> u64 {
+    !0u64 << (64 - bits.min(63))
+}
+
+/// Random values for each byte, fixed so every device cuts the same chunks
+static GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    // splitmix64 from a fixed seed
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x6465_636b_7361_7665;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn pseudo_random(len: usize) -> Vec<u8> {
+        let mut state = 1u32;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunks_cover_data_within_bounds() {
+        let chunker = Chunker::new(2 * 1024, 8 * 1024, 32 * 1024);
+        let data = pseudo_random(1024 * 1024);
+
+        let chunks = chunker.chunks(&data);
+        assert_eq!(chunks.first().unwrap().start, 0);
+        assert_eq!(chunks.last().unwrap().end, data.len());
+        assert!(chunks.windows(2).all(|pair| pair[0].end == pair[1].start));
+        assert!(chunks[..chunks.len() - 1].iter().all(|c| c.len() >= 2 * 1024 && c.len() <= 32 * 1024));
+
+        // Roughly the average size, and cut the same way every time
+        assert!(chunks.len() > 1024 / 32 && chunks.len() < 1024 / 4, "{} chunks", chunks.len());
+        assert_eq!(chunker.chunks(&data), chunks);
+
+        assert!(chunker.chunks(&[]).is_empty());
+        assert_eq!(chunker.chunks(b"tiny"), vec![0..4]);
+    }
+
+    #[test]
+    fn test_small_edit_only_changes_nearby_chunks() {
+        let chunker = Chunker::new(2 * 1024, 8 * 1024, 32 * 1024);
+        let original = pseudo_random(1024 * 1024);
+        let mut edited = original.clone();
+        edited.splice(500_000..500_000, b"a few new bytes".iter().copied());
+
+        let hashes = |data: &[u8]| -> HashSet<String> {
+            chunker.chunks(data).into_iter()
+                .map(|range| crate::versioning::calculate_hash(&data[range]))
+                .collect()
+        };
+        let before = hashes(&original);
+        let after = hashes(&edited);
+
+        let new_chunks = after.difference(&before).count();
+        assert!(new_chunks >= 1 && new_chunks <= 3, "{} new chunks", new_chunks);
+    }
+}
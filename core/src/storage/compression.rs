@@ -0,0 +1,153 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use crate::versioning::FileVersion;
+
+/// `FileVersion.storage_metadata` key recording how a payload was compressed
+pub const COMPRESSION_METADATA_KEY: &str = "compression";
+
+/// Compression codec applied to file payloads before they reach storage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionCodec {
+    None,
+    Gzip,
+    #[default]
+    Zstd,
+}
+
+impl CompressionCodec {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CompressionCodec::None => "none",
+            CompressionCodec::Gzip => "gzip",
+            CompressionCodec::Zstd => "zstd",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "none" | "" => Ok(CompressionCodec::None),
+            "gzip" => Ok(CompressionCodec::Gzip),
+            "zstd" => Ok(CompressionCodec::Zstd),
+            other => Err(anyhow::anyhow!("Unknown compression codec: {}", other)),
+        }
+    }
+
+    /// Codec recorded for a stored version. Versions written before compression
+    /// existed carry no entry and are treated as uncompressed.
+    pub fn for_version(version: &FileVersion) -> Result<Self> {
+        match version.storage_metadata.get(COMPRESSION_METADATA_KEY) {
+            Some(name) => Self::from_name(name),
+            None => Ok(CompressionCodec::None),
+        }
+    }
+
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            CompressionCodec::None => Ok(data.to_vec()),
+            CompressionCodec::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish().context("gzip compression failed")
+            }
+            CompressionCodec::Zstd => zstd::encode_all(data, 0).context("zstd compression failed"),
+        }
+    }
+
+    pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            CompressionCodec::None => Ok(data.to_vec()),
+            CompressionCodec::Gzip => {
+                let mut decoded = Vec::new();
+                flate2::read::GzDecoder::new(data)
+                    .read_to_end(&mut decoded)
+                    .context("gzip decompression failed")?;
+                Ok(decoded)
+            }
+            CompressionCodec::Zstd => zstd::decode_all(data).context("zstd decompression failed"),
+        }
+    }
+}
+
+/// Compress an upload payload.
+///
+/// Returns the bytes to store and the metadata the caller should record on the
+/// version. Payloads that do not shrink are stored as-is with no codec entry.
+pub fn compress_payload(
+    enabled: bool,
+    codec: CompressionCodec,
+    data: &[u8],
+) -> Result<(Vec<u8>, HashMap<String, String>)> {
+    let mut metadata = HashMap::new();
+
+    if !enabled || codec == CompressionCodec::None {
+        return Ok((data.to_vec(), metadata));
+    }
+
+    let compressed = codec.compress(data)?;
+    if compressed.len() >= data.len() {
+        return Ok((data.to_vec(), metadata));
+    }
+
+    metadata.insert(COMPRESSION_METADATA_KEY.to_string(), codec.as_str().to_string());
+    Ok((compressed, metadata))
+}
+
+/// Undo `compress_payload` for a downloaded version
+pub fn decompress_payload(version: &FileVersion, data: Vec<u8>) -> Result<Vec<u8>> {
+    match CompressionCodec::for_version(version)? {
+        CompressionCodec::None => Ok(data),
+        codec => codec.decompress(&data),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version_with(metadata: HashMap<String, String>) -> FileVersion {
+        FileVersion {
+            version_id: "v1".to_string(),
+            timestamp: chrono::Utc::now(),
+            size: 0,
+            hash: String::new(),
+            content_hash: None,
+            storage_metadata: metadata,
+            description: None,
+            is_pinned: false,
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_all_codecs() {
+        let data = br#"{"slot":1,"gold":100,"inventory":["sword","sword","sword"]}"#.repeat(50);
+        for codec in [CompressionCodec::Gzip, CompressionCodec::Zstd] {
+            let (encoded, metadata) = compress_payload(true, codec, &data).unwrap();
+            assert!(encoded.len() < data.len());
+            assert_eq!(metadata.get(COMPRESSION_METADATA_KEY).map(String::as_str), Some(codec.as_str()));
+
+            let decoded = decompress_payload(&version_with(metadata), encoded).unwrap();
+            assert_eq!(decoded, data);
+        }
+    }
+
+    #[test]
+    fn test_disabled_or_incompressible_payload_is_stored_raw() {
+        let (encoded, metadata) = compress_payload(false, CompressionCodec::Zstd, b"plain text plain text plain text").unwrap();
+        assert_eq!(encoded, b"plain text plain text plain text");
+        assert!(metadata.is_empty());
+
+        let (encoded, metadata) = compress_payload(true, CompressionCodec::Zstd, b"x").unwrap();
+        assert_eq!(encoded, b"x");
+        assert!(metadata.is_empty());
+    }
+
+    #[test]
+    fn test_legacy_version_without_codec_restores() {
+        let decoded = decompress_payload(&version_with(HashMap::new()), b"old save".to_vec()).unwrap();
+        assert_eq!(decoded, b"old save");
+    }
+}
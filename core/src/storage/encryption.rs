@@ -0,0 +1,162 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit},
+    Aes256Gcm, Key, Nonce,
+};
+use anyhow::{anyhow, Context, Result};
+use argon2::Argon2;
+use base64::{engine::general_purpose, Engine as _};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Magic prefix of an encrypted envelope: `DSE1 | salt (16) | nonce (12) | ciphertext`
+pub const ENVELOPE_MAGIC: &[u8; 4] = b"DSE1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = ENVELOPE_MAGIC.len() + SALT_LEN + NONCE_LEN;
+
+/// Salt of the key blobs are named with. Every device must arrive at the same
+/// blob names, so unlike the envelope keys this key has one fixed salt.
+const BLOB_NAME_SALT: &[u8; SALT_LEN] = b"decksaves-blobs1";
+
+/// `FileVersion.storage_metadata` keys describing how a payload was encrypted
+pub const ENCRYPTION_METADATA_KEY: &str = "encryption";
+pub const ENCRYPTION_KDF_METADATA_KEY: &str = "encryption_kdf";
+pub const ENCRYPTION_SALT_METADATA_KEY: &str = "encryption_salt";
+pub const ENCRYPTION_NONCE_METADATA_KEY: &str = "encryption_nonce";
+
+pub const CIPHER_NAME: &str = "aes-256-gcm";
+/// Argon2id with the crate defaults (19 MiB, 2 passes, 1 lane), fixed for envelope version 1
+pub const KDF_NAME: &str = "argon2id-m19456-t2-p1";
+
+/// Encrypts save payloads and manifests with a key derived from a user passphrase.
+///
+/// Every device configured with the same passphrase can decrypt, because the
+/// salt travels with each envelope. Derived keys are cached per salt so the
+/// Argon2 cost is paid once per salt rather than once per file.
+pub struct PayloadCipher {
+    passphrase: String,
+    upload_salt: [u8; SALT_LEN],
+    keys: Mutex<HashMap<[u8; SALT_LEN], Key<Aes256Gcm>>>,
+}
+
+impl PayloadCipher {
+    pub fn new(passphrase: &str) -> Result<Self> {
+        if passphrase.is_empty() {
+            return Err(anyhow!("Encryption passphrase must not be empty"));
+        }
+
+        let mut upload_salt = [0u8; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut upload_salt);
+
+        Ok(Self {
+            passphrase: passphrase.to_string(),
+            upload_salt,
+            keys: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn key_for_salt(&self, salt: &[u8; SALT_LEN]) -> Result<Key<Aes256Gcm>> {
+        let mut keys = self.keys.lock().map_err(|_| anyhow!("Key cache poisoned"))?;
+        if let Some(key) = keys.get(salt) {
+            return Ok(*key);
+        }
+
+        let mut key_bytes = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(self.passphrase.as_bytes(), salt, &mut key_bytes)
+            .map_err(|e| anyhow!("Key derivation failed: {}", e))?;
+
+        let key = *Key::<Aes256Gcm>::from_slice(&key_bytes);
+        keys.insert(*salt, key);
+        Ok(key)
+    }
+
+    /// Encrypt into a self-describing envelope.
+    /// Returns the envelope and the parameters to record on the version.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<(Vec<u8>, HashMap<String, String>)> {
+        let key = self.key_for_salt(&self.upload_salt)?;
+        let cipher = Aes256Gcm::new(&key);
+        let nonce = Aes256Gcm::generate_nonce(&mut rand::thread_rng());
+
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|e| anyhow!("Encryption failed: {}", e))?;
+
+        let mut envelope = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+        envelope.extend_from_slice(ENVELOPE_MAGIC);
+        envelope.extend_from_slice(&self.upload_salt);
+        envelope.extend_from_slice(&nonce);
+        envelope.extend_from_slice(&ciphertext);
+
+        let mut metadata = HashMap::new();
+        metadata.insert(ENCRYPTION_METADATA_KEY.to_string(), CIPHER_NAME.to_string());
+        metadata.insert(ENCRYPTION_KDF_METADATA_KEY.to_string(), KDF_NAME.to_string());
+        metadata.insert(ENCRYPTION_SALT_METADATA_KEY.to_string(), general_purpose::STANDARD.encode(self.upload_salt));
+        metadata.insert(ENCRYPTION_NONCE_METADATA_KEY.to_string(), general_purpose::STANDARD.encode(nonce));
+
+        Ok((envelope, metadata))
+    }
+
+    /// Name of the blob holding `data`: a hex HMAC-SHA256 keyed from the
+    /// passphrase. Unlike a plain SHA-256, the name neither lets anyone
+    /// without the passphrase confirm what a blob holds nor matches the names
+    /// used with other passphrases.
+    pub fn blob_name(&self, data: &[u8]) -> Result<String> {
+        let key = self.key_for_salt(BLOB_NAME_SALT)?;
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&key)
+            .map_err(|e| anyhow!("Invalid blob name key: {}", e))?;
+        mac.update(data);
+        Ok(format!("{:x}", mac.finalize().into_bytes()))
+    }
+
+    /// Decrypt an envelope produced by `encrypt`
+    pub fn decrypt(&self, envelope: &[u8]) -> Result<Vec<u8>> {
+        if !is_envelope(envelope) {
+            return Err(anyhow!("Data is not an encrypted DeckSaves envelope"));
+        }
+
+        let (header, ciphertext) = envelope.split_at(HEADER_LEN);
+        let salt: [u8; SALT_LEN] = header[ENVELOPE_MAGIC.len()..ENVELOPE_MAGIC.len() + SALT_LEN]
+            .try_into()
+            .context("Invalid envelope salt")?;
+        let nonce = Nonce::from_slice(&header[ENVELOPE_MAGIC.len() + SALT_LEN..]);
+
+        let key = self.key_for_salt(&salt)?;
+        Aes256Gcm::new(&key)
+            .decrypt(nonce, ciphertext)
+            .map_err(|_| anyhow!("Decryption failed: wrong passphrase or corrupted data"))
+    }
+}
+
+/// Whether data starts with the encrypted envelope header
+pub fn is_envelope(data: &[u8]) -> bool {
+    data.len() >= HEADER_LEN && data.starts_with(ENVELOPE_MAGIC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_other_device_with_same_passphrase_decrypts() {
+        let device_a = PayloadCipher::new("correct horse battery staple").unwrap();
+        let device_b = PayloadCipher::new("correct horse battery staple").unwrap();
+
+        let (envelope, metadata) = device_a.encrypt(b"save data").unwrap();
+        assert!(is_envelope(&envelope));
+        assert_eq!(metadata.get(ENCRYPTION_METADATA_KEY).map(String::as_str), Some(CIPHER_NAME));
+        assert_eq!(device_b.decrypt(&envelope).unwrap(), b"save data");
+    }
+
+    #[test]
+    fn test_wrong_passphrase_fails() {
+        let cipher = PayloadCipher::new("right").unwrap();
+        let (envelope, _) = cipher.encrypt(b"save data").unwrap();
+
+        let wrong = PayloadCipher::new("wrong").unwrap();
+        assert!(wrong.decrypt(&envelope).is_err());
+    }
+}
@@ -1,3 +1,15 @@
+pub mod blobs;
+pub mod chunking;
+pub mod compression;
+pub mod encryption;
+pub mod payload;
 pub mod providers;
+pub mod retry;
+pub mod webdav;
 
+pub use blobs::StorageLayout;
+pub use compression::CompressionCodec;
+pub use payload::PayloadCodec;
 pub use providers::*;
+pub use retry::{retry_transient, RetryPolicy, RetryingStorageProvider};
+pub use webdav::WebDavStorageProvider;
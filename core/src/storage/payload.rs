@@ -0,0 +1,305 @@
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use std::collections::HashMap;
+
+use super::compression::{compress_payload, decompress_payload, CompressionCodec, COMPRESSION_METADATA_KEY};
+use super::encryption::{is_envelope, PayloadCipher, ENCRYPTION_METADATA_KEY};
+use super::providers::StorageConfig;
+use crate::versioning::{calculate_hash, FileVersion, GameVersionManifest};
+
+/// Magic prefix of a blob before encryption: `DSB1 | codec name length | codec name | data`
+const BLOB_MAGIC: &[u8; 4] = b"DSB1";
+
+/// Transforms applied to data on its way to and from a storage backend:
+/// compression first, then client-side encryption.
+pub struct PayloadCodec {
+    enable_compression: bool,
+    compression_codec: CompressionCodec,
+    cipher: Option<PayloadCipher>,
+}
+
+impl PayloadCodec {
+    pub fn from_config(config: &StorageConfig) -> Result<Self> {
+        let cipher = if config.encryption_enabled {
+            let passphrase = config.encryption_passphrase.as_deref()
+                .filter(|p| !p.is_empty())
+                .context("Encryption is enabled but no encryption passphrase is configured")?;
+            Some(PayloadCipher::new(passphrase)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            enable_compression: config.enable_compression,
+            compression_codec: config.compression_codec,
+            cipher,
+        })
+    }
+
+    pub fn is_encrypting(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Encode a file payload for upload.
+    /// Returns the bytes to store and the metadata to record on the version.
+    pub fn encode(&self, data: &[u8]) -> Result<(Vec<u8>, HashMap<String, String>)> {
+        let (mut payload, mut metadata) = compress_payload(self.enable_compression, self.compression_codec, data)?;
+
+        if let Some(cipher) = &self.cipher {
+            let (envelope, encryption_metadata) = cipher.encrypt(&payload)?;
+            payload = envelope;
+            metadata.extend(encryption_metadata);
+        }
+
+        Ok((payload, metadata))
+    }
+
+    /// Decode a downloaded payload using the parameters recorded on its version.
+    /// Versions stored before compression or encryption existed decode unchanged.
+    pub fn decode(&self, version: &FileVersion, data: Vec<u8>) -> Result<Vec<u8>> {
+        let data = if version.storage_metadata.contains_key(ENCRYPTION_METADATA_KEY) {
+            let cipher = self.cipher.as_ref()
+                .context("Version is encrypted but no encryption passphrase is configured")?;
+            cipher.decrypt(&data)?
+        } else {
+            data
+        };
+
+        decompress_payload(version, data)
+    }
+
+    /// Encode a blob for the content-addressed layout. Blobs are shared by
+    /// versions uploaded by different devices, so unlike `encode` the codec is
+    /// recorded in the blob itself rather than on any one version.
+    pub fn encode_blob(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let (compressed, metadata) = compress_payload(self.enable_compression, self.compression_codec, data)?;
+        let codec = metadata.get(COMPRESSION_METADATA_KEY).map(String::as_str).unwrap_or("none");
+
+        let mut blob = Vec::with_capacity(BLOB_MAGIC.len() + 1 + codec.len() + compressed.len());
+        blob.extend_from_slice(BLOB_MAGIC);
+        blob.push(codec.len() as u8);
+        blob.extend_from_slice(codec.as_bytes());
+        blob.extend_from_slice(&compressed);
+
+        match &self.cipher {
+            Some(cipher) => Ok(cipher.encrypt(&blob)?.0),
+            None => Ok(blob),
+        }
+    }
+
+    /// Name of the blob storing `data`: its SHA-256, or with encryption a hash
+    /// keyed from the passphrase, so that blob names do not reveal content
+    pub fn blob_name(&self, data: &[u8]) -> Result<String> {
+        match &self.cipher {
+            Some(cipher) => cipher.blob_name(data),
+            None => Ok(calculate_hash(data)),
+        }
+    }
+
+    /// Decode a downloaded blob and check that it holds the content its name
+    /// stands for. Blobs named by their plain SHA-256 before encryption was
+    /// enabled still check out.
+    pub fn open_blob(&self, name: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let content = self.decode_blob(data)?;
+        if calculate_hash(&content) != name && self.blob_name(&content)? != name {
+            return Err(anyhow!("Blob {} is corrupt", name));
+        }
+        Ok(content)
+    }
+
+    /// Decode a blob written by `encode_blob` on any device
+    pub fn decode_blob(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let blob = if is_envelope(data) {
+            let cipher = self.cipher.as_ref()
+                .context("Blob is encrypted but no encryption passphrase is configured")?;
+            cipher.decrypt(data)?
+        } else {
+            data.to_vec()
+        };
+
+        let rest = blob.strip_prefix(BLOB_MAGIC.as_slice())
+            .ok_or_else(|| anyhow!("Data is not a DeckSaves blob"))?;
+        let (&name_len, rest) = rest.split_first()
+            .ok_or_else(|| anyhow!("Truncated blob header"))?;
+        if rest.len() < name_len as usize {
+            return Err(anyhow!("Truncated blob header"));
+        }
+        let (name, compressed) = rest.split_at(name_len as usize);
+
+        CompressionCodec::from_name(std::str::from_utf8(name).context("Invalid blob codec name")?)?
+            .decompress(compressed)
+    }
+
+    /// Encrypt a short value, such as a content hash, for storage in object
+    /// metadata that anyone with access to the bucket could otherwise read
+    pub fn seal_value(&self, value: &str) -> Result<String> {
+        let cipher = self.cipher.as_ref()
+            .context("Sealing a value requires an encryption passphrase")?;
+        Ok(general_purpose::STANDARD.encode(cipher.encrypt(value.as_bytes())?.0))
+    }
+
+    /// Decrypt a value sealed by `seal_value` on any device
+    pub fn open_value(&self, sealed: &str) -> Result<String> {
+        let cipher = self.cipher.as_ref()
+            .context("Value is encrypted but no encryption passphrase is configured")?;
+        let envelope = general_purpose::STANDARD.decode(sealed).context("Invalid sealed value")?;
+        String::from_utf8(cipher.decrypt(&envelope)?).context("Invalid sealed value")
+    }
+
+    /// Encode a serialized manifest. Encrypted manifests are self-describing
+    /// envelopes since there is no version record to carry their parameters.
+    pub fn encode_manifest(&self, manifest: &GameVersionManifest) -> Result<Vec<u8>> {
+        let json = serde_json::to_vec_pretty(manifest)?;
+        match &self.cipher {
+            Some(cipher) => Ok(cipher.encrypt(&json)?.0),
+            None => Ok(json),
+        }
+    }
+
+    /// Decode a manifest, accepting both encrypted envelopes and plain JSON
+    pub fn decode_manifest(&self, data: &[u8]) -> Result<GameVersionManifest> {
+        let json = if is_envelope(data) {
+            let cipher = self.cipher.as_ref()
+                .ok_or_else(|| anyhow!("Manifest is encrypted but no encryption passphrase is configured"))?;
+            cipher.decrypt(data)?
+        } else {
+            data.to_vec()
+        };
+
+        serde_json::from_slice(&json).context("Failed to parse game version manifest")
+    }
+
+    /// Content type for stored manifests
+    pub fn manifest_content_type(&self) -> &'static str {
+        if self.is_encrypting() {
+            "application/octet-stream"
+        } else {
+            "application/json"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encrypted_config(passphrase: &str) -> StorageConfig {
+        StorageConfig {
+            encryption_enabled: true,
+            encryption_passphrase: Some(passphrase.to_string()),
+            ..StorageConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_encrypted_payload_roundtrip_records_parameters() {
+        let codec = PayloadCodec::from_config(&encrypted_config("hunter2")).unwrap();
+        let data = b"<profile><gold>100</gold></profile>".repeat(10);
+
+        let (stored, metadata) = codec.encode(&data).unwrap();
+        assert!(is_envelope(&stored));
+        assert!(metadata.contains_key("compression"));
+        assert!(metadata.contains_key("encryption_salt"));
+
+        let version = FileVersion {
+            version_id: "v1".to_string(),
+            timestamp: chrono::Utc::now(),
+            size: data.len() as u64,
+            hash: String::new(),
+            content_hash: None,
+            storage_metadata: metadata,
+            description: None,
+            is_pinned: false,
+        };
+        let other_device = PayloadCodec::from_config(&encrypted_config("hunter2")).unwrap();
+        assert_eq!(other_device.decode(&version, stored).unwrap(), data);
+    }
+
+    #[test]
+    fn test_manifest_encryption_and_plaintext_fallback() {
+        let manifest = GameVersionManifest {
+            game_name: "Celeste".to_string(),
+            manifest_version: 1,
+            last_updated: chrono::Utc::now(),
+            files: HashMap::new(),
+            metadata: HashMap::new(),
+        };
+
+        let codec = PayloadCodec::from_config(&encrypted_config("hunter2")).unwrap();
+        let stored = codec.encode_manifest(&manifest).unwrap();
+        assert!(!String::from_utf8_lossy(&stored).contains("Celeste"));
+        assert_eq!(codec.decode_manifest(&stored).unwrap().game_name, "Celeste");
+
+        // Manifests written before encryption was enabled still load
+        let plain = serde_json::to_vec(&manifest).unwrap();
+        assert_eq!(codec.decode_manifest(&plain).unwrap().game_name, "Celeste");
+
+        // Without the passphrase an encrypted manifest is rejected instead of misparsed
+        let no_key = PayloadCodec::from_config(&StorageConfig::default()).unwrap();
+        assert!(no_key.decode_manifest(&stored).is_err());
+    }
+
+    #[test]
+    fn test_blobs_decode_without_version_metadata() {
+        let data = b"<profile><gold>100</gold></profile>".repeat(10);
+
+        let plain = PayloadCodec::from_config(&StorageConfig::default()).unwrap();
+        let blob = plain.encode_blob(&data).unwrap();
+        assert!(blob.len() < data.len());
+        assert_eq!(plain.decode_blob(&blob).unwrap(), data);
+
+        // Incompressible data is stored as-is behind the header
+        let tiny = plain.encode_blob(b"x").unwrap();
+        assert_eq!(plain.decode_blob(&tiny).unwrap(), b"x");
+
+        let encrypted = PayloadCodec::from_config(&encrypted_config("hunter2")).unwrap();
+        let sealed = encrypted.encode_blob(&data).unwrap();
+        assert!(is_envelope(&sealed));
+        assert_eq!(encrypted.decode_blob(&sealed).unwrap(), data);
+        assert!(plain.decode_blob(&sealed).is_err());
+        assert!(plain.decode_blob(b"not a blob").is_err());
+    }
+
+    #[test]
+    fn test_encrypted_blob_names_are_keyed() {
+        let data = b"<profile><gold>100</gold></profile>";
+
+        let plain = PayloadCodec::from_config(&StorageConfig::default()).unwrap();
+        assert_eq!(plain.blob_name(data).unwrap(), calculate_hash(data));
+
+        let codec = PayloadCodec::from_config(&encrypted_config("hunter2")).unwrap();
+        let name = codec.blob_name(data).unwrap();
+        assert_ne!(name, calculate_hash(data));
+        assert_eq!(name.len(), 64);
+        let other_device = PayloadCodec::from_config(&encrypted_config("hunter2")).unwrap();
+        assert_eq!(other_device.blob_name(data).unwrap(), name);
+        let other_passphrase = PayloadCodec::from_config(&encrypted_config("correct horse")).unwrap();
+        assert_ne!(other_passphrase.blob_name(data).unwrap(), name);
+
+        let blob = codec.encode_blob(data).unwrap();
+        assert_eq!(other_device.open_blob(&name, &blob).unwrap(), data);
+        assert_eq!(other_device.open_blob(&calculate_hash(data), &blob).unwrap(), data);
+        assert!(other_device.open_blob(&"0".repeat(64), &blob).is_err());
+    }
+
+    #[test]
+    fn test_sealed_values_need_the_passphrase() {
+        let codec = PayloadCodec::from_config(&encrypted_config("hunter2")).unwrap();
+        let sealed = codec.seal_value("abc123").unwrap();
+        assert!(!sealed.contains("abc123"));
+        assert_ne!(codec.seal_value("abc123").unwrap(), sealed);
+
+        let other_device = PayloadCodec::from_config(&encrypted_config("hunter2")).unwrap();
+        assert_eq!(other_device.open_value(&sealed).unwrap(), "abc123");
+
+        let plain = PayloadCodec::from_config(&StorageConfig::default()).unwrap();
+        assert!(plain.seal_value("abc123").is_err());
+        assert!(plain.open_value(&sealed).is_err());
+    }
+
+    #[test]
+    fn test_encryption_requires_passphrase() {
+        let config = StorageConfig { encryption_enabled: true, ..StorageConfig::default() };
+        assert!(PayloadCodec::from_config(&config).is_err());
+    }
+}
@@ -4,13 +4,35 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 use crate::versioning::{FileVersion, GameVersionManifest};
+use super::blobs::{download_blobs, is_blob_name, needs_refresh, upload_chunked, is_stored_in_blobs, StorageLayout, BLOB_DIR, BLOB_METADATA_KEY};
+use super::compression::CompressionCodec;
+use super::payload::PayloadCodec;
+use super::retry::{RetryPolicy, RetryingStorageProvider};
+use super::webdav::WebDavStorageProvider;
 
 /// Storage backend identifier
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum StorageBackend {
-    S3 { bucket: String, region: String },
+    S3 {
+        bucket: String,
+        region: String,
+        /// Custom endpoint for S3-compatible services (MinIO, Garage, Backblaze B2, ...)
+        #[serde(default)]
+        endpoint_url: Option<String>,
+        /// Use path-style addressing (`host/bucket/key`) instead of virtual-hosted style
+        #[serde(default)]
+        force_path_style: bool,
+        /// Optional prefix prepended to every object key
+        #[serde(default)]
+        key_prefix: Option<String>,
+    },
     GoogleDrive { folder_id: String },
-    WebDAV { base_url: String, username: String },
+    WebDAV {
+        base_url: String,
+        username: String,
+        #[serde(default, skip_serializing)]
+        password: Option<String>,
+    },
     Local { base_path: String },
 }
 
@@ -55,7 +77,8 @@ pub trait StorageProvider: Send + Sync {
     /// Download game version manifest
     async fn download_manifest(&self, game_name: &str) -> Result<Option<GameVersionManifest>>;
 
-    /// Delete a specific file version
+    /// Delete a specific file version. Blobs of content-addressed versions
+    /// are left in place; `delete_blob` removes them once unreferenced.
     async fn delete_version(
         &self,
         game_name: &str,
@@ -63,6 +86,24 @@ pub trait StorageProvider: Send + Sync {
         version: &FileVersion,
     ) -> Result<StorageResult>;
 
+    /// Store data under its content hash, skipping the upload when the blob
+    /// exists. The blob name is reported under `blobs::BLOB_METADATA_KEY`.
+    /// Reusing a blob refreshes its modification time when `blobs::needs_refresh`
+    /// says so, which keeps garbage collection from deleting it.
+    async fn upload_blob(&self, data: &[u8]) -> Result<StorageResult>;
+
+    /// When a blob was last uploaded or refreshed, or `None` if it does not exist
+    async fn blob_last_modified(&self, hash: &str) -> Result<Option<chrono::DateTime<chrono::Utc>>>;
+
+    /// Download and decode a blob
+    async fn download_blob(&self, hash: &str) -> Result<Vec<u8>>;
+
+    /// List the names (content hashes) of all stored blobs
+    async fn list_blobs(&self) -> Result<Vec<String>>;
+
+    /// Delete a blob. Callers must make sure no version references it.
+    async fn delete_blob(&self, hash: &str) -> Result<StorageResult>;
+
     /// List all games in storage
     async fn list_games(&self) -> Result<Vec<String>>;
 
@@ -77,10 +118,24 @@ pub trait StorageProvider: Send + Sync {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StorageConfig {
     pub backend: StorageBackend,
+    /// How long to wait for a connection, and for a stalled transfer to make
+    /// progress again. Transfers that keep moving may take as long as they need.
     pub connection_timeout_seconds: u64,
     pub retry_attempts: u32,
     pub enable_compression: bool,
+    /// Codec used when `enable_compression` is set
+    #[serde(default)]
+    pub compression_codec: CompressionCodec,
+    /// Encrypt payloads and manifests client-side before upload.
+    /// Requires `encryption_passphrase`.
     pub encryption_enabled: bool,
+    /// Passphrase the encryption key is derived from. Never written to disk
+    /// as part of the storage config; callers supply it at runtime.
+    #[serde(default, skip_serializing)]
+    pub encryption_passphrase: Option<String>,
+    /// Where new versions are stored
+    #[serde(default)]
+    pub layout: StorageLayout,
 }
 
 impl Default for StorageConfig {
@@ -92,70 +147,160 @@ impl Default for StorageConfig {
             connection_timeout_seconds: 30,
             retry_attempts: 3,
             enable_compression: true,
-            encryption_enabled: true,
+            compression_codec: CompressionCodec::Zstd,
+            encryption_enabled: false,
+            encryption_passphrase: None,
+            layout: StorageLayout::PerVersion,
         }
     }
 }
 
+impl StorageConfig {
+    /// Time without progress after which a connection or transfer is abandoned
+    pub fn idle_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.connection_timeout_seconds.max(1))
+    }
+}
+
 /// Factory for creating storage providers
 pub struct StorageFactory;
 
 impl StorageFactory {
-    /// Create a storage provider based on config
+    /// Create a storage provider based on config, wrapped with the configured
+    /// timeout and retry policy
     pub async fn create_provider(config: &StorageConfig) -> Result<Box<dyn StorageProvider>> {
+        let provider = Self::create_backend(config).await?;
+        Ok(Box::new(RetryingStorageProvider::new(provider, RetryPolicy::from_config(config))))
+    }
+
+    /// Create the bare provider for a backend without retries
+    pub async fn create_backend(config: &StorageConfig) -> Result<Box<dyn StorageProvider>> {
         match &config.backend {
-            StorageBackend::S3 { bucket, region } => {
-                let provider = S3StorageProvider::new(bucket.clone(), region.clone(), config.clone()).await?;
+            StorageBackend::S3 { bucket, region, endpoint_url, force_path_style, key_prefix } => {
+                let provider = S3StorageProvider::new(
+                    bucket.clone(),
+                    region.clone(),
+                    S3Endpoint {
+                        endpoint_url: endpoint_url.clone(),
+                        force_path_style: *force_path_style,
+                        key_prefix: key_prefix.clone(),
+                    },
+                    config.clone(),
+                ).await?;
                 Ok(Box::new(provider))
             }
             StorageBackend::Local { base_path } => {
                 let provider = LocalStorageProvider::new(base_path.clone(), config.clone())?;
                 Ok(Box::new(provider))
             }
+            StorageBackend::WebDAV { base_url, username, password } => {
+                let provider = WebDavStorageProvider::new(
+                    base_url.clone(),
+                    username.clone(),
+                    password.clone(),
+                    config.clone(),
+                )?;
+                Ok(Box::new(provider))
+            }
             // Future implementations
             StorageBackend::GoogleDrive { .. } => {
                 Err(anyhow::anyhow!("Google Drive storage not implemented yet"))
             }
-            StorageBackend::WebDAV { .. } => {
-                Err(anyhow::anyhow!("WebDAV storage not implemented yet"))
-            }
         }
     }
 }
 
+/// Endpoint settings for S3 and S3-compatible services
+#[derive(Debug, Clone, Default)]
+pub struct S3Endpoint {
+    pub endpoint_url: Option<String>,
+    pub force_path_style: bool,
+    pub key_prefix: Option<String>,
+}
+
+impl S3Endpoint {
+    /// Key prefix normalized to either "" or "some/prefix/"
+    pub fn normalized_prefix(&self) -> String {
+        match self.key_prefix.as_deref().map(|p| p.trim_matches('/')) {
+            Some(prefix) if !prefix.is_empty() => format!("{}/", prefix),
+            _ => String::new(),
+        }
+    }
+
+    /// Build an S3 client from a loaded AWS config, applying the custom endpoint if any.
+    ///
+    /// Requests time out connecting or when a transfer stalls for `idle_timeout`,
+    /// never for taking long overall. The SDK's own retries are disabled since
+    /// callers retry with `RetryPolicy`.
+    pub fn build_client(&self, aws_config: &aws_config::SdkConfig, idle_timeout: std::time::Duration) -> aws_sdk_s3::Client {
+        let mut builder = aws_sdk_s3::config::Builder::from(aws_config)
+            .force_path_style(self.force_path_style)
+            .timeout_config(
+                aws_sdk_s3::config::timeout::TimeoutConfig::builder()
+                    .connect_timeout(idle_timeout)
+                    .build(),
+            )
+            .stalled_stream_protection(
+                aws_sdk_s3::config::StalledStreamProtectionConfig::enabled()
+                    .grace_period(idle_timeout)
+                    .build(),
+            )
+            .retry_config(aws_sdk_s3::config::retry::RetryConfig::disabled());
+
+        if let Some(endpoint_url) = self.endpoint_url.as_deref().filter(|url| !url.is_empty()) {
+            builder = builder.endpoint_url(endpoint_url);
+        }
+
+        aws_sdk_s3::Client::from_conf(builder.build())
+    }
+}
+
 /// S3 storage provider implementation
 pub struct S3StorageProvider {
     bucket: String,
     region: String,
+    endpoint: S3Endpoint,
+    key_prefix: String,
     client: aws_sdk_s3::Client,
-    config: StorageConfig,
+    payload: PayloadCodec,
+    layout: StorageLayout,
 }
 
 impl S3StorageProvider {
-    pub async fn new(bucket: String, region: String, config: StorageConfig) -> Result<Self> {
+    pub async fn new(bucket: String, region: String, endpoint: S3Endpoint, config: StorageConfig) -> Result<Self> {
         // Create AWS config
         let aws_config = aws_config::defaults(aws_config::BehaviorVersion::latest())
             .region(aws_config::Region::new(region.clone()))
             .load()
             .await;
 
-        let client = aws_sdk_s3::Client::new(&aws_config);
+        let client = endpoint.build_client(&aws_config, config.idle_timeout());
+        let key_prefix = endpoint.normalized_prefix();
+        let payload = PayloadCodec::from_config(&config)?;
 
         Ok(Self {
             bucket,
             region,
+            endpoint,
+            key_prefix,
             client,
-            config,
+            payload,
+            layout: config.layout,
         })
     }
 
     fn get_object_key(&self, game_name: &str, file_path: &str, version_id: &str) -> String {
-        format!("games/{}/files/{}/versions/{}", game_name, file_path, version_id)
+        format!("{}games/{}/files/{}/versions/{}", self.key_prefix, game_name, file_path, version_id)
     }
 
     fn get_manifest_key(&self, game_name: &str) -> String {
-        format!("games/{}/manifest.json", game_name)
+        format!("{}games/{}/manifest.json", self.key_prefix, game_name)
     }
+
+    fn get_blob_key(&self, hash: &str) -> String {
+        format!("{}{}/{}", self.key_prefix, BLOB_DIR, hash)
+    }
+
 }
 
 #[async_trait]
@@ -167,13 +312,20 @@ impl StorageProvider for S3StorageProvider {
         version: &FileVersion,
         data: &[u8],
     ) -> Result<StorageResult> {
+        match self.layout {
+            StorageLayout::ContentAddressed => return self.upload_blob(data).await,
+            StorageLayout::Chunked => return upload_chunked(self, data).await,
+            StorageLayout::PerVersion => {}
+        }
+
         let key = self.get_object_key(game_name, file_path, &version.version_id);
+        let (payload, payload_metadata) = self.payload.encode(data)?;
         
         let mut put_request = self.client
             .put_object()
             .bucket(&self.bucket)
             .key(&key)
-            .body(aws_sdk_s3::primitives::ByteStream::from(data.to_vec()));
+            .body(aws_sdk_s3::primitives::ByteStream::from(payload));
 
         // Add metadata
         put_request = put_request
@@ -182,11 +334,15 @@ impl StorageProvider for S3StorageProvider {
             .metadata("file-hash", &version.hash)
             .metadata("timestamp", version.timestamp.to_rfc3339());
 
+        for (name, value) in &payload_metadata {
+            put_request = put_request.metadata(name, value);
+        }
+
         let result = put_request.send().await;
 
         match result {
             Ok(_) => {
-                let mut metadata = HashMap::new();
+                let mut metadata = payload_metadata;
                 metadata.insert("s3_bucket".to_string(), self.bucket.clone());
                 metadata.insert("s3_key".to_string(), key);
                 metadata.insert("s3_region".to_string(), self.region.clone());
@@ -200,7 +356,7 @@ impl StorageProvider for S3StorageProvider {
             Err(e) => Ok(StorageResult {
                 success: false,
                 metadata: HashMap::new(),
-                error: Some(e.to_string()),
+                error: Some(aws_sdk_s3::error::DisplayErrorContext(&e).to_string()),
             }),
         }
     }
@@ -211,6 +367,10 @@ impl StorageProvider for S3StorageProvider {
         file_path: &str,
         version: &FileVersion,
     ) -> Result<Vec<u8>> {
+        if let Some(data) = download_blobs(self, version).await? {
+            return Ok(data);
+        }
+
         let key = self.get_object_key(game_name, file_path, &version.version_id);
         
         let result = self.client
@@ -221,7 +381,7 @@ impl StorageProvider for S3StorageProvider {
             .await?;
 
         let data = result.body.collect().await?.into_bytes().to_vec();
-        Ok(data)
+        self.payload.decode(version, data)
     }
 
     async fn upload_manifest(
@@ -230,14 +390,14 @@ impl StorageProvider for S3StorageProvider {
         manifest: &GameVersionManifest,
     ) -> Result<StorageResult> {
         let key = self.get_manifest_key(game_name);
-        let manifest_data = serde_json::to_vec_pretty(manifest)?;
+        let manifest_data = self.payload.encode_manifest(manifest)?;
 
         let result = self.client
             .put_object()
             .bucket(&self.bucket)
             .key(&key)
             .body(aws_sdk_s3::primitives::ByteStream::from(manifest_data))
-            .content_type("application/json")
+            .content_type(self.payload.manifest_content_type())
             .metadata("manifest-version", &manifest.manifest_version.to_string())
             .send()
             .await;
@@ -257,7 +417,7 @@ impl StorageProvider for S3StorageProvider {
             Err(e) => Ok(StorageResult {
                 success: false,
                 metadata: HashMap::new(),
-                error: Some(e.to_string()),
+                error: Some(aws_sdk_s3::error::DisplayErrorContext(&e).to_string()),
             }),
         }
     }
@@ -274,13 +434,17 @@ impl StorageProvider for S3StorageProvider {
         {
             Ok(result) => {
                 let data = result.body.collect().await?.into_bytes();
-                let manifest: GameVersionManifest = serde_json::from_slice(&data)?;
-                Ok(Some(manifest))
+                Ok(Some(self.payload.decode_manifest(&data)?))
             }
-            Err(_) => {
+            Err(e) if e.as_service_error().is_some_and(|se| se.is_no_such_key()) => {
                 // Manifest doesn't exist yet
                 Ok(None)
             }
+            Err(e) => Err(anyhow::anyhow!(
+                "Failed to download manifest for {}: {}",
+                game_name,
+                aws_sdk_s3::error::DisplayErrorContext(&e)
+            )),
         }
     }
 
@@ -290,6 +454,14 @@ impl StorageProvider for S3StorageProvider {
         file_path: &str,
         version: &FileVersion,
     ) -> Result<StorageResult> {
+        if is_stored_in_blobs(version) {
+            return Ok(StorageResult {
+                success: true,
+                metadata: HashMap::new(),
+                error: None,
+            });
+        }
+
         let key = self.get_object_key(game_name, file_path, &version.version_id);
         
         match self.client
@@ -307,34 +479,175 @@ impl StorageProvider for S3StorageProvider {
             Err(e) => Ok(StorageResult {
                 success: false,
                 metadata: HashMap::new(),
-                error: Some(e.to_string()),
+                error: Some(aws_sdk_s3::error::DisplayErrorContext(&e).to_string()),
             }),
         }
     }
 
     async fn list_games(&self) -> Result<Vec<String>> {
         let mut games = Vec::new();
-        let prefix = "games/";
+        let prefix = format!("{}games/", self.key_prefix);
+        let mut continuation_token = None;
+
+        loop {
+            let result = self.client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(&prefix)
+                .delimiter("/")
+                .set_continuation_token(continuation_token)
+                .send()
+                .await?;
+
+            for prefix_obj in result.common_prefixes() {
+                if let Some(prefix_str) = prefix_obj.prefix() {
+                    if let Some(game_name) = prefix_str.strip_prefix(prefix.as_str()).and_then(|s| s.strip_suffix("/")) {
+                        games.push(game_name.to_string());
+                    }
+                }
+            }
 
+            match result.next_continuation_token() {
+                Some(token) => continuation_token = Some(token.to_string()),
+                None => break,
+            }
+        }
+
+        Ok(games)
+    }
+
+    async fn upload_blob(&self, data: &[u8]) -> Result<StorageResult> {
+        let hash = self.payload.blob_name(data)?;
+        let key = self.get_blob_key(&hash);
+
+        let result = match self.blob_last_modified(&hash).await? {
+            None => self.client
+                .put_object()
+                .bucket(&self.bucket)
+                .key(&key)
+                .body(aws_sdk_s3::primitives::ByteStream::from(self.payload.encode_blob(data)?))
+                .send()
+                .await
+                .map(|_| ())
+                .map_err(|e| aws_sdk_s3::error::DisplayErrorContext(&e).to_string()),
+            // Copying the object onto itself is the only way to bump LastModified
+            Some(last_modified) if needs_refresh(last_modified) => self.client
+                .copy_object()
+                .bucket(&self.bucket)
+                .key(&key)
+                .copy_source(format!("{}/{}", self.bucket, key))
+                .metadata_directive(aws_sdk_s3::types::MetadataDirective::Replace)
+                .send()
+                .await
+                .map(|_| ())
+                .map_err(|e| aws_sdk_s3::error::DisplayErrorContext(&e).to_string()),
+            Some(_) => Ok(()),
+        };
+
+        if let Err(e) = result {
+            return Ok(StorageResult {
+                success: false,
+                metadata: HashMap::new(),
+                error: Some(e),
+            });
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert(BLOB_METADATA_KEY.to_string(), hash);
+        metadata.insert("s3_bucket".to_string(), self.bucket.clone());
+        metadata.insert("s3_key".to_string(), key);
+        metadata.insert("s3_region".to_string(), self.region.clone());
+
+        Ok(StorageResult {
+            success: true,
+            metadata,
+            error: None,
+        })
+    }
+
+    async fn blob_last_modified(&self, hash: &str) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        let key = self.get_blob_key(hash);
+        match self.client.head_object().bucket(&self.bucket).key(&key).send().await {
+            Ok(output) => Ok(Some(
+                output.last_modified()
+                    .and_then(|dt| chrono::DateTime::from_timestamp(dt.secs(), dt.subsec_nanos()))
+                    .unwrap_or_else(chrono::Utc::now),
+            )),
+            Err(e) if e.as_service_error().is_some_and(|se| se.is_not_found()) => Ok(None),
+            Err(e) => Err(anyhow::anyhow!(
+                "Failed to check for blob {}: {}",
+                key,
+                aws_sdk_s3::error::DisplayErrorContext(&e)
+            )),
+        }
+    }
+
+    async fn download_blob(&self, hash: &str) -> Result<Vec<u8>> {
         let result = self.client
-            .list_objects_v2()
+            .get_object()
             .bucket(&self.bucket)
-            .prefix(prefix)
-            .delimiter("/")
+            .key(self.get_blob_key(hash))
             .send()
             .await?;
 
-        if let Some(common_prefixes) = result.common_prefixes {
-            for prefix_obj in common_prefixes {
-                if let Some(prefix_str) = prefix_obj.prefix {
-                    if let Some(game_name) = prefix_str.strip_prefix("games/").and_then(|s| s.strip_suffix("/")) {
-                        games.push(game_name.to_string());
+        let data = result.body.collect().await?.into_bytes();
+        self.payload.open_blob(hash, &data)
+    }
+
+    async fn list_blobs(&self) -> Result<Vec<String>> {
+        let prefix = format!("{}{}/", self.key_prefix, BLOB_DIR);
+        let mut blobs = Vec::new();
+        let mut continuation_token = None;
+
+        loop {
+            let result = self.client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(&prefix)
+                .set_continuation_token(continuation_token)
+                .send()
+                .await?;
+
+            for object in result.contents() {
+                if let Some(hash) = object.key().and_then(|key| key.strip_prefix(prefix.as_str())) {
+                    if is_blob_name(hash) {
+                        blobs.push(hash.to_string());
                     }
                 }
             }
+
+            match result.next_continuation_token() {
+                Some(token) => continuation_token = Some(token.to_string()),
+                None => break,
+            }
         }
 
-        Ok(games)
+        Ok(blobs)
+    }
+
+    async fn delete_blob(&self, hash: &str) -> Result<StorageResult> {
+        if !is_blob_name(hash) {
+            return Err(anyhow::anyhow!("Invalid blob name: {}", hash));
+        }
+
+        match self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(self.get_blob_key(hash))
+            .send()
+            .await
+        {
+            Ok(_) => Ok(StorageResult {
+                success: true,
+                metadata: HashMap::new(),
+                error: None,
+            }),
+            Err(e) => Ok(StorageResult {
+                success: false,
+                metadata: HashMap::new(),
+                error: Some(aws_sdk_s3::error::DisplayErrorContext(&e).to_string()),
+            }),
+        }
     }
 
     async fn health_check(&self) -> Result<bool> {
@@ -355,6 +668,9 @@ impl StorageProvider for S3StorageProvider {
         StorageBackend::S3 {
             bucket: self.bucket.clone(),
             region: self.region.clone(),
+            endpoint_url: self.endpoint.endpoint_url.clone(),
+            force_path_style: self.endpoint.force_path_style,
+            key_prefix: self.endpoint.key_prefix.clone(),
         }
     }
 }
@@ -362,7 +678,8 @@ impl StorageProvider for S3StorageProvider {
 /// Local filesystem storage provider
 pub struct LocalStorageProvider {
     base_path: std::path::PathBuf,
-    config: StorageConfig,
+    payload: PayloadCodec,
+    layout: StorageLayout,
 }
 
 impl LocalStorageProvider {
@@ -372,10 +689,12 @@ impl LocalStorageProvider {
         
         // Create base directory if it doesn't exist
         std::fs::create_dir_all(&base_path)?;
+        let payload = PayloadCodec::from_config(&config)?;
 
         Ok(Self {
             base_path,
-            config,
+            payload,
+            layout: config.layout,
         })
     }
 
@@ -395,6 +714,11 @@ impl LocalStorageProvider {
             .join(game_name)
             .join("manifest.json")
     }
+
+    fn get_blob_path(&self, hash: &str) -> std::path::PathBuf {
+        self.base_path.join(BLOB_DIR).join(hash)
+    }
+
 }
 
 #[async_trait]
@@ -406,15 +730,22 @@ impl StorageProvider for LocalStorageProvider {
         version: &FileVersion,
         data: &[u8],
     ) -> Result<StorageResult> {
+        match self.layout {
+            StorageLayout::ContentAddressed => return self.upload_blob(data).await,
+            StorageLayout::Chunked => return upload_chunked(self, data).await,
+            StorageLayout::PerVersion => {}
+        }
+
         let file_path_buf = self.get_file_path(game_name, file_path, &version.version_id);
         
         if let Some(parent) = file_path_buf.parent() {
             tokio::fs::create_dir_all(parent).await?;
         }
 
-        tokio::fs::write(&file_path_buf, data).await?;
+        let (payload, payload_metadata) = self.payload.encode(data)?;
+        tokio::fs::write(&file_path_buf, payload).await?;
 
-        let mut metadata = HashMap::new();
+        let mut metadata = payload_metadata;
         metadata.insert("local_path".to_string(), file_path_buf.to_string_lossy().to_string());
 
         Ok(StorageResult {
@@ -430,9 +761,13 @@ impl StorageProvider for LocalStorageProvider {
         file_path: &str,
         version: &FileVersion,
     ) -> Result<Vec<u8>> {
+        if let Some(data) = download_blobs(self, version).await? {
+            return Ok(data);
+        }
+
         let file_path = self.get_file_path(game_name, file_path, &version.version_id);
         let data = tokio::fs::read(file_path).await?;
-        Ok(data)
+        self.payload.decode(version, data)
     }
 
     async fn upload_manifest(
@@ -446,7 +781,7 @@ impl StorageProvider for LocalStorageProvider {
             tokio::fs::create_dir_all(parent).await?;
         }
 
-        let manifest_data = serde_json::to_vec_pretty(manifest)?;
+        let manifest_data = self.payload.encode_manifest(manifest)?;
         tokio::fs::write(&manifest_path, manifest_data).await?;
 
         let mut metadata = HashMap::new();
@@ -467,8 +802,7 @@ impl StorageProvider for LocalStorageProvider {
         }
 
         let data = tokio::fs::read(manifest_path).await?;
-        let manifest: GameVersionManifest = serde_json::from_slice(&data)?;
-        Ok(Some(manifest))
+        Ok(Some(self.payload.decode_manifest(&data)?))
     }
 
     async fn delete_version(
@@ -477,6 +811,14 @@ impl StorageProvider for LocalStorageProvider {
         file_path: &str,
         version: &FileVersion,
     ) -> Result<StorageResult> {
+        if is_stored_in_blobs(version) {
+            return Ok(StorageResult {
+                success: true,
+                metadata: HashMap::new(),
+                error: None,
+            });
+        }
+
         let file_path = self.get_file_path(game_name, file_path, &version.version_id);
         
         match tokio::fs::remove_file(file_path).await {
@@ -513,6 +855,86 @@ impl StorageProvider for LocalStorageProvider {
         Ok(games)
     }
 
+    async fn upload_blob(&self, data: &[u8]) -> Result<StorageResult> {
+        let hash = self.payload.blob_name(data)?;
+        let blob_path = self.get_blob_path(&hash);
+
+        match self.blob_last_modified(&hash).await? {
+            None => {
+                tokio::fs::create_dir_all(self.base_path.join(BLOB_DIR)).await?;
+                // Write under a temporary name so a partial blob is never taken as stored
+                let temp_path = blob_path.with_extension(format!("tmp{}", std::process::id()));
+                tokio::fs::write(&temp_path, self.payload.encode_blob(data)?).await?;
+                tokio::fs::rename(&temp_path, &blob_path).await?;
+            }
+            Some(last_modified) if needs_refresh(last_modified) => {
+                let file = tokio::fs::OpenOptions::new().write(true).open(&blob_path).await?;
+                file.into_std().await.set_modified(std::time::SystemTime::now())?;
+            }
+            Some(_) => {}
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert(BLOB_METADATA_KEY.to_string(), hash);
+        metadata.insert("local_path".to_string(), blob_path.to_string_lossy().to_string());
+
+        Ok(StorageResult {
+            success: true,
+            metadata,
+            error: None,
+        })
+    }
+
+    async fn blob_last_modified(&self, hash: &str) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        match tokio::fs::metadata(self.get_blob_path(hash)).await {
+            Ok(metadata) => Ok(Some(metadata.modified()?.into())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn download_blob(&self, hash: &str) -> Result<Vec<u8>> {
+        let data = tokio::fs::read(self.get_blob_path(hash)).await?;
+        self.payload.open_blob(hash, &data)
+    }
+
+    async fn list_blobs(&self) -> Result<Vec<String>> {
+        let blob_dir = self.base_path.join(BLOB_DIR);
+        let mut blobs = Vec::new();
+
+        if !blob_dir.exists() {
+            return Ok(blobs);
+        }
+
+        let mut entries = tokio::fs::read_dir(blob_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if let Some(name) = entry.file_name().to_str().filter(|name| is_blob_name(name)) {
+                blobs.push(name.to_string());
+            }
+        }
+
+        Ok(blobs)
+    }
+
+    async fn delete_blob(&self, hash: &str) -> Result<StorageResult> {
+        if !is_blob_name(hash) {
+            return Err(anyhow::anyhow!("Invalid blob name: {}", hash));
+        }
+
+        match tokio::fs::remove_file(self.get_blob_path(hash)).await {
+            Ok(_) => Ok(StorageResult {
+                success: true,
+                metadata: HashMap::new(),
+                error: None,
+            }),
+            Err(e) => Ok(StorageResult {
+                success: false,
+                metadata: HashMap::new(),
+                error: Some(e.to_string()),
+            }),
+        }
+    }
+
     async fn health_check(&self) -> Result<bool> {
         Ok(self.base_path.exists() && self.base_path.is_dir())
     }
@@ -523,3 +945,142 @@ impl StorageProvider for LocalStorageProvider {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A version of `data` as the version manager records it before upload
+    fn version(id: &str, data: &[u8]) -> FileVersion {
+        FileVersion {
+            version_id: id.to_string(),
+            timestamp: chrono::Utc::now(),
+            size: data.len() as u64,
+            hash: crate::versioning::calculate_hash(data),
+            content_hash: None,
+            storage_metadata: HashMap::new(),
+            description: None,
+            is_pinned: false,
+        }
+    }
+
+    #[test]
+    fn test_s3_endpoint_prefix_normalization() {
+        let mut endpoint = S3Endpoint::default();
+        assert_eq!(endpoint.normalized_prefix(), "");
+
+        endpoint.key_prefix = Some("/decksaves/deck-1/".to_string());
+        assert_eq!(endpoint.normalized_prefix(), "decksaves/deck-1/");
+
+        endpoint.key_prefix = Some("/".to_string());
+        assert_eq!(endpoint.normalized_prefix(), "");
+    }
+
+    #[tokio::test]
+    async fn test_local_provider_compresses_payloads() {
+        let temp = tempfile::tempdir().unwrap();
+        let base = temp.path();
+        let provider = LocalStorageProvider::new(base.to_string_lossy().to_string(), StorageConfig::default()).unwrap();
+
+        let data = b"<save><slot>1</slot><slot>1</slot><slot>1</slot></save>".repeat(20);
+        let mut version = version("v1", &data);
+
+        let result = provider.upload_file("Game", "save.xml", &version, &data).await.unwrap();
+        assert_eq!(result.metadata.get("compression").map(String::as_str), Some("zstd"));
+        let stored = std::fs::read(provider.get_file_path("Game", "save.xml", "v1")).unwrap();
+        assert!(stored.len() < data.len());
+
+        version.storage_metadata.extend(result.metadata);
+        assert_eq!(provider.download_file("Game", "save.xml", &version).await.unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_local_provider_shares_blobs_between_versions() {
+        let temp = tempfile::tempdir().unwrap();
+        let base = temp.path();
+        let config = StorageConfig { layout: StorageLayout::ContentAddressed, ..StorageConfig::default() };
+        let provider = LocalStorageProvider::new(base.to_string_lossy().to_string(), config).unwrap();
+
+        let data = b"<save><slot>1</slot></save>".repeat(20);
+        let mut first = version("v1", &data);
+        let mut second = version("v2", &data);
+        first.storage_metadata.extend(provider.upload_file("Celeste", "0.celeste", &first, &data).await.unwrap().metadata);
+        second.storage_metadata.extend(provider.upload_file("Hades", "Profile1.sav", &second, &data).await.unwrap().metadata);
+
+        let hash = crate::versioning::calculate_hash(&data);
+        assert_eq!(first.storage_metadata.get(BLOB_METADATA_KEY), Some(&hash));
+        assert_eq!(provider.list_blobs().await.unwrap(), vec![hash.clone()]);
+        assert!(!provider.get_file_path("Celeste", "0.celeste", "v1").exists());
+
+        // Deleting a version keeps the shared blob until it is collected
+        assert!(provider.delete_version("Celeste", "0.celeste", &first).await.unwrap().success);
+        assert_eq!(provider.download_file("Hades", "Profile1.sav", &second).await.unwrap(), data);
+        assert!(provider.delete_blob(&hash).await.unwrap().success);
+        assert!(provider.list_blobs().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_local_provider_uploads_only_changed_chunks() {
+        let temp = tempfile::tempdir().unwrap();
+        let base = temp.path();
+        let config = StorageConfig { layout: StorageLayout::Chunked, ..StorageConfig::default() };
+        let provider = LocalStorageProvider::new(base.to_string_lossy().to_string(), config).unwrap();
+
+        let mut state = 7u32;
+        let original: Vec<u8> = (0..8 * 1024 * 1024)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        let mut edited = original.clone();
+        edited[4 * 1024 * 1024..4 * 1024 * 1024 + 16].copy_from_slice(b"sixteen new byte");
+
+        let mut first = version("v1", &original);
+        first.storage_metadata.extend(provider.upload_file("Game", "world.sav", &first, &original).await.unwrap().metadata);
+        let chunks_after_first = provider.list_blobs().await.unwrap().len();
+        assert!(chunks_after_first > 1);
+
+        let mut second = version("v2", &edited);
+        second.storage_metadata.extend(provider.upload_file("Game", "world.sav", &second, &edited).await.unwrap().metadata);
+        let new_chunks = provider.list_blobs().await.unwrap().len() - chunks_after_first;
+        assert!((1..=2).contains(&new_chunks), "{} new chunks", new_chunks);
+
+        assert_eq!(provider.download_file("Game", "world.sav", &second).await.unwrap(), edited);
+        assert_eq!(provider.download_file("Game", "world.sav", &first).await.unwrap(), original);
+
+        // Reassembly is checked against the version hash
+        let mut mislabeled = second.clone();
+        mislabeled.hash = first.hash.clone();
+        assert!(provider.download_file("Game", "world.sav", &mislabeled).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_s3_provider_keys_with_custom_endpoint() {
+        let endpoint = S3Endpoint {
+            endpoint_url: Some("http://localhost:9000".to_string()),
+            force_path_style: true,
+            key_prefix: Some("team".to_string()),
+        };
+        let provider = S3StorageProvider::new(
+            "saves".to_string(),
+            "us-east-1".to_string(),
+            endpoint,
+            StorageConfig::default(),
+        ).await.unwrap();
+
+        assert_eq!(provider.get_object_key("Celeste", "0.celeste", "v1"), "team/games/Celeste/files/0.celeste/versions/v1");
+        assert_eq!(provider.get_manifest_key("Celeste"), "team/games/Celeste/manifest.json");
+        assert_eq!(provider.get_blob_key("abc"), "team/blobs/abc");
+
+        match provider.get_backend_info() {
+            StorageBackend::S3 { endpoint_url, force_path_style, .. } => {
+                assert_eq!(endpoint_url.as_deref(), Some("http://localhost:9000"));
+                assert!(force_path_style);
+            }
+            other => panic!("unexpected backend: {:?}", other),
+        }
+    }
+}
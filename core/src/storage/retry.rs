@@ -0,0 +1,497 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use rand::Rng;
+use regex::Regex;
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use super::providers::{StorageBackend, StorageConfig, StorageProvider, StorageResult};
+use crate::versioning::{FileVersion, GameVersionManifest};
+
+/// Whether a failed storage call is worth repeating
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Network drops, timeouts, throttling and server-side errors
+    Retryable,
+    /// Bad credentials, missing objects, corrupt data and anything unrecognised
+    Fatal,
+}
+
+/// Classify an error returned by a storage provider
+pub fn classify_error(err: &anyhow::Error) -> ErrorClass {
+    for cause in err.chain() {
+        if cause.downcast_ref::<tokio::time::error::Elapsed>().is_some() {
+            return ErrorClass::Retryable;
+        }
+
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            if e.is_timeout() || e.is_connect() || e.is_request() {
+                return ErrorClass::Retryable;
+            }
+            if let Some(status) = e.status() {
+                return classify_status(status.as_u16());
+            }
+        }
+
+        if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+            use std::io::ErrorKind::*;
+            return match e.kind() {
+                TimedOut | Interrupted | WouldBlock | ConnectionReset | ConnectionAborted
+                | ConnectionRefused | NotConnected | BrokenPipe | UnexpectedEof => ErrorClass::Retryable,
+                _ => ErrorClass::Fatal,
+            };
+        }
+    }
+
+    // Provider errors are mostly opaque (AWS SDK, formatted HTTP statuses),
+    // so fall back to the rendered message including its source chain
+    classify_message(&format!("{:#}", err))
+}
+
+/// Classify an error message, e.g. from `StorageResult.error`
+pub fn classify_message(message: &str) -> ErrorClass {
+    static STATUS: OnceLock<Regex> = OnceLock::new();
+    let status = STATUS.get_or_init(|| Regex::new(r"(?i)status:?\s*(\d{3})\b").unwrap());
+    if let Some(code) = status.captures(message).and_then(|c| c[1].parse::<u16>().ok()) {
+        return classify_status(code);
+    }
+
+    let message = message.to_lowercase();
+
+    const FATAL: &[&str] = &[
+        "accessdenied", "access denied", "invalidaccesskeyid", "signaturedoesnotmatch",
+        "nosuchbucket", "nosuchkey", "forbidden", "unauthorized", "decryption failed",
+        "passphrase", "not found",
+    ];
+    if FATAL.iter().any(|needle| message.contains(needle)) {
+        return ErrorClass::Fatal;
+    }
+
+    const RETRYABLE: &[&str] = &[
+        "timeout", "timed out", "dispatch failure", "connection", "connect error",
+        "broken pipe", "network", "dns error", "temporarily", "unavailable", "slowdown",
+        "slow down", "throttl", "too many requests", "internalerror", "internal error",
+        "bad gateway", "requesttimeout", "throughput",
+    ];
+    if RETRYABLE.iter().any(|needle| message.contains(needle)) {
+        return ErrorClass::Retryable;
+    }
+
+    ErrorClass::Fatal
+}
+
+fn classify_status(code: u16) -> ErrorClass {
+    match code {
+        408 | 429 | 500..=599 => ErrorClass::Retryable,
+        _ => ErrorClass::Fatal,
+    }
+}
+
+/// Retry settings applied to every storage call.
+///
+/// Attempts themselves are not limited in time, since a large save on a slow
+/// link may take long to transfer. Backends time out connecting and stalled
+/// transfers instead, see `StorageConfig::connection_timeout_seconds`.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt
+    pub max_retries: u32,
+    /// Limit for a health check, which should report the current state rather than wait it out
+    pub health_check_timeout: Duration,
+    /// Backoff before the first retry, doubled for each subsequent one
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &StorageConfig) -> Self {
+        Self {
+            max_retries: config.retry_attempts,
+            health_check_timeout: config.idle_timeout(),
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+
+    /// Exponential backoff with full jitter for the given retry (0-based)
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponential = self.base_delay.saturating_mul(2u32.saturating_pow(retry));
+        let cap = exponential.min(self.max_delay);
+        let millis = cap.as_millis() as u64;
+        if millis == 0 {
+            return cap;
+        }
+        Duration::from_millis(rand::thread_rng().gen_range(millis / 2..=millis))
+    }
+}
+
+/// Decorates any `StorageProvider` with retries of transient failures. Both
+/// `Err` results and unsuccessful `StorageResult`s are retried when they
+/// classify as `ErrorClass::Retryable`; a stalled connection or transfer
+/// fails through the backend's own connect and idle timeouts.
+pub struct RetryingStorageProvider {
+    inner: Box<dyn StorageProvider>,
+    policy: RetryPolicy,
+}
+
+impl RetryingStorageProvider {
+    pub fn new(inner: Box<dyn StorageProvider>, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    async fn run<T, F, Fut>(&self, operation: &str, call: F) -> Result<T>
+    where
+        T: RetryOutcome,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        retry_with(&self.policy, operation, call, T::failure_message).await
+    }
+}
+
+/// Run a storage call outside of a `StorageProvider`, such as a raw S3
+/// request, retrying it with backoff while it fails with a transient error
+pub async fn retry_transient<T, F, Fut>(policy: &RetryPolicy, operation: &str, call: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    retry_with(policy, operation, call, |_| None).await
+}
+
+async fn retry_with<T, F, Fut>(
+    policy: &RetryPolicy,
+    operation: &str,
+    mut call: F,
+    failure_message: impl Fn(&T) -> Option<&str>,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut retry = 0;
+    loop {
+        let outcome = call().await;
+
+        let failure = match &outcome {
+            Ok(value) => match failure_message(value) {
+                Some(message) if classify_message(message) == ErrorClass::Retryable => Some(message.to_string()),
+                _ => None,
+            },
+            Err(e) if classify_error(e) == ErrorClass::Retryable => Some(format!("{:#}", e)),
+            Err(_) => None,
+        };
+
+        match failure {
+            Some(message) if retry < policy.max_retries => {
+                let delay = policy.backoff(retry);
+                retry += 1;
+                warn!(
+                    "{} failed ({}), retrying in {:?} (attempt {}/{})",
+                    operation, message, delay, retry, policy.max_retries
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Some(message) => {
+                debug!("{} giving up after {} retries: {}", operation, retry, message);
+                return outcome;
+            }
+            None => return outcome,
+        }
+    }
+}
+
+/// Lets the retry loop see failures that providers report inside `Ok`
+trait RetryOutcome {
+    fn failure_message(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl RetryOutcome for StorageResult {
+    fn failure_message(&self) -> Option<&str> {
+        if self.success {
+            None
+        } else {
+            Some(self.error.as_deref().unwrap_or("unknown storage error"))
+        }
+    }
+}
+
+impl RetryOutcome for Vec<u8> {}
+impl RetryOutcome for Vec<String> {}
+impl RetryOutcome for Option<GameVersionManifest> {}
+impl RetryOutcome for Option<chrono::DateTime<chrono::Utc>> {}
+
+#[async_trait]
+impl StorageProvider for RetryingStorageProvider {
+    async fn upload_file(
+        &self,
+        game_name: &str,
+        file_path: &str,
+        version: &FileVersion,
+        data: &[u8],
+    ) -> Result<StorageResult> {
+        self.run("upload_file", || self.inner.upload_file(game_name, file_path, version, data)).await
+    }
+
+    async fn download_file(
+        &self,
+        game_name: &str,
+        file_path: &str,
+        version: &FileVersion,
+    ) -> Result<Vec<u8>> {
+        self.run("download_file", || self.inner.download_file(game_name, file_path, version)).await
+    }
+
+    async fn upload_manifest(
+        &self,
+        game_name: &str,
+        manifest: &GameVersionManifest,
+    ) -> Result<StorageResult> {
+        self.run("upload_manifest", || self.inner.upload_manifest(game_name, manifest)).await
+    }
+
+    async fn download_manifest(&self, game_name: &str) -> Result<Option<GameVersionManifest>> {
+        self.run("download_manifest", || self.inner.download_manifest(game_name)).await
+    }
+
+    async fn delete_version(
+        &self,
+        game_name: &str,
+        file_path: &str,
+        version: &FileVersion,
+    ) -> Result<StorageResult> {
+        self.run("delete_version", || self.inner.delete_version(game_name, file_path, version)).await
+    }
+
+    async fn list_games(&self) -> Result<Vec<String>> {
+        self.run("list_games", || self.inner.list_games()).await
+    }
+
+    async fn upload_blob(&self, data: &[u8]) -> Result<StorageResult> {
+        self.run("upload_blob", || self.inner.upload_blob(data)).await
+    }
+
+    async fn blob_last_modified(&self, hash: &str) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        self.run("blob_last_modified", || self.inner.blob_last_modified(hash)).await
+    }
+
+    async fn download_blob(&self, hash: &str) -> Result<Vec<u8>> {
+        self.run("download_blob", || self.inner.download_blob(hash)).await
+    }
+
+    async fn list_blobs(&self) -> Result<Vec<String>> {
+        self.run("list_blobs", || self.inner.list_blobs()).await
+    }
+
+    async fn delete_blob(&self, hash: &str) -> Result<StorageResult> {
+        self.run("delete_blob", || self.inner.delete_blob(hash)).await
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        tokio::time::timeout(self.policy.health_check_timeout, self.inner.health_check())
+            .await
+            .unwrap_or_else(|_| Err(anyhow!("Health check timed out after {:?}", self.policy.health_check_timeout)))
+    }
+
+    fn get_backend_info(&self) -> StorageBackend {
+        self.inner.get_backend_info()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    /// Takes `delay` for every call, failing the first `failures` with `error`
+    struct FlakyProvider {
+        calls: Arc<AtomicU32>,
+        failures: u32,
+        error: &'static str,
+        delay: Duration,
+    }
+
+    impl FlakyProvider {
+        fn wrap(failures: u32, error: &'static str, delay: Duration) -> (RetryingStorageProvider, Arc<AtomicU32>) {
+            let calls = Arc::new(AtomicU32::new(0));
+            let inner = FlakyProvider { calls: calls.clone(), failures, error, delay };
+            let policy = RetryPolicy {
+                max_retries: 3,
+                health_check_timeout: Duration::from_millis(200),
+                base_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(5),
+            };
+            (RetryingStorageProvider::new(Box::new(inner), policy), calls)
+        }
+
+        async fn attempt(&self) -> Result<()> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            if call < self.failures {
+                return Err(anyhow!(self.error));
+            }
+            Ok(())
+        }
+
+        /// An attempt reported the way providers report failed writes
+        async fn write(&self) -> Result<StorageResult> {
+            let error = self.attempt().await.err().map(|e| e.to_string());
+            Ok(StorageResult { success: error.is_none(), metadata: HashMap::new(), error })
+        }
+    }
+
+    #[async_trait]
+    impl StorageProvider for FlakyProvider {
+        async fn upload_file(&self, _: &str, _: &str, _: &FileVersion, _: &[u8]) -> Result<StorageResult> {
+            self.write().await
+        }
+
+        async fn download_file(&self, _: &str, _: &str, _: &FileVersion) -> Result<Vec<u8>> {
+            self.attempt().await.map(|_| b"data".to_vec())
+        }
+
+        async fn upload_manifest(&self, _: &str, _: &GameVersionManifest) -> Result<StorageResult> {
+            self.write().await
+        }
+
+        async fn download_manifest(&self, _: &str) -> Result<Option<GameVersionManifest>> {
+            self.attempt().await.map(|_| None)
+        }
+
+        async fn delete_version(&self, _: &str, _: &str, _: &FileVersion) -> Result<StorageResult> {
+            self.write().await
+        }
+
+        async fn upload_blob(&self, _: &[u8]) -> Result<StorageResult> {
+            self.write().await
+        }
+
+        async fn blob_last_modified(&self, _: &str) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+            self.attempt().await.map(|_| None)
+        }
+
+        async fn download_blob(&self, _: &str) -> Result<Vec<u8>> {
+            self.attempt().await.map(|_| b"data".to_vec())
+        }
+
+        async fn list_blobs(&self) -> Result<Vec<String>> {
+            self.attempt().await.map(|_| vec!["0".repeat(64)])
+        }
+
+        async fn delete_blob(&self, _: &str) -> Result<StorageResult> {
+            self.write().await
+        }
+
+        async fn list_games(&self) -> Result<Vec<String>> {
+            self.attempt().await.map(|_| vec!["Celeste".to_string()])
+        }
+
+        async fn health_check(&self) -> Result<bool> {
+            tokio::time::sleep(self.delay).await;
+            Ok(true)
+        }
+
+        fn get_backend_info(&self) -> StorageBackend {
+            StorageBackend::Local { base_path: String::new() }
+        }
+    }
+
+    fn version() -> FileVersion {
+        FileVersion {
+            version_id: "v1".to_string(),
+            timestamp: chrono::Utc::now(),
+            size: 4,
+            hash: String::new(),
+            content_hash: None,
+            storage_metadata: HashMap::new(),
+            description: None,
+            is_pinned: false,
+        }
+    }
+
+    #[test]
+    fn test_classification() {
+        assert_eq!(classify_message("dispatch failure: io error: connection refused"), ErrorClass::Retryable);
+        assert_eq!(classify_message("service error: unhandled error (SlowDown)"), ErrorClass::Retryable);
+        assert_eq!(classify_message("PUT http://nas/dav/404/save failed with status 503 Service Unavailable"), ErrorClass::Retryable);
+        assert_eq!(classify_message("GET http://nas/dav/save failed with status 404 Not Found"), ErrorClass::Fatal);
+        assert_eq!(classify_message("service error: AccessDenied: connection not allowed"), ErrorClass::Fatal);
+        assert_eq!(classify_message("Decryption failed: wrong passphrase or corrupted data"), ErrorClass::Fatal);
+
+        let io = anyhow::Error::new(std::io::Error::from(std::io::ErrorKind::ConnectionReset));
+        assert_eq!(classify_error(&io), ErrorClass::Retryable);
+        let io = anyhow::Error::new(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        assert_eq!(classify_error(&io), ErrorClass::Fatal);
+    }
+
+    #[tokio::test]
+    async fn test_transient_failures_are_retried() {
+        let (provider, calls) = FlakyProvider::wrap(2, "dispatch failure: connection reset", Duration::ZERO);
+        assert_eq!(provider.download_file("Game", "save", &version()).await.unwrap(), b"data");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let (provider, calls) = FlakyProvider::wrap(2, "status 503", Duration::ZERO);
+        assert!(provider.upload_file("Game", "save", &version(), b"data").await.unwrap().success);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fatal_failures_are_not_retried() {
+        let (provider, calls) = FlakyProvider::wrap(1, "AccessDenied", Duration::ZERO);
+        let result = provider.upload_file("Game", "save", &version(), b"data").await.unwrap();
+        assert!(!result.success);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_retries() {
+        let (provider, calls) = FlakyProvider::wrap(10, "connection refused", Duration::ZERO);
+        assert!(provider.list_games().await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_slow_transfers_are_not_cut_off() {
+        // Slower than the health check timeout, which only applies to health checks
+        let (provider, calls) = FlakyProvider::wrap(0, "unused", Duration::from_millis(300));
+        assert_eq!(provider.download_blob(&"0".repeat(64)).await.unwrap(), b"data");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(provider.health_check().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_every_operation_is_retried() {
+        let (provider, calls) = FlakyProvider::wrap(1, "status 503", Duration::ZERO);
+        let manifest = GameVersionManifest {
+            game_name: "Game".to_string(),
+            manifest_version: 1,
+            last_updated: chrono::Utc::now(),
+            files: HashMap::new(),
+            metadata: HashMap::new(),
+        };
+        assert!(provider.upload_manifest("Game", &manifest).await.unwrap().success);
+        assert!(provider.download_manifest("Game").await.unwrap().is_none());
+        assert!(provider.delete_version("Game", "save", &version()).await.unwrap().success);
+        assert!(provider.upload_blob(b"data").await.unwrap().success);
+        assert_eq!(provider.list_blobs().await.unwrap().len(), 1);
+        assert!(provider.delete_blob(&"0".repeat(64)).await.unwrap().success);
+        assert_eq!(calls.load(Ordering::SeqCst), 7);
+
+        let (provider, _) = FlakyProvider::wrap(1, "connection reset", Duration::ZERO);
+        let attempts = AtomicU32::new(0);
+        let value = retry_transient(&provider.policy, "get_object", || async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 => Err(anyhow!("dispatch failure: connection reset")),
+                _ => Ok(42),
+            }
+        }).await.unwrap();
+        assert_eq!(value, 42);
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+}
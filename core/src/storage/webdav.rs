@@ -0,0 +1,787 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use regex::Regex;
+use reqwest::{Method, StatusCode, Url};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::debug;
+
+use super::blobs::{download_blobs, is_blob_name, needs_refresh, upload_chunked, is_stored_in_blobs, StorageLayout, BLOB_DIR, BLOB_METADATA_KEY};
+use super::payload::PayloadCodec;
+use super::providers::{StorageBackend, StorageConfig, StorageProvider, StorageResult};
+use crate::versioning::{FileVersion, GameVersionManifest};
+
+/// PROPFIND body asking only for the resource type of each entry
+const PROPFIND_RESOURCETYPE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:resourcetype/></d:prop></d:propfind>"#;
+
+/// Slowest upload rate tolerated before a PUT is abandoned. reqwest does not
+/// report upload progress, so unlike a download a PUT cannot time out only
+/// once it stalls; its time limit grows with the amount of data instead.
+const MIN_UPLOAD_BYTES_PER_SECOND: u64 = 16 * 1024;
+
+/// WebDAV storage provider (Nextcloud, ownCloud, Apache mod_dav, rclone serve, ...)
+///
+/// Uses the same `games/<game>/files/<path>/versions/<id>` layout as the
+/// local and S3 providers, rooted at `base_url`.
+pub struct WebDavStorageProvider {
+    base_url: Url,
+    username: String,
+    password: Option<String>,
+    client: reqwest::Client,
+    idle_timeout: Duration,
+    payload: PayloadCodec,
+    layout: StorageLayout,
+}
+
+impl WebDavStorageProvider {
+    pub fn new(
+        base_url: String,
+        username: String,
+        password: Option<String>,
+        config: StorageConfig,
+    ) -> Result<Self> {
+        let base_url = Url::parse(&base_url)
+            .with_context(|| format!("Invalid WebDAV base URL: {}", base_url))?;
+
+        if base_url.cannot_be_a_base() {
+            return Err(anyhow::anyhow!("WebDAV base URL cannot be used as a base: {}", base_url));
+        }
+
+        let client = reqwest::Client::builder()
+            .connect_timeout(config.idle_timeout())
+            .build()
+            .context("Failed to create WebDAV HTTP client")?;
+        let payload = PayloadCodec::from_config(&config)?;
+
+        Ok(Self {
+            base_url,
+            username,
+            password,
+            client,
+            idle_timeout: config.idle_timeout(),
+            payload,
+            layout: config.layout,
+        })
+    }
+
+    /// Build a URL below the base URL, percent-encoding every segment
+    fn url_for(&self, segments: &[&str], trailing_slash: bool) -> Url {
+        let mut url = self.base_url.clone();
+        {
+            // `cannot_be_a_base` was rejected in `new`, so this cannot fail
+            let mut path = url.path_segments_mut().expect("base URL checked in new");
+            path.pop_if_empty();
+            for segment in segments {
+                path.extend(segment.split('/').filter(|s| !s.is_empty()));
+            }
+            if trailing_slash {
+                path.push("");
+            }
+        }
+        url
+    }
+
+    fn file_segments<'a>(game_name: &'a str, file_path: &'a str, version_id: &'a str) -> Vec<&'a str> {
+        vec!["games", game_name, "files", file_path, "versions", version_id]
+    }
+
+    fn get_file_url(&self, game_name: &str, file_path: &str, version_id: &str) -> Url {
+        self.url_for(&Self::file_segments(game_name, file_path, version_id), false)
+    }
+
+    fn get_manifest_url(&self, game_name: &str) -> Url {
+        self.url_for(&["games", game_name, "manifest.json"], false)
+    }
+
+    fn get_blob_url(&self, hash: &str) -> Url {
+        self.url_for(&[BLOB_DIR, hash], false)
+    }
+
+    fn request(&self, method: Method, url: Url) -> reqwest::RequestBuilder {
+        self.client
+            .request(method, url)
+            .basic_auth(&self.username, self.password.as_deref())
+    }
+
+    /// Send a request without a large body, failing when the server does not
+    /// answer within the idle timeout
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        match tokio::time::timeout(self.idle_timeout, request.send()).await {
+            Ok(response) => Ok(response?),
+            Err(elapsed) => Err(anyhow::Error::new(elapsed).context("WebDAV server did not respond")),
+        }
+    }
+
+    /// Read a response body, failing when it stops arriving for the idle
+    /// timeout rather than when it takes long overall
+    async fn read_body(&self, mut response: reqwest::Response) -> Result<Vec<u8>> {
+        let mut body = Vec::new();
+        loop {
+            match tokio::time::timeout(self.idle_timeout, response.chunk()).await {
+                Ok(chunk) => match chunk? {
+                    Some(chunk) => body.extend_from_slice(&chunk),
+                    None => return Ok(body),
+                },
+                Err(elapsed) => return Err(anyhow::Error::new(elapsed).context("WebDAV download stalled")),
+            }
+        }
+    }
+
+    /// Create every collection leading up to (but not including) the last segment.
+    /// WebDAV has no recursive MKCOL, so each level is created in turn.
+    async fn ensure_collections(&self, segments: &[&str]) -> Result<()> {
+        let parts: Vec<&str> = segments
+            .iter()
+            .flat_map(|s| s.split('/'))
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        for depth in 1..parts.len() {
+            let url = self.url_for(&parts[..depth], true);
+            let response = self
+                .send(self.request(Method::from_bytes(b"MKCOL")?, url.clone()))
+                .await
+                .with_context(|| format!("MKCOL request failed for {}", url))?;
+
+            match response.status() {
+                // 405 means the collection already exists
+                s if s.is_success() || s == StatusCode::METHOD_NOT_ALLOWED => {}
+                s => return Err(anyhow::anyhow!("MKCOL {} failed with status {}", url, s)),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn put(&self, url: Url, data: Vec<u8>, content_type: &str) -> Result<StatusCode> {
+        let timeout = self.idle_timeout + Duration::from_secs(data.len() as u64 / MIN_UPLOAD_BYTES_PER_SECOND);
+        let response = self
+            .request(Method::PUT, url)
+            .timeout(timeout)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(data)
+            .send()
+            .await?;
+        Ok(response.status())
+    }
+
+    async fn delete(&self, url: Url) -> Result<StorageResult> {
+        match self.send(self.request(Method::DELETE, url.clone())).await {
+            Ok(response) if response.status().is_success() => Ok(StorageResult {
+                success: true,
+                metadata: HashMap::new(),
+                error: None,
+            }),
+            Ok(response) => Ok(StorageResult {
+                success: false,
+                metadata: HashMap::new(),
+                error: Some(format!("DELETE {} failed with status {}", url, response.status())),
+            }),
+            Err(e) => Ok(StorageResult {
+                success: false,
+                metadata: HashMap::new(),
+                error: Some(e.to_string()),
+            }),
+        }
+    }
+
+    /// Names of the child collections, or of the child resources, of a
+    /// collection, listed with a depth 1 PROPFIND
+    async fn list_children(&self, segments: &[&str], collections: bool) -> Result<Vec<String>> {
+        let url = self.url_for(segments, true);
+
+        let request = self
+            .request(Method::from_bytes(b"PROPFIND")?, url.clone())
+            .header("Depth", "1")
+            .header(reqwest::header::CONTENT_TYPE, "application/xml")
+            .body(PROPFIND_RESOURCETYPE);
+        let response = self.send(request).await?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(Vec::new()),
+            s if s.is_success() => {
+                let body = self.read_body(response).await?;
+                Ok(Self::parse_children(&String::from_utf8_lossy(&body), &url, collections))
+            }
+            s => Err(anyhow::anyhow!("PROPFIND {} failed with status {}", url, s)),
+        }
+    }
+
+    /// Extract the names of the child collections (or, with `collections`
+    /// unset, the child resources) from a PROPFIND multistatus body
+    fn parse_children(body: &str, parent: &Url, collections: bool) -> Vec<String> {
+        static RESPONSE: OnceLock<Regex> = OnceLock::new();
+        static HREF: OnceLock<Regex> = OnceLock::new();
+        static COLLECTION: OnceLock<Regex> = OnceLock::new();
+        let response_regex = RESPONSE.get_or_init(|| {
+            Regex::new(r"(?s)<(?:[A-Za-z0-9]+:)?response\b.*?</(?:[A-Za-z0-9]+:)?response>").unwrap()
+        });
+        let href_regex = HREF.get_or_init(|| {
+            Regex::new(r"(?s)<(?:[A-Za-z0-9]+:)?href>\s*(.*?)\s*</(?:[A-Za-z0-9]+:)?href>").unwrap()
+        });
+        let collection_regex = COLLECTION.get_or_init(|| Regex::new(r"<(?:[A-Za-z0-9]+:)?collection\s*/?>").unwrap());
+
+        let parent_path = parent.path().trim_end_matches('/').to_string();
+        let mut names = Vec::new();
+
+        for response in response_regex.find_iter(body) {
+            let response = response.as_str();
+            if collection_regex.is_match(response) != collections {
+                continue;
+            }
+
+            let Some(href) = href_regex.captures(response).and_then(|c| c.get(1)) else {
+                continue;
+            };
+
+            // Servers may return absolute URLs or absolute paths
+            let href_path = match parent.join(href.as_str()) {
+                Ok(url) => url.path().to_string(),
+                Err(_) => continue,
+            };
+            let href_path = href_path.trim_end_matches('/');
+
+            if href_path == parent_path {
+                continue;
+            }
+
+            if let Some(name) = href_path.rsplit('/').next() {
+                let decoded = percent_decode(name);
+                if !decoded.is_empty() {
+                    names.push(decoded);
+                }
+            }
+        }
+
+        names
+    }
+}
+
+/// Minimal percent-decoding for href segments returned by PROPFIND
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[async_trait]
+impl StorageProvider for WebDavStorageProvider {
+    async fn upload_file(
+        &self,
+        game_name: &str,
+        file_path: &str,
+        version: &FileVersion,
+        data: &[u8],
+    ) -> Result<StorageResult> {
+        match self.layout {
+            StorageLayout::ContentAddressed => return self.upload_blob(data).await,
+            StorageLayout::Chunked => return upload_chunked(self, data).await,
+            StorageLayout::PerVersion => {}
+        }
+
+        let segments = Self::file_segments(game_name, file_path, &version.version_id);
+        let url = self.get_file_url(game_name, file_path, &version.version_id);
+
+        if let Err(e) = self.ensure_collections(&segments).await {
+            return Ok(StorageResult {
+                success: false,
+                metadata: HashMap::new(),
+                error: Some(e.to_string()),
+            });
+        }
+
+        let (payload, payload_metadata) = self.payload.encode(data)?;
+        debug!("WebDAV PUT {} ({} bytes)", url, payload.len());
+        let status = self.put(url.clone(), payload, "application/octet-stream").await?;
+
+        if status.is_success() {
+            let mut metadata = payload_metadata;
+            metadata.insert("webdav_url".to_string(), url.to_string());
+
+            Ok(StorageResult {
+                success: true,
+                metadata,
+                error: None,
+            })
+        } else {
+            Ok(StorageResult {
+                success: false,
+                metadata: HashMap::new(),
+                error: Some(format!("PUT {} failed with status {}", url, status)),
+            })
+        }
+    }
+
+    async fn download_file(
+        &self,
+        game_name: &str,
+        file_path: &str,
+        version: &FileVersion,
+    ) -> Result<Vec<u8>> {
+        if let Some(data) = download_blobs(self, version).await? {
+            return Ok(data);
+        }
+
+        let url = self.get_file_url(game_name, file_path, &version.version_id);
+
+        let response = self.send(self.request(Method::GET, url.clone())).await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("GET {} failed with status {}", url, response.status()));
+        }
+
+        self.payload.decode(version, self.read_body(response).await?)
+    }
+
+    async fn upload_manifest(
+        &self,
+        game_name: &str,
+        manifest: &GameVersionManifest,
+    ) -> Result<StorageResult> {
+        let url = self.get_manifest_url(game_name);
+        let manifest_data = self.payload.encode_manifest(manifest)?;
+
+        if let Err(e) = self.ensure_collections(&["games", game_name, "manifest.json"]).await {
+            return Ok(StorageResult {
+                success: false,
+                metadata: HashMap::new(),
+                error: Some(e.to_string()),
+            });
+        }
+
+        let status = self.put(url.clone(), manifest_data, self.payload.manifest_content_type()).await?;
+
+        if status.is_success() {
+            let mut metadata = HashMap::new();
+            metadata.insert("webdav_url".to_string(), url.to_string());
+
+            Ok(StorageResult {
+                success: true,
+                metadata,
+                error: None,
+            })
+        } else {
+            Ok(StorageResult {
+                success: false,
+                metadata: HashMap::new(),
+                error: Some(format!("PUT {} failed with status {}", url, status)),
+            })
+        }
+    }
+
+    async fn download_manifest(&self, game_name: &str) -> Result<Option<GameVersionManifest>> {
+        let url = self.get_manifest_url(game_name);
+
+        let response = self.send(self.request(Method::GET, url.clone())).await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            s if s.is_success() => {
+                let data = self.read_body(response).await?;
+                Ok(Some(self.payload.decode_manifest(&data)?))
+            }
+            s => Err(anyhow::anyhow!("GET {} failed with status {}", url, s)),
+        }
+    }
+
+    async fn delete_version(
+        &self,
+        game_name: &str,
+        file_path: &str,
+        version: &FileVersion,
+    ) -> Result<StorageResult> {
+        if is_stored_in_blobs(version) {
+            return Ok(StorageResult {
+                success: true,
+                metadata: HashMap::new(),
+                error: None,
+            });
+        }
+
+        self.delete(self.get_file_url(game_name, file_path, &version.version_id)).await
+    }
+
+    async fn list_games(&self) -> Result<Vec<String>> {
+        self.list_children(&["games"], true).await
+    }
+
+    async fn upload_blob(&self, data: &[u8]) -> Result<StorageResult> {
+        let hash = self.payload.blob_name(data)?;
+        let url = self.get_blob_url(&hash);
+
+        match self.blob_last_modified(&hash).await? {
+            Some(last_modified) if !needs_refresh(last_modified) => debug!("Blob {} already stored", hash),
+            existing => {
+                if existing.is_none() {
+                    if let Err(e) = self.ensure_collections(&[BLOB_DIR, &hash]).await {
+                        return Ok(StorageResult {
+                            success: false,
+                            metadata: HashMap::new(),
+                            error: Some(e.to_string()),
+                        });
+                    }
+                }
+
+                // WebDAV cannot touch a resource, so a blob due for a refresh is written again
+                let blob = self.payload.encode_blob(data)?;
+                debug!("WebDAV PUT {} ({} bytes)", url, blob.len());
+                let status = self.put(url.clone(), blob, "application/octet-stream").await?;
+                if !status.is_success() {
+                    return Ok(StorageResult {
+                        success: false,
+                        metadata: HashMap::new(),
+                        error: Some(format!("PUT {} failed with status {}", url, status)),
+                    });
+                }
+            }
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert(BLOB_METADATA_KEY.to_string(), hash);
+        metadata.insert("webdav_url".to_string(), url.to_string());
+
+        Ok(StorageResult {
+            success: true,
+            metadata,
+            error: None,
+        })
+    }
+
+    async fn blob_last_modified(&self, hash: &str) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        let url = self.get_blob_url(hash);
+
+        let response = self.send(self.request(Method::HEAD, url.clone())).await?;
+        match response.status() {
+            // A server that does not report Last-Modified never lets a blob age
+            s if s.is_success() => Ok(Some(
+                response.headers()
+                    .get(reqwest::header::LAST_MODIFIED)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| chrono::DateTime::parse_from_rfc2822(value).ok())
+                    .map(|dt| dt.with_timezone(&chrono::Utc))
+                    .unwrap_or_else(chrono::Utc::now),
+            )),
+            StatusCode::NOT_FOUND => Ok(None),
+            s => Err(anyhow::anyhow!("HEAD {} failed with status {}", url, s)),
+        }
+    }
+
+    async fn download_blob(&self, hash: &str) -> Result<Vec<u8>> {
+        let url = self.get_blob_url(hash);
+
+        let response = self.send(self.request(Method::GET, url.clone())).await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("GET {} failed with status {}", url, response.status()));
+        }
+
+        self.payload.open_blob(hash, &self.read_body(response).await?)
+    }
+
+    async fn list_blobs(&self) -> Result<Vec<String>> {
+        let mut blobs = self.list_children(&[BLOB_DIR], false).await?;
+        blobs.retain(|name| is_blob_name(name));
+        Ok(blobs)
+    }
+
+    async fn delete_blob(&self, hash: &str) -> Result<StorageResult> {
+        if !is_blob_name(hash) {
+            return Err(anyhow::anyhow!("Invalid blob name: {}", hash));
+        }
+        self.delete(self.get_blob_url(hash)).await
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        let url = self.url_for(&[], true);
+
+        let request = self
+            .request(Method::from_bytes(b"PROPFIND")?, url)
+            .header("Depth", "0")
+            .header(reqwest::header::CONTENT_TYPE, "application/xml")
+            .body(PROPFIND_RESOURCETYPE);
+
+        match self.send(request).await {
+            Ok(response) => Ok(response.status().is_success()),
+            Err(_) => Ok(false),
+        }
+    }
+
+    fn get_backend_info(&self) -> StorageBackend {
+        StorageBackend::WebDAV {
+            base_url: self.base_url.to_string(),
+            username: self.username.clone(),
+            password: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::versioning::calculate_hash;
+    use std::collections::HashSet;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// In-memory stand-in for a WebDAV server, speaking just enough HTTP/1.1
+    /// (one request per connection) for the provider's MKCOL/PUT/GET/DELETE/PROPFIND calls
+    #[derive(Default)]
+    struct DavState {
+        files: HashMap<String, Vec<u8>>,
+        collections: HashSet<String>,
+    }
+
+    async fn spawn_dav_server() -> (String, Arc<Mutex<DavState>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(DavState::default()));
+        state.lock().unwrap().collections.insert("/dav".to_string());
+
+        let server_state = state.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else { break };
+                let state = server_state.clone();
+                tokio::spawn(async move {
+                    let _ = handle_connection(stream, state).await;
+                });
+            }
+        });
+
+        (format!("http://{}/dav/", addr), state)
+    }
+
+    async fn handle_connection(mut stream: TcpStream, state: Arc<Mutex<DavState>>) -> std::io::Result<()> {
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 4096];
+        let header_end = loop {
+            let n = stream.read(&mut chunk).await?;
+            if n == 0 {
+                return Ok(());
+            }
+            buf.extend_from_slice(&chunk[..n]);
+            if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                break pos + 4;
+            }
+        };
+
+        let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+        let mut lines = head.lines();
+        let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+        let method = request_line.next().unwrap_or_default().to_string();
+        let path = percent_decode(request_line.next().unwrap_or_default());
+        let mut content_length = 0usize;
+        let mut depth = "1".to_string();
+        for line in lines {
+            if let Some((name, value)) = line.split_once(':') {
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "content-length" => content_length = value.trim().parse().unwrap_or(0),
+                    "depth" => depth = value.trim().to_string(),
+                    _ => {}
+                }
+            }
+        }
+
+        let mut body = buf[header_end..].to_vec();
+        while body.len() < content_length {
+            let n = stream.read(&mut chunk).await?;
+            if n == 0 {
+                break;
+            }
+            body.extend_from_slice(&chunk[..n]);
+        }
+
+        let (status, response_body) = dav_respond(&state, &method, &path, &depth, body);
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            status,
+            response_body.len()
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.write_all(&response_body).await?;
+        stream.shutdown().await
+    }
+
+    fn dav_respond(state: &Mutex<DavState>, method: &str, path: &str, depth: &str, body: Vec<u8>) -> (&'static str, Vec<u8>) {
+        let mut state = state.lock().unwrap();
+        let key = path.trim_end_matches('/').to_string();
+        let parent = key.rsplit_once('/').map(|(p, _)| p.to_string()).unwrap_or_default();
+
+        match method {
+            "MKCOL" if state.collections.contains(&key) => ("405 Method Not Allowed", Vec::new()),
+            "MKCOL" if !state.collections.contains(&parent) => ("409 Conflict", Vec::new()),
+            "MKCOL" => {
+                state.collections.insert(key);
+                ("201 Created", Vec::new())
+            }
+            "PUT" if !state.collections.contains(&parent) => ("409 Conflict", Vec::new()),
+            "PUT" => {
+                state.files.insert(key, body);
+                ("201 Created", Vec::new())
+            }
+            "HEAD" => match state.files.contains_key(&key) {
+                true => ("200 OK", Vec::new()),
+                false => ("404 Not Found", Vec::new()),
+            },
+            "GET" => match state.files.get(&key) {
+                Some(data) => ("200 OK", data.clone()),
+                None => ("404 Not Found", Vec::new()),
+            },
+            "DELETE" => match state.files.remove(&key) {
+                Some(_) => ("204 No Content", Vec::new()),
+                None => ("404 Not Found", Vec::new()),
+            },
+            "PROPFIND" if !state.collections.contains(&key) => ("404 Not Found", Vec::new()),
+            "PROPFIND" => {
+                let mut xml = String::from(r#"<?xml version="1.0"?><d:multistatus xmlns:d="DAV:">"#);
+                let mut entries = vec![key.clone()];
+                if depth != "0" {
+                    entries.extend(state.collections.iter().filter(|c| c.rsplit_once('/').map(|(p, _)| p) == Some(key.as_str())).cloned());
+                }
+                for entry in entries {
+                    xml.push_str(&format!(
+                        "<d:response><d:href>{}/</d:href><d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat></d:response>",
+                        entry.replace(' ', "%20")
+                    ));
+                }
+                for file in state.files.keys().filter(|f| depth != "0" && f.rsplit_once('/').map(|(p, _)| p) == Some(key.as_str())) {
+                    xml.push_str(&format!(
+                        "<d:response><d:href>{}</d:href><d:propstat><d:prop><d:resourcetype/></d:prop></d:propstat></d:response>",
+                        file
+                    ));
+                }
+                xml.push_str("</d:multistatus>");
+                ("207 Multi-Status", xml.into_bytes())
+            }
+            _ => ("405 Method Not Allowed", Vec::new()),
+        }
+    }
+
+    fn test_version(version_id: &str, data: &[u8]) -> FileVersion {
+        FileVersion {
+            version_id: version_id.to_string(),
+            timestamp: chrono::Utc::now(),
+            size: data.len() as u64,
+            hash: crate::versioning::calculate_hash(data),
+            content_hash: None,
+            storage_metadata: HashMap::new(),
+            description: None,
+            is_pinned: false,
+        }
+    }
+
+    fn provider_for(url: &str) -> WebDavStorageProvider {
+        WebDavStorageProvider::new(url.to_string(), "deck".to_string(), Some("secret".to_string()), StorageConfig::default()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_webdav_file_roundtrip() {
+        let (url, state) = spawn_dav_server().await;
+        let provider = provider_for(&url);
+        assert!(provider.health_check().await.unwrap());
+
+        let data = b"save slot 1";
+        let version = test_version("20240101_000000_000000000_abcdef12", data);
+        let result = provider.upload_file("Hollow Knight", "profile 1/user1.dat", &version, data).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert!(state.lock().unwrap().files.contains_key(
+            "/dav/games/Hollow Knight/files/profile 1/user1.dat/versions/20240101_000000_000000000_abcdef12"
+        ));
+
+        let downloaded = provider.download_file("Hollow Knight", "profile 1/user1.dat", &version).await.unwrap();
+        assert_eq!(downloaded, data);
+
+        let deleted = provider.delete_version("Hollow Knight", "profile 1/user1.dat", &version).await.unwrap();
+        assert!(deleted.success);
+        assert!(provider.download_file("Hollow Knight", "profile 1/user1.dat", &version).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_webdav_manifest_and_list_games() {
+        let (url, _state) = spawn_dav_server().await;
+        let provider = provider_for(&url);
+
+        assert!(provider.list_games().await.unwrap().is_empty());
+        assert!(provider.download_manifest("Celeste").await.unwrap().is_none());
+
+        for game in ["Celeste", "Hades II"] {
+            let manifest = GameVersionManifest {
+                game_name: game.to_string(),
+                manifest_version: 1,
+                last_updated: chrono::Utc::now(),
+                files: HashMap::new(),
+                metadata: HashMap::new(),
+            };
+            assert!(provider.upload_manifest(game, &manifest).await.unwrap().success);
+        }
+
+        let manifest = provider.download_manifest("Hades II").await.unwrap().unwrap();
+        assert_eq!(manifest.game_name, "Hades II");
+
+        let mut games = provider.list_games().await.unwrap();
+        games.sort();
+        assert_eq!(games, vec!["Celeste".to_string(), "Hades II".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_webdav_content_addressed_layout() {
+        let (url, state) = spawn_dav_server().await;
+        let config = StorageConfig { layout: StorageLayout::ContentAddressed, ..StorageConfig::default() };
+        let provider = WebDavStorageProvider::new(url, "deck".to_string(), None, config).unwrap();
+
+        let data = b"save slot 1";
+        let hash = calculate_hash(data);
+        let mut version = test_version("v1", data);
+        for (game, id) in [("Celeste", "v1"), ("Hades", "v2")] {
+            version.version_id = id.to_string();
+            let result = provider.upload_file(game, "save.dat", &version, data).await.unwrap();
+            assert!(result.success, "{:?}", result.error);
+            assert_eq!(result.metadata.get(BLOB_METADATA_KEY), Some(&hash));
+        }
+        assert_eq!(state.lock().unwrap().files.len(), 1);
+        assert_eq!(provider.list_blobs().await.unwrap(), vec![hash.clone()]);
+
+        version.storage_metadata.insert(BLOB_METADATA_KEY.to_string(), hash.clone());
+        assert_eq!(provider.download_file("Celeste", "save.dat", &version).await.unwrap(), data);
+        assert!(provider.delete_blob(&hash).await.unwrap().success);
+        assert!(provider.list_blobs().await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_child_collections_nextcloud_style() {
+        let parent = Url::parse("https://cloud.example.com/remote.php/dav/files/deck/DeckSaves/games/").unwrap();
+        let body = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:" xmlns:oc="http://owncloud.org/ns">
+  <d:response>
+    <d:href>/remote.php/dav/files/deck/DeckSaves/games/</d:href>
+    <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>https://cloud.example.com/remote.php/dav/files/deck/DeckSaves/games/Baldur%27s%20Gate%203/</d:href>
+    <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/remote.php/dav/files/deck/DeckSaves/games/notes.txt</d:href>
+    <d:propstat><d:prop><d:resourcetype/></d:prop></d:propstat>
+  </d:response>
+</d:multistatus>"#;
+
+        let names = WebDavStorageProvider::parse_children(body, &parent, true);
+        assert_eq!(names, vec!["Baldur's Gate 3".to_string()]);
+        let files = WebDavStorageProvider::parse_children(body, &parent, false);
+        assert_eq!(files, vec!["notes.txt".to_string()]);
+    }
+}
@@ -0,0 +1,472 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::SystemTime;
+use tracing::{debug, warn};
+
+use crate::SyncAction;
+
+/// What to do automatically when a save changed on both sides
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    KeepNewest,
+    KeepLocal,
+    KeepRemote,
+    /// Keep the newest copy in place and a renamed copy of the other
+    KeepBoth,
+    /// Leave both copies untouched until the player resolves the conflict
+    #[default]
+    Ask,
+}
+
+impl ConflictPolicy {
+    /// Resolution to apply without asking, if any
+    pub fn resolution(&self) -> Option<ConflictResolution> {
+        match self {
+            ConflictPolicy::KeepNewest => Some(ConflictResolution::KeepNewest),
+            ConflictPolicy::KeepLocal => Some(ConflictResolution::KeepLocal),
+            ConflictPolicy::KeepRemote => Some(ConflictResolution::KeepRemote),
+            ConflictPolicy::KeepBoth => Some(ConflictResolution::KeepBoth),
+            ConflictPolicy::Ask => None,
+        }
+    }
+}
+
+impl std::str::FromStr for ConflictPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "ask" => Ok(ConflictPolicy::Ask),
+            other => other.parse::<ConflictResolution>().map(|resolution| match resolution {
+                ConflictResolution::KeepNewest => ConflictPolicy::KeepNewest,
+                ConflictResolution::KeepLocal => ConflictPolicy::KeepLocal,
+                ConflictResolution::KeepRemote => ConflictPolicy::KeepRemote,
+                ConflictResolution::KeepBoth => ConflictPolicy::KeepBoth,
+            }),
+        }
+    }
+}
+
+/// How a single conflict is settled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictResolution {
+    KeepNewest,
+    KeepLocal,
+    KeepRemote,
+    KeepBoth,
+}
+
+impl std::str::FromStr for ConflictResolution {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "keep_newest" | "newest" => Ok(ConflictResolution::KeepNewest),
+            "keep_local" | "local" => Ok(ConflictResolution::KeepLocal),
+            "keep_remote" | "remote" => Ok(ConflictResolution::KeepRemote),
+            "keep_both" | "both" => Ok(ConflictResolution::KeepBoth),
+            other => Err(anyhow::anyhow!(
+                "Unknown conflict resolution '{}' (expected newest, local, remote or both)", other
+            )),
+        }
+    }
+}
+
+/// Name for the losing copy kept by `ConflictResolution::KeepBoth`,
+/// e.g. `save.conflict-20240101-120000.dat`
+pub fn conflict_copy_path(file_path: &Path, timestamp: DateTime<Utc>) -> PathBuf {
+    let stem = file_path.file_stem().and_then(|s| s.to_str()).unwrap_or("save");
+    let suffix = timestamp.format("%Y%m%d-%H%M%S");
+    let name = match file_path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}.conflict-{}.{}", stem, suffix, ext),
+        None => format!("{}.conflict-{}", stem, suffix),
+    };
+    file_path.with_file_name(name)
+}
+
+/// Whether a file is a copy named by `conflict_copy_path`. Such copies stay on
+/// the device that resolved the conflict instead of being synced as saves.
+pub fn is_conflict_copy(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    name.match_indices(".conflict-").any(|(i, marker)| {
+        let rest = &name.as_bytes()[i + marker.len()..];
+        let is_timestamp = rest.len() >= 15 && rest[..15].iter().enumerate()
+            .all(|(i, &b)| if i == 8 { b == b'-' } else { b.is_ascii_digit() });
+        is_timestamp && matches!(rest.get(15), None | Some(b'.'))
+    })
+}
+
+/// What a file looked like the last time this device synced it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncedFile {
+    /// SHA-256 of the content both sides agreed on
+    pub hash: String,
+    /// Remote ETag at that point, used when the remote carries no content hash
+    pub remote_etag: Option<String>,
+    pub synced_at: DateTime<Utc>,
+}
+
+/// A file changed on both this device and the remote since the last sync
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncConflict {
+    pub game_name: String,
+    pub file_path: String,
+    pub local_hash: String,
+    pub remote_hash: String,
+    /// Last synced hash; `None` when both sides already existed on first sync
+    pub base_hash: Option<String>,
+    pub detected_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct SyncState {
+    #[serde(default)]
+    files: HashMap<String, SyncedFile>,
+    #[serde(default)]
+    conflicts: HashMap<String, SyncConflict>,
+}
+
+/// In-memory copy of the state file and the version of the file it was read from
+#[derive(Debug, Default)]
+struct Cached {
+    state: SyncState,
+    stamp: Option<(SystemTime, u64)>,
+}
+
+/// Per-device sync base, persisted as JSON next to the other local data.
+/// Never uploaded: each device tracks its own view of the last sync.
+///
+/// The daemon, the CLI and the UI may run at once, so every write re-reads
+/// the file under an exclusive lock and applies its change to what is there,
+/// and reads pick up changes other processes wrote.
+pub struct SyncStateStore {
+    path: PathBuf,
+    cached: Mutex<Cached>,
+}
+
+impl SyncStateStore {
+    /// Load the state file from the default per-device location
+    pub fn load_default() -> Result<Self> {
+        let project_dirs = ProjectDirs::from("com", "decksaves", "game-sync")
+            .context("Failed to get project directories")?;
+        Self::load(project_dirs.data_local_dir().join("sync_state.json"))
+    }
+
+    /// The store for the default location, shared by everything in this process
+    pub fn shared_default() -> Result<Arc<Self>> {
+        static SHARED: OnceLock<Arc<SyncStateStore>> = OnceLock::new();
+        if let Some(store) = SHARED.get() {
+            return Ok(store.clone());
+        }
+        let store = Arc::new(Self::load_default()?);
+        Ok(SHARED.get_or_init(|| store).clone())
+    }
+
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let cached = Self::read(&path)?;
+        Ok(Self { path, cached: Mutex::new(cached) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn read(path: &Path) -> Result<Cached> {
+        let stamp = file_stamp(path);
+        if stamp.is_none() {
+            return Ok(Cached::default());
+        }
+
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read sync state {}", path.display()))?;
+        let state = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse sync state {}", path.display()))?;
+        Ok(Cached { state, stamp })
+    }
+
+    /// The cached state, re-read first if another process changed the file
+    fn current(&self) -> Option<MutexGuard<'_, Cached>> {
+        let mut cached = self.cached.lock().ok()?;
+        if file_stamp(&self.path) != cached.stamp {
+            match Self::read(&self.path) {
+                Ok(fresh) => *cached = fresh,
+                Err(e) => warn!("Keeping cached sync state: {:#}", e),
+            }
+        }
+        Some(cached)
+    }
+
+    fn key(game_name: &str, file_path: &str) -> String {
+        format!("{}/{}", game_name, file_path)
+    }
+
+    pub fn base(&self, game_name: &str, file_path: &str) -> Option<SyncedFile> {
+        let cached = self.current()?;
+        cached.state.files.get(&Self::key(game_name, file_path)).cloned()
+    }
+
+    /// Local paths of every file of a game with a recorded sync base
+    pub fn tracked_files(&self, game_name: &str) -> Vec<String> {
+        let Some(cached) = self.current() else {
+            return Vec::new();
+        };
+        let prefix = Self::key(game_name, "");
+        cached.state.files.keys()
+            .filter_map(|key| key.strip_prefix(&prefix))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Record a successful sync and clear any conflict it resolved
+    pub fn record_synced(&self, game_name: &str, file_path: &str, hash: &str, remote_etag: Option<String>) -> Result<()> {
+        self.update(|state| {
+            let key = Self::key(game_name, file_path);
+            state.conflicts.remove(&key);
+            state.files.insert(key, SyncedFile {
+                hash: hash.to_string(),
+                remote_etag,
+                synced_at: Utc::now(),
+            });
+        })
+    }
+
+    pub fn record_conflict(&self, conflict: SyncConflict) -> Result<()> {
+        self.update(|state| {
+            state.conflicts.insert(Self::key(&conflict.game_name, &conflict.file_path), conflict);
+        })
+    }
+
+    /// Drop the record of a file deleted on both sides
+    pub fn forget(&self, game_name: &str, file_path: &str) -> Result<()> {
+        let key = Self::key(game_name, file_path);
+        let tracked = self.current()
+            .map(|cached| cached.state.files.contains_key(&key) || cached.state.conflicts.contains_key(&key))
+            .unwrap_or(false);
+        if !tracked {
+            return Ok(());
+        }
+
+        self.update(|state| {
+            state.files.remove(&key);
+            state.conflicts.remove(&key);
+        })
+    }
+
+    pub fn conflict(&self, game_name: &str, file_path: &str) -> Option<SyncConflict> {
+        let cached = self.current()?;
+        cached.state.conflicts.get(&Self::key(game_name, file_path)).cloned()
+    }
+
+    pub fn conflicts(&self) -> Vec<SyncConflict> {
+        let Some(cached) = self.current() else {
+            return Vec::new();
+        };
+        let mut conflicts: Vec<_> = cached.state.conflicts.values().cloned().collect();
+        conflicts.sort_by(|a, b| (&a.game_name, &a.file_path).cmp(&(&b.game_name, &b.file_path)));
+        conflicts
+    }
+
+    /// Apply a change to the latest state on disk and save it, holding an
+    /// exclusive lock so changes from other processes are kept
+    fn update(&self, change: impl FnOnce(&mut SyncState)) -> Result<()> {
+        let mut cached = self.cached.lock().map_err(|_| anyhow::anyhow!("Sync state lock poisoned"))?;
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .context("Failed to create sync state directory")?;
+        }
+        let lock_file = std::fs::File::create(self.path.with_extension("json.lock"))
+            .context("Failed to open sync state lock")?;
+        lock_file.lock().context("Failed to lock sync state")?;
+
+        let mut latest = Self::read(&self.path)?;
+        change(&mut latest.state);
+
+        // Write then rename so a crash never leaves a truncated state file
+        let tmp_path = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec_pretty(&latest.state)?)
+            .context("Failed to write sync state")?;
+        std::fs::rename(&tmp_path, &self.path)
+            .context("Failed to replace sync state")?;
+
+        latest.stamp = file_stamp(&self.path);
+        *cached = latest;
+        debug!("Saved sync state to {}", self.path.display());
+        Ok(())
+    }
+}
+
+/// Modification time and size of the state file, `None` when it does not exist
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Three-way comparison of local and remote content against the last synced base.
+///
+/// `None` hashes mean the file does not exist on that side. A file missing on
+/// one side but unchanged on the other since the last sync was deleted
+/// deliberately, so the deletion is propagated instead of the file resurrected.
+pub fn decide_sync_action(local_hash: Option<&str>, remote_hash: Option<&str>, base: Option<&SyncedFile>) -> SyncAction {
+    let base_hash = base.map(|b| b.hash.as_str());
+
+    match (local_hash, remote_hash) {
+        (None, None) => SyncAction::NoAction,
+        (Some(local), None) if base_hash == Some(local) => SyncAction::DeleteLocal,
+        (Some(_), None) => SyncAction::UploadToS3,
+        (None, Some(remote)) if base_hash == Some(remote) => SyncAction::DeleteRemote,
+        (None, Some(_)) => SyncAction::DownloadFromS3,
+        (Some(local), Some(remote)) if local == remote => SyncAction::NoAction,
+        (Some(local), Some(remote)) => {
+            let local_changed = base_hash != Some(local);
+            let remote_changed = base_hash != Some(remote);
+
+            match (local_changed, remote_changed) {
+                (true, false) => SyncAction::UploadToS3,
+                (false, true) => SyncAction::DownloadFromS3,
+                _ => SyncAction::Conflict {
+                    local_hash: local.to_string(),
+                    remote_hash: remote.to_string(),
+                    base_hash: base_hash.map(str::to_string),
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base(hash: &str) -> SyncedFile {
+        SyncedFile { hash: hash.to_string(), remote_etag: None, synced_at: Utc::now() }
+    }
+
+    #[test]
+    fn test_three_way_decisions() {
+        let b = base("aaa");
+        assert_eq!(decide_sync_action(Some("aaa"), Some("aaa"), Some(&b)), SyncAction::NoAction);
+        assert_eq!(decide_sync_action(Some("bbb"), Some("aaa"), Some(&b)), SyncAction::UploadToS3);
+        assert_eq!(decide_sync_action(Some("aaa"), Some("ccc"), Some(&b)), SyncAction::DownloadFromS3);
+        // Both sides converged on the same content
+        assert_eq!(decide_sync_action(Some("ddd"), Some("ddd"), Some(&b)), SyncAction::NoAction);
+
+        assert_eq!(
+            decide_sync_action(Some("bbb"), Some("ccc"), Some(&b)),
+            SyncAction::Conflict {
+                local_hash: "bbb".to_string(),
+                remote_hash: "ccc".to_string(),
+                base_hash: Some("aaa".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_first_sync_with_differing_sides_is_a_conflict() {
+        assert!(matches!(decide_sync_action(Some("bbb"), Some("ccc"), None), SyncAction::Conflict { base_hash: None, .. }));
+        assert_eq!(decide_sync_action(Some("bbb"), Some("bbb"), None), SyncAction::NoAction);
+    }
+
+    #[test]
+    fn test_deletions_propagate_without_resurrecting() {
+        let b = base("aaa");
+        // New on one side
+        assert_eq!(decide_sync_action(Some("aaa"), None, None), SyncAction::UploadToS3);
+        assert_eq!(decide_sync_action(None, Some("aaa"), None), SyncAction::DownloadFromS3);
+        // Deleted on one side, untouched on the other
+        assert_eq!(decide_sync_action(Some("aaa"), None, Some(&b)), SyncAction::DeleteLocal);
+        assert_eq!(decide_sync_action(None, Some("aaa"), Some(&b)), SyncAction::DeleteRemote);
+        // Deleted on one side, edited on the other: the edit wins
+        assert_eq!(decide_sync_action(Some("bbb"), None, Some(&b)), SyncAction::UploadToS3);
+        assert_eq!(decide_sync_action(None, Some("ccc"), Some(&b)), SyncAction::DownloadFromS3);
+        assert_eq!(decide_sync_action(None, None, Some(&b)), SyncAction::NoAction);
+    }
+
+    #[test]
+    fn test_policy_parsing_and_conflict_copy_name() {
+        assert_eq!("keep-both".parse::<ConflictPolicy>().unwrap(), ConflictPolicy::KeepBoth);
+        assert_eq!("ask".parse::<ConflictPolicy>().unwrap(), ConflictPolicy::Ask);
+        assert_eq!("remote".parse::<ConflictResolution>().unwrap(), ConflictResolution::KeepRemote);
+        assert!("ask".parse::<ConflictResolution>().is_err());
+        assert_eq!(ConflictPolicy::default().resolution(), None);
+
+        let timestamp = DateTime::parse_from_rfc3339("2024-01-02T03:04:05Z").unwrap().with_timezone(&Utc);
+        assert_eq!(
+            conflict_copy_path(Path::new("/saves/slot1.sav"), timestamp),
+            PathBuf::from("/saves/slot1.conflict-20240102-030405.sav")
+        );
+        assert_eq!(
+            conflict_copy_path(Path::new("/saves/profile"), timestamp),
+            PathBuf::from("/saves/profile.conflict-20240102-030405")
+        );
+
+        assert!(is_conflict_copy(&conflict_copy_path(Path::new("/saves/slot1.sav"), timestamp)));
+        assert!(is_conflict_copy(&conflict_copy_path(Path::new("/saves/profile"), timestamp)));
+        assert!(!is_conflict_copy(Path::new("/saves/slot1.sav")));
+        assert!(!is_conflict_copy(Path::new("/saves/game.conflict-resolved.sav")));
+    }
+
+    #[test]
+    fn test_state_persists_and_sync_clears_conflict() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("sync_state.json");
+
+        let store = SyncStateStore::load(path.clone()).unwrap();
+        store.record_synced("Celeste", "/saves/0.celeste", "aaa", Some("etag1".to_string())).unwrap();
+        store.record_conflict(SyncConflict {
+            game_name: "Celeste".to_string(),
+            file_path: "/saves/1.celeste".to_string(),
+            local_hash: "bbb".to_string(),
+            remote_hash: "ccc".to_string(),
+            base_hash: None,
+            detected_at: Utc::now(),
+        }).unwrap();
+
+        let reloaded = SyncStateStore::load(path.clone()).unwrap();
+        assert_eq!(reloaded.base("Celeste", "/saves/0.celeste").unwrap().remote_etag.as_deref(), Some("etag1"));
+        assert_eq!(reloaded.conflicts().len(), 1);
+
+        reloaded.record_synced("Celeste", "/saves/1.celeste", "ccc", None).unwrap();
+        assert!(reloaded.conflicts().is_empty());
+    }
+
+    #[test]
+    fn test_stores_in_separate_processes_keep_each_others_changes() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("sync_state.json");
+
+        // As if the daemon and the CLI each loaded the file before either wrote
+        let daemon = SyncStateStore::load(path.clone()).unwrap();
+        let cli = SyncStateStore::load(path.clone()).unwrap();
+
+        daemon.record_synced("Celeste", "/saves/0.celeste", "aaa", None).unwrap();
+        cli.record_synced("Hades", "/saves/Profile1.sav", "bbb", None).unwrap();
+        daemon.record_conflict(SyncConflict {
+            game_name: "Celeste".to_string(),
+            file_path: "/saves/1.celeste".to_string(),
+            local_hash: "ccc".to_string(),
+            remote_hash: "ddd".to_string(),
+            base_hash: None,
+            detected_at: Utc::now(),
+        }).unwrap();
+
+        // Reads pick up the other store's writes without a reload
+        assert_eq!(cli.conflicts().len(), 1);
+        assert_eq!(daemon.base("Hades", "/saves/Profile1.sav").unwrap().hash, "bbb");
+        cli.record_synced("Celeste", "/saves/1.celeste", "ddd", None).unwrap();
+        assert!(daemon.conflicts().is_empty());
+
+        let reloaded = SyncStateStore::load(path).unwrap();
+        assert_eq!(reloaded.tracked_files("Celeste").len(), 2);
+        assert_eq!(reloaded.tracked_files("Hades"), ["/saves/Profile1.sav"]);
+    }
+}
@@ -1,10 +1,11 @@
 use anyhow::{Result, Context};
 use std::path::Path;
 use std::collections::HashMap;
-use tracing::{info, warn, error};
+use tracing::{debug, info, warn, error};
 
-use crate::versioning::{VersionManager, VersionConfig, FileVersion};
+use crate::versioning::{AddedVersion, VersionManager, VersionConfig, FileVersion};
 use crate::storage::{StorageProvider, StorageFactory, StorageConfig};
+use crate::storage::blobs::{count_references, is_collectable, version_blobs};
 
 /// Enhanced sync manager with versioning support
 pub struct VersionedSync {
@@ -43,23 +44,68 @@ impl VersionedSync {
         })
     }
 
-    /// Sync a file to storage, creating a new version
+    /// Sync a file to storage, creating a new version unless its content
+    /// matches the current one
     pub async fn sync_file_to_storage<P: AsRef<Path>>(
         &mut self,
         local_file_path: P,
         relative_path: &str,
         description: Option<String>,
-    ) -> Result<FileVersion> {
+    ) -> Result<AddedVersion> {
+        self.sync_snapshot_to_storage(local_file_path, relative_path, None, description).await
+    }
+
+    /// Sync a file whose logical content is identified by `content_hash`, such
+    /// as a directory snapshot archive, so a re-archived but unchanged
+    /// directory does not create a new version
+    pub async fn sync_snapshot_to_storage<P: AsRef<Path>>(
+        &mut self,
+        local_file_path: P,
+        relative_path: &str,
+        content_hash: Option<String>,
+        description: Option<String>,
+    ) -> Result<AddedVersion> {
         let local_path = local_file_path.as_ref();
+        let blobs_before = count_references([self.version_manager.get_manifest()]);
         
         // First create a version entry
-        let version = self.version_manager.add_version(
+        let added = self.version_manager.add_version_with_content_hash(
             relative_path,
             local_path,
+            content_hash,
             HashMap::new(), // Will be filled by storage provider
             description,
         ).await?;
 
+        if added.unchanged {
+            info!("{} is unchanged since version {}, skipping upload", relative_path, added.version.version_id);
+            return Ok(added);
+        }
+
+        match self.upload_version(local_path, relative_path, &added.version).await {
+            Ok(version) => {
+                // Storing the version pruned old ones along with their blob references
+                let blobs_after = count_references([self.version_manager.get_manifest()]);
+                let dropped: Vec<String> = blobs_before.into_keys()
+                    .filter(|hash| !blobs_after.contains_key(hash))
+                    .collect();
+                self.release_blobs(&dropped).await;
+
+                Ok(AddedVersion { version, ..added })
+            }
+            Err(e) => {
+                // Forget the version so the next sync retries the upload
+                self.version_manager.discard_version(
+                    relative_path,
+                    &added.version.version_id,
+                    added.previous_version.as_deref(),
+                );
+                Err(e)
+            }
+        }
+    }
+
+    async fn upload_version(&mut self, local_path: &Path, relative_path: &str, version: &FileVersion) -> Result<FileVersion> {
         // Read file data
         let file_data = tokio::fs::read(local_path).await
             .context("Failed to read file for sync")?;
@@ -68,7 +114,7 @@ impl VersionedSync {
         let storage_result = self.storage_provider.upload_file(
             &self.game_name,
             relative_path,
-            &version,
+            version,
             &file_data,
         ).await?;
 
@@ -79,15 +125,18 @@ impl VersionedSync {
             ));
         }
 
-        // Update version with storage metadata
-        let manifest = self.version_manager.get_manifest();
-        if let Some(file_manifest) = manifest.files.get(relative_path) {
-            if let Some(updated_version) = file_manifest.versions.first() {
-                // Storage metadata is already in the version from upload_file
-                info!("Successfully synced {} to storage (version: {})", 
-                      relative_path, updated_version.version_id);
-            }
-        }
+        // Record backend metadata (object key, compression codec, ...) on the version
+        // so downloads know how the payload was stored
+        let version = self.version_manager.update_storage_metadata(
+            relative_path,
+            &version.version_id,
+            storage_result.metadata,
+        )?;
+        info!("Successfully synced {} to storage (version: {})", 
+              relative_path, version.version_id);
+
+        // Only prune old versions now that the new one is stored
+        self.version_manager.cleanup_old_versions(relative_path).await?;
 
         // Upload updated manifest
         let manifest_result = self.storage_provider.upload_manifest(
@@ -264,6 +313,10 @@ impl VersionedSync {
         // Save the updated manifest - get the manifest reference and upload it
         let manifest = self.version_manager.get_manifest();
         self.storage_provider.upload_manifest(&self.game_name, manifest).await?;
+
+        // Content-addressed versions share their blobs; drop them once nothing references them
+        let blobs: Vec<String> = version_blobs(&version).into_iter().map(String::from).collect();
+        self.release_blobs(&blobs).await;
         
         println!("DEBUG VersionedSync::delete_version: successfully deleted version '{}' for '{}'", version_id, relative_path);
         Ok(())
@@ -296,6 +349,97 @@ impl VersionedSync {
         Ok(())
     }
 
+    /// Count blob references across the manifests of every game in storage,
+    /// using the in-memory manifest for this game
+    async fn blob_references(&self) -> Result<HashMap<String, usize>> {
+        let mut manifests = vec![self.version_manager.get_manifest().clone()];
+        for game in self.storage_provider.list_games().await? {
+            if game == self.game_name {
+                continue;
+            }
+            if let Some(manifest) = self.storage_provider.download_manifest(&game).await
+                .with_context(|| format!("Failed to read manifest of {}", game))?
+            {
+                manifests.push(manifest);
+            }
+        }
+
+        Ok(count_references(&manifests))
+    }
+
+    /// Whether an unreferenced blob is past its grace period. Blobs uploaded or
+    /// reused recently may belong to a manifest another device is about to write.
+    async fn blob_collectable(&self, hash: &str) -> Result<bool> {
+        let last_modified = self.storage_provider.blob_last_modified(hash).await?;
+        Ok(last_modified.is_some_and(is_collectable))
+    }
+
+    /// Delete the given blobs that no version of any game references any more
+    /// and that are past their grace period; younger ones are left to `collect_garbage`.
+    /// Failures only leave garbage behind, so they are logged rather than returned.
+    async fn release_blobs(&self, hashes: &[String]) {
+        if hashes.is_empty() {
+            return;
+        }
+
+        let references = match self.blob_references().await {
+            Ok(references) => references,
+            Err(e) => {
+                warn!("Not releasing blobs, failed to count references: {}", e);
+                return;
+            }
+        };
+
+        for hash in hashes.iter().filter(|hash| !references.contains_key(*hash)) {
+            match self.blob_collectable(hash).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    debug!("Keeping unreferenced blob {} within its grace period", hash);
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Not releasing blob {}: {}", hash, e);
+                    continue;
+                }
+            }
+
+            match self.storage_provider.delete_blob(hash).await {
+                Ok(result) if result.success => info!("Deleted unreferenced blob {}", hash),
+                Ok(result) => warn!("Failed to delete blob {}: {}", hash, result.error.unwrap_or_default()),
+                Err(e) => warn!("Failed to delete blob {}: {}", hash, e),
+            }
+        }
+    }
+
+    /// Delete every stored blob that no version of any game references, such as
+    /// blobs of versions pruned on other devices, once it is past its grace
+    /// period. Returns the deleted blobs.
+    pub async fn collect_garbage(&self) -> Result<Vec<String>> {
+        let blobs = self.storage_provider.list_blobs().await?;
+        if blobs.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let references = self.blob_references().await?;
+        let mut deleted = Vec::new();
+        for hash in blobs.into_iter().filter(|hash| !references.contains_key(hash)) {
+            if !self.blob_collectable(&hash).await? {
+                debug!("Keeping unreferenced blob {} within its grace period", hash);
+                continue;
+            }
+
+            let result = self.storage_provider.delete_blob(&hash).await?;
+            if result.success {
+                deleted.push(hash);
+            } else {
+                warn!("Failed to delete blob {}: {}", hash, result.error.unwrap_or_default());
+            }
+        }
+
+        info!("Garbage collection deleted {} unreferenced blobs", deleted.len());
+        Ok(deleted)
+    }
+
     /// Cleanup old versions based on configuration
     pub async fn cleanup_old_versions(&mut self) -> Result<Vec<String>> {
         let mut cleaned_versions = Vec::new();
@@ -341,3 +485,109 @@ impl VersionedSync {
         Ok(cleaned_versions)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{StorageBackend, StorageLayout};
+    use crate::storage::blobs::{BLOB_GRACE_PERIOD, BLOB_METADATA_KEY};
+    use crate::versioning::AutoPinStrategy;
+
+    #[tokio::test]
+    async fn test_blobs_are_shared_and_released_with_their_last_version() {
+        let temp = tempfile::tempdir().unwrap();
+        let base = temp.path();
+
+        let storage_config = StorageConfig {
+            backend: StorageBackend::Local { base_path: base.join("storage").to_string_lossy().to_string() },
+            layout: StorageLayout::ContentAddressed,
+            ..StorageConfig::default()
+        };
+        let version_config = VersionConfig { auto_pin_strategy: AutoPinStrategy::None, ..VersionConfig::default() };
+        let mut sync = VersionedSync::new("default_game".to_string(), storage_config, version_config).await.unwrap();
+
+        let save = base.join("save.dat");
+        std::fs::write(&save, b"same bytes in two places").unwrap();
+        let first = sync.sync_file_to_storage(&save, "Celeste/0.celeste", None).await.unwrap().version;
+        let second = sync.sync_file_to_storage(&save, "Celeste/1.celeste", None).await.unwrap().version;
+        assert_eq!(version_blobs(&first), version_blobs(&second));
+        assert_eq!(sync.get_storage_provider().list_blobs().await.unwrap().len(), 1);
+
+        // Orphans left behind by other devices are only removed by a full
+        // collection, and only once they are past their grace period
+        let orphan = base.join("storage/blobs").join("0".repeat(64));
+        std::fs::write(&orphan, b"orphan").unwrap();
+        assert!(sync.collect_garbage().await.unwrap().is_empty());
+        age_blobs(&base.join("storage/blobs"));
+        assert_eq!(sync.collect_garbage().await.unwrap(), vec!["0".repeat(64)]);
+
+        sync.delete_version("Celeste/0.celeste", &first.version_id).await.unwrap();
+        assert_eq!(sync.get_storage_provider().list_blobs().await.unwrap().len(), 1);
+        let restored = base.join("restored.dat");
+        sync.download_version("Celeste/1.celeste", &second.version_id, &restored).await.unwrap();
+        assert_eq!(std::fs::read(&restored).unwrap(), b"same bytes in two places");
+
+        age_blobs(&base.join("storage/blobs"));
+        sync.delete_version("Celeste/1.celeste", &second.version_id).await.unwrap();
+        assert!(sync.get_storage_provider().list_blobs().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_empty_save_round_trips_in_chunked_layout() {
+        let temp = tempfile::tempdir().unwrap();
+        let base = temp.path();
+
+        let storage_config = StorageConfig {
+            backend: StorageBackend::Local { base_path: base.join("storage").to_string_lossy().to_string() },
+            layout: StorageLayout::Chunked,
+            ..StorageConfig::default()
+        };
+        let version_config = VersionConfig { auto_pin_strategy: AutoPinStrategy::None, ..VersionConfig::default() };
+        let mut sync = VersionedSync::new("default_game".to_string(), storage_config, version_config).await.unwrap();
+
+        let save = base.join("save.dat");
+        std::fs::write(&save, b"").unwrap();
+        let version = sync.sync_file_to_storage(&save, "Celeste/0.celeste", None).await.unwrap().version;
+        assert!(version_blobs(&version).is_empty());
+
+        let restored = base.join("restored.dat");
+        std::fs::write(&restored, b"stale").unwrap();
+        sync.download_version("Celeste/0.celeste", &version.version_id, &restored).await.unwrap();
+        assert_eq!(std::fs::read(&restored).unwrap(), b"");
+
+        sync.delete_version("Celeste/0.celeste", &version.version_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reused_blobs_outlive_a_concurrent_collection() {
+        let temp = tempfile::tempdir().unwrap();
+        let base = temp.path();
+
+        let storage_config = StorageConfig {
+            backend: StorageBackend::Local { base_path: base.join("storage").to_string_lossy().to_string() },
+            layout: StorageLayout::ContentAddressed,
+            ..StorageConfig::default()
+        };
+        let version_config = VersionConfig { auto_pin_strategy: AutoPinStrategy::None, ..VersionConfig::default() };
+        let sync = VersionedSync::new("default_game".to_string(), storage_config, version_config).await.unwrap();
+
+        // An old orphan is about to be reused by another device that has not
+        // written its manifest yet
+        let provider = sync.get_storage_provider();
+        let hash = provider.upload_blob(b"shared save").await.unwrap().metadata[BLOB_METADATA_KEY].clone();
+        age_blobs(&base.join("storage/blobs"));
+        provider.upload_blob(b"shared save").await.unwrap();
+
+        assert!(sync.collect_garbage().await.unwrap().is_empty());
+        assert_eq!(provider.list_blobs().await.unwrap(), vec![hash]);
+    }
+
+    /// Backdate every blob past the garbage collection grace period
+    fn age_blobs(dir: &Path) {
+        let old = std::time::SystemTime::now() - BLOB_GRACE_PERIOD * 2;
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let file = std::fs::File::options().write(true).open(entry.unwrap().path()).unwrap();
+            file.set_modified(old).unwrap();
+        }
+    }
+}
@@ -24,6 +24,10 @@ pub struct FileVersion {
     pub size: u64,
     /// SHA256 hash of the file content
     pub hash: FileHash,
+    /// Hash of the logical content when it differs from the stored bytes,
+    /// e.g. the files inside a directory snapshot rather than its archive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<FileHash>,
     /// Storage-specific metadata (e.g., S3 object key, Google Drive file ID)
     pub storage_metadata: HashMap<String, String>,
     /// Human-readable description (optional)
@@ -32,6 +36,24 @@ pub struct FileVersion {
     pub is_pinned: bool,
 }
 
+impl FileVersion {
+    /// Hash used to tell whether a new version would change anything
+    pub fn logical_hash(&self) -> &str {
+        self.content_hash.as_deref().unwrap_or(&self.hash)
+    }
+}
+
+/// Outcome of adding a version
+#[derive(Debug, Clone)]
+pub struct AddedVersion {
+    /// The new version, or the current one when nothing changed
+    pub version: FileVersion,
+    /// True when the content matched the current version and nothing was added
+    pub unchanged: bool,
+    /// The current version before this one was added, for `discard_version`
+    pub previous_version: Option<VersionId>,
+}
+
 /// Manifest containing all versions of a specific file
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileVersionManifest {
@@ -135,14 +157,27 @@ impl VersionManager {
         }
     }
 
-    /// Add a new version of a file
+    /// Add a new version of a file, unless its content matches the current version
     pub async fn add_version<P1: AsRef<str>, P2: AsRef<Path>>(
         &mut self,
         file_path: P1,
         local_path: P2,
         storage_metadata: HashMap<String, String>,
         description: Option<String>,
-    ) -> Result<FileVersion> {
+    ) -> Result<AddedVersion> {
+        self.add_version_with_content_hash(file_path, local_path, None, storage_metadata, description).await
+    }
+
+    /// Add a new version of a file whose logical content is identified by
+    /// `content_hash` rather than by its bytes, such as a directory snapshot
+    pub async fn add_version_with_content_hash<P1: AsRef<str>, P2: AsRef<Path>>(
+        &mut self,
+        file_path: P1,
+        local_path: P2,
+        content_hash: Option<FileHash>,
+        storage_metadata: HashMap<String, String>,
+        description: Option<String>,
+    ) -> Result<AddedVersion> {
         let file_path = file_path.as_ref().to_string();
         let local_path = local_path.as_ref();
 
@@ -152,6 +187,15 @@ impl VersionManager {
         
         let size = file_data.len() as u64;
         let hash = calculate_hash(&file_data);
+
+        if let Some(current) = self.get_current_version(&file_path) {
+            if current.logical_hash() == content_hash.as_deref().unwrap_or(&hash) {
+                debug!("Content of {} matches current version {}, not adding a version", file_path, current.version_id);
+                let previous_version = Some(current.version_id.clone());
+                return Ok(AddedVersion { version: current.clone(), unchanged: true, previous_version });
+            }
+        }
+
         let timestamp = Utc::now();
         
         // Generate unique version ID
@@ -165,6 +209,7 @@ impl VersionManager {
             timestamp,
             size,
             hash,
+            content_hash,
             storage_metadata,
             description,
             is_pinned,
@@ -180,18 +225,35 @@ impl VersionManager {
                 max_versions: Some(self.config.max_versions_per_file),
             });
 
-        // Insert version in chronological order (newest first)
+        // Insert version in chronological order (newest first). Old versions
+        // are only pruned once this one is stored, see `cleanup_old_versions`.
         file_manifest.versions.insert(0, version.clone());
-        file_manifest.current_version = Some(version_id.clone());
-
-        // Clean up old versions
-        self.cleanup_old_versions(&file_path).await?;
+        let previous_version = file_manifest.current_version.replace(version_id.clone());
 
         // Update manifest timestamp
         self.game_manifest.last_updated = Utc::now();
 
         info!("Added version {} for file {}", version_id, file_path);
-        Ok(version)
+        Ok(AddedVersion { version, unchanged: false, previous_version })
+    }
+
+    /// Drop a version that never made it to storage, pinned or not, so the
+    /// next sync does not mistake its content for already stored. The file's
+    /// current version goes back to `previous_version` from `AddedVersion`.
+    pub fn discard_version(&mut self, file_path: &str, version_id: &str, previous_version: Option<&str>) {
+        let Some(manifest) = self.game_manifest.files.get_mut(file_path) else {
+            return;
+        };
+
+        manifest.versions.retain(|v| v.version_id != version_id);
+        if manifest.current_version.as_deref() == Some(version_id) {
+            manifest.current_version = previous_version
+                .filter(|previous| manifest.versions.iter().any(|v| v.version_id == *previous))
+                .map(str::to_string);
+        }
+        if manifest.versions.is_empty() {
+            self.game_manifest.files.remove(file_path);
+        }
     }
 
     /// Get the version configuration
@@ -240,6 +302,24 @@ impl VersionManager {
         Ok(())
     }
 
+    /// Merge backend-reported storage metadata (object keys, codecs, ...) into a version
+    pub fn update_storage_metadata(
+        &mut self,
+        file_path: &str,
+        version_id: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<FileVersion> {
+        let manifest = self.game_manifest.files.get_mut(file_path)
+            .context("File not found in manifest")?;
+
+        let version = manifest.versions.iter_mut()
+            .find(|v| v.version_id == version_id)
+            .context("Version not found")?;
+
+        version.storage_metadata.extend(metadata);
+        Ok(version.clone())
+    }
+
     /// Remove a specific version (if not pinned)
     pub fn remove_version(&mut self, file_path: &str, version_id: &VersionId) -> Result<FileVersion> {
         let manifest = self.game_manifest.files.get_mut(file_path)
@@ -276,8 +356,9 @@ impl VersionManager {
         &self.game_manifest
     }
 
-    /// Clean up old versions based on config
-    async fn cleanup_old_versions(&mut self, file_path: &str) -> Result<()> {
+    /// Prune a file's versions by age and count. Call this once a newly added
+    /// version is stored, so a failed upload never costs an older version.
+    pub async fn cleanup_old_versions(&mut self, file_path: &str) -> Result<()> {
         let manifest = self.game_manifest.files.get_mut(file_path)
             .context("File manifest not found")?;
 
@@ -412,7 +493,7 @@ mod tests {
             &temp_file_path,
             metadata,
             Some("Test version".to_string()),
-        ).await.unwrap();
+        ).await.unwrap().version;
 
         assert_eq!(vm.get_file_versions("save.dat").unwrap().len(), 1);
         assert_eq!(vm.get_current_version("save.dat").unwrap().version_id, version.version_id);
@@ -421,6 +502,60 @@ mod tests {
         let _ = tokio::fs::remove_file(&temp_file_path).await;
     }
 
+    #[tokio::test]
+    async fn test_unchanged_content_reuses_current_version() {
+        let mut vm = VersionManager::new("test_game".to_string(), VersionConfig::default());
+        let temp = tempfile::tempdir().unwrap();
+        let temp_file_path = temp.path().join("save.dat");
+
+        tokio::fs::write(&temp_file_path, b"slot one").await.unwrap();
+        let first = vm.add_version("save.dat", &temp_file_path, HashMap::new(), None).await.unwrap();
+        assert!(!first.unchanged);
+
+        let again = vm.add_version("save.dat", &temp_file_path, HashMap::new(), None).await.unwrap();
+        assert!(again.unchanged);
+        assert_eq!(again.version.version_id, first.version.version_id);
+        assert_eq!(vm.get_file_versions("save.dat").unwrap().len(), 1);
+
+        tokio::fs::write(&temp_file_path, b"slot two").await.unwrap();
+        let changed = vm.add_version("save.dat", &temp_file_path, HashMap::new(), None).await.unwrap();
+        assert!(!changed.unchanged);
+        assert_eq!(vm.get_file_versions("save.dat").unwrap().len(), 2);
+
+        // Snapshots compare their logical content, whatever the archive bytes
+        tokio::fs::write(&temp_file_path, b"archive A").await.unwrap();
+        let snapshot = vm.add_version_with_content_hash("game", &temp_file_path, Some("tree".to_string()), HashMap::new(), None).await.unwrap();
+        assert!(!snapshot.unchanged);
+        tokio::fs::write(&temp_file_path, b"archive B").await.unwrap();
+        let same_tree = vm.add_version_with_content_hash("game", &temp_file_path, Some("tree".to_string()), HashMap::new(), None).await.unwrap();
+        assert!(same_tree.unchanged);
+        assert_eq!(same_tree.version.hash, snapshot.version.hash);
+
+        // A version that failed to upload does not count as stored
+        vm.discard_version("game", &snapshot.version.version_id, snapshot.previous_version.as_deref());
+        assert!(vm.get_current_version("game").is_none());
+
+        // Discarding hands "current" back to the version it replaced, even
+        // when that is not the newest one left
+        let restored = vm.get_file_versions("save.dat").unwrap().last().unwrap().version_id.clone();
+        vm.game_manifest.files.get_mut("save.dat").unwrap().current_version = Some(restored.clone());
+        tokio::fs::write(&temp_file_path, b"slot three").await.unwrap();
+        let failed = vm.add_version("save.dat", &temp_file_path, HashMap::new(), None).await.unwrap();
+        assert_eq!(failed.previous_version.as_deref(), Some(restored.as_str()));
+        vm.discard_version("save.dat", &failed.version.version_id, failed.previous_version.as_deref());
+        assert_eq!(vm.get_current_version("save.dat").unwrap().version_id, restored);
+        assert_eq!(vm.get_file_versions("save.dat").unwrap().len(), 2);
+
+        // Old versions are pruned only when asked to, once a new one is stored
+        let mut vm = VersionManager::new("test_game".to_string(), VersionConfig { max_versions_per_file: 1, auto_pin_strategy: AutoPinStrategy::None, ..VersionConfig::default() });
+        vm.add_version("save.dat", &temp_file_path, HashMap::new(), None).await.unwrap();
+        tokio::fs::write(&temp_file_path, b"slot four").await.unwrap();
+        vm.add_version("save.dat", &temp_file_path, HashMap::new(), None).await.unwrap();
+        assert_eq!(vm.get_file_versions("save.dat").unwrap().len(), 2);
+        vm.cleanup_old_versions("save.dat").await.unwrap();
+        assert_eq!(vm.get_file_versions("save.dat").unwrap().len(), 1);
+    }
+
     #[test]
     fn test_hash_calculation() {
         let data = b"test content";
@@ -1,17 +1,66 @@
 use crate::{FileWatcher, GameSaveSync, process_file_events};
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use tokio::task::JoinHandle;
 use tracing::{info, error};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// How a configured save path is being watched
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum WatchState {
+    /// Changes are noticed, by polling or by the OS watcher
+    Watching { polling: bool },
+    /// The path does not exist yet, e.g. before the game's first launch or
+    /// while an SD card is not mounted. `ancestor` is watched for it to appear.
+    Waiting { ancestor: PathBuf },
+    Failed { error: String },
+}
+
+/// Watch state of each configured save path of a game, shared between the
+/// watcher task that updates it and whoever reports it
+#[derive(Debug, Clone, Default)]
+pub struct WatchStates(Arc<Mutex<BTreeMap<String, WatchState>>>);
+
+impl WatchStates {
+    pub fn get(&self, path: &str) -> Option<WatchState> {
+        self.0.lock().ok()?.get(path).cloned()
+    }
+
+    pub fn set(&self, path: &str, state: WatchState) {
+        if let Ok(mut states) = self.0.lock() {
+            states.insert(path.to_string(), state);
+        }
+    }
+
+    /// Record that watched paths are now polled
+    pub fn mark_polling(&self) {
+        if let Ok(mut states) = self.0.lock() {
+            for state in states.values_mut() {
+                if let WatchState::Watching { polling } = state {
+                    *polling = true;
+                }
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, WatchState> {
+        self.0.lock().map(|states| states.clone()).unwrap_or_default()
+    }
+}
 
 pub struct WatcherManager {
     watchers: HashMap<String, JoinHandle<()>>,
+    states: HashMap<String, WatchStates>,
 }
 
 impl WatcherManager {
     pub fn new() -> Self {
         Self {
             watchers: HashMap::new(),
+            states: HashMap::new(),
         }
     }
 
@@ -24,7 +73,7 @@ impl WatcherManager {
         // Stop existing watcher if any
         self.stop_watching_game(&game_name).await;
 
-        let (mut file_watcher, event_rx) = FileWatcher::new()?;
+        let (mut file_watcher, event_rx) = FileWatcher::with_settings(sync_handler.watch_settings(&game_name))?;
 
         // Start watching all paths for this game
         for path in &paths {
@@ -33,10 +82,13 @@ impl WatcherManager {
             }
         }
 
-        // Spawn the event processing task
+        self.states.insert(game_name.clone(), file_watcher.states());
+
+        // Spawn the event processing task, which owns the watcher so it keeps
+        // running and can pick up new matches of glob save paths
         let game_name_clone = game_name.clone();
         let handle = tokio::spawn(async move {
-            if let Err(e) = process_file_events(event_rx, sync_handler, game_name_clone.clone()).await {
+            if let Err(e) = process_file_events(file_watcher, event_rx, sync_handler, game_name_clone.clone()).await {
                 error!("Error processing file events for {}: {}", game_name_clone, e);
             }
         });
@@ -48,6 +100,7 @@ impl WatcherManager {
     }
 
     pub async fn stop_watching_game(&mut self, game_name: &str) {
+        self.states.remove(game_name);
         if let Some(handle) = self.watchers.remove(game_name) {
             handle.abort();
             info!("Stopped watching game: {}", game_name);
@@ -55,6 +108,7 @@ impl WatcherManager {
     }
 
     pub async fn stop_all(&mut self) {
+        self.states.clear();
         for (game_name, handle) in self.watchers.drain() {
             handle.abort();
             info!("Stopped watching game: {}", game_name);
@@ -68,6 +122,13 @@ impl WatcherManager {
     pub fn watched_games(&self) -> Vec<String> {
         self.watchers.keys().cloned().collect()
     }
+
+    /// Watch state of each save path of a game, empty when it is not watched
+    pub fn watch_states(&self, game_name: &str) -> BTreeMap<String, WatchState> {
+        self.states.get(game_name)
+            .map(WatchStates::snapshot)
+            .unwrap_or_default()
+    }
 }
 
 impl Default for WatcherManager {
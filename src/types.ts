@@ -5,6 +5,7 @@ export interface Game {
   sync_enabled: boolean
   last_sync: string | null
   is_watching: boolean
+  watch_states?: Record<string, WatchState>
   platform?: 'steam' | 'epic' | 'gog' | 'manual'
   app_id?: string
   install_dir?: string
@@ -19,12 +20,56 @@ export interface SteamGame {
   library_path: string
   last_updated?: number
   size_on_disk?: number
+  state_flags?: number
+  last_owner?: string
+}
+
+export type ConflictResolution = 'keep_newest' | 'keep_local' | 'keep_remote' | 'keep_both'
+
+export type ConflictPolicy = ConflictResolution | 'ask'
+
+export type WatchState =
+  | { state: 'watching'; polling: boolean }
+  | { state: 'waiting'; ancestor: string }
+  | { state: 'failed'; error: string }
+
+export type WatchBackend = 'auto' | 'native' | 'polling'
+
+export interface DaemonGameStatus {
+  name: string
+  sync_enabled: boolean
+  watching: boolean
+  paused: boolean
+  watch_states?: Record<string, WatchState>
+}
+
+export interface DaemonStatus {
+  pid: number
+  started_at: string
+  games: DaemonGameStatus[]
 }
 
 export interface GameConfig {
   name: string
   save_paths: string[]
   sync_enabled: boolean
+  conflict_policy?: ConflictPolicy
+  include?: string[]
+  exclude?: string[]
+  max_file_size?: number
+  watch_backend?: WatchBackend
+  poll_interval_ms?: number
+  debounce_ms?: number
+  settle_polls?: number
+}
+
+export interface SyncConflict {
+  game_name: string
+  file_path: string
+  local_hash: string
+  remote_hash: string
+  base_hash?: string
+  detected_at: string
 }
 
 export interface SyncOperation {
@@ -41,8 +86,13 @@ export interface Config {
   aws_profile: string
   s3_bucket: string
   s3_region: string
+  s3_endpoint_url?: string
+  s3_force_path_style?: boolean
+  s3_key_prefix?: string
   aws_access_key_id?: string
   aws_secret_access_key?: string
+  encryption_passphrase?: string
+  storage_layout?: 'per_version' | 'content_addressed' | 'chunked'
   peer_sync_enabled: boolean
   websocket_url: string
   local_base_path: string
@@ -61,6 +111,7 @@ export interface FileVersion {
   timestamp: string
   size: number
   hash: string
+  content_hash?: string
   storage_metadata: Record<string, string>
   description?: string
   is_pinned: boolean
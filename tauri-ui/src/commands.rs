@@ -5,15 +5,21 @@ use decksaves_core::{
     VersionedGameSaveSync,
     GameConfig, 
     SyncConfig,
+    ConflictPolicy,
+    ConflictResolution,
+    SyncConflict,
+    SaveFilter,
+    WatchSettings,
     FileVersion,
-    watcher::WatcherManager,
-    steam::{SteamDetector, SteamGame},
+    ipc::{DaemonClient, DaemonStatus},
+    watcher::{WatchState, WatcherManager},
+    steam::{LudusaviManifest, SteamDetector, SteamGame},
 };
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::path::PathBuf;
 use uuid::Uuid;
-use tracing::{info, error, debug};
+use tracing::{info, error, debug, warn};
 use chrono::Utc;
 use chrono;
 
@@ -41,6 +47,12 @@ impl AppState {
     }
 }
 
+/// Client of the running daemon, if there is one. Watching is left to the
+/// daemon when it runs, so the UI does not start watchers of its own.
+async fn daemon_client() -> Option<DaemonClient> {
+    DaemonClient::connect_default().await.ok()
+}
+
 // Configuration commands
 #[command]
 pub async fn get_config(state: State<'_, AppState>) -> Result<SyncConfig, String> {
@@ -124,6 +136,70 @@ pub async fn get_aws_credentials(state: State<'_, AppState>) -> Result<(String,
     Ok((access_key, secret_key))
 }
 
+#[command]
+pub async fn set_encryption_passphrase(
+    passphrase: String,
+    state: State<'_, AppState>
+) -> Result<String, String> {
+    let mut config = state.config_manager.load_config().await.map_err(|e| e.to_string())?;
+
+    config.set_encryption_passphrase(&passphrase).map_err(|e| e.to_string())?;
+    state.config_manager.save_config(&config).await.map_err(|e| e.to_string())?;
+
+    if passphrase.is_empty() {
+        Ok("End-to-end encryption disabled".to_string())
+    } else {
+        Ok("Encryption passphrase saved securely".to_string())
+    }
+}
+
+// Conflict commands
+#[command]
+pub async fn get_conflicts(state: State<'_, AppState>) -> Result<Vec<SyncConflict>, String> {
+    if let Some(mut client) = daemon_client().await {
+        return client.conflicts().await.map_err(|e| e.to_string());
+    }
+
+    let config = state.config_manager.load_config().await.map_err(|e| e.to_string())?;
+    let sync_handler = GameSaveSync::new(config).await.map_err(|e| e.to_string())?;
+    Ok(sync_handler.conflicts())
+}
+
+#[command]
+pub async fn resolve_conflict(
+    game_name: String,
+    file_path: String,
+    resolution: ConflictResolution,
+    state: State<'_, AppState>
+) -> Result<String, String> {
+    if let Some(mut client) = daemon_client().await {
+        client.resolve_conflict(&game_name, &file_path, resolution).await.map_err(|e| e.to_string())?;
+        return Ok(format!("Conflict resolved for {}", file_path));
+    }
+
+    let config = state.config_manager.load_config().await.map_err(|e| e.to_string())?;
+    let sync_handler = GameSaveSync::new(config).await.map_err(|e| e.to_string())?;
+
+    sync_handler.resolve_conflict(&game_name, &file_path, resolution).await.map_err(|e| e.to_string())?;
+    Ok(format!("Conflict resolved for {}", file_path))
+}
+
+#[command]
+pub async fn set_conflict_policy(
+    game_name: String,
+    policy: ConflictPolicy,
+    state: State<'_, AppState>
+) -> Result<String, String> {
+    let mut config = state.config_manager.load_config().await.map_err(|e| e.to_string())?;
+
+    let game_config = config.games.get_mut(&game_name)
+        .ok_or_else(|| format!("Game '{}' not found", game_name))?;
+    game_config.conflict_policy = policy;
+    state.config_manager.save_config(&config).await.map_err(|e| e.to_string())?;
+
+    Ok(format!("Conflict policy for '{}' updated", game_name))
+}
+
 #[command]
 pub async fn get_config_path(state: State<'_, AppState>) -> Result<String, String> {
     Ok(state.config_manager.config_path().display().to_string())
@@ -144,15 +220,34 @@ pub struct GameWithStatus {
     pub sync_enabled: bool,
     pub last_sync: Option<String>,
     pub is_watching: bool,
+    /// Watch state of each save path while the game is watched
+    pub watch_states: std::collections::BTreeMap<String, WatchState>,
 }
 
 #[command]
 pub async fn get_games_with_status(state: State<'_, AppState>) -> Result<Vec<GameWithStatus>, String> {
     let config = state.config_manager.load_config().await.map_err(|e| e.to_string())?;
-    let watching_games = if let Ok(watcher) = state.watcher_manager.lock() {
-        watcher.watched_games()
+    let daemon_status = match daemon_client().await {
+        Some(mut client) => client.status().await.ok(),
+        None => None,
+    };
+    let (watching_games, watch_states) = if let Some(status) = daemon_status {
+        let watching_games = status.games.iter()
+            .filter(|game| game.watching)
+            .map(|game| game.name.clone())
+            .collect();
+        let watch_states: HashMap<_, _> = status.games.into_iter()
+            .map(|game| (game.name, game.watch_states))
+            .collect();
+        (watching_games, watch_states)
+    } else if let Ok(watcher) = state.watcher_manager.lock() {
+        let watching_games = watcher.watched_games();
+        let watch_states: HashMap<_, _> = watching_games.iter()
+            .map(|game_id| (game_id.clone(), watcher.watch_states(game_id)))
+            .collect();
+        (watching_games, watch_states)
     } else {
-        Vec::new()
+        (Vec::new(), HashMap::new())
     };
     
     let sync_history = if let Ok(history) = state.sync_history.lock() {
@@ -171,6 +266,7 @@ pub async fn get_games_with_status(state: State<'_, AppState>) -> Result<Vec<Gam
             sync_enabled: game_config.sync_enabled,
             last_sync: sync_history.get(game_id).cloned(),
             is_watching: watching_games.contains(game_id),
+            watch_states: watch_states.get(game_id).cloned().unwrap_or_default(),
         });
     }
     
@@ -200,6 +296,9 @@ pub async fn add_game(
         name: display_name.clone(),
         save_paths: paths.clone(),
         sync_enabled: enabled,
+        conflict_policy: ConflictPolicy::default(),
+        filter: SaveFilter::default(),
+        watch: WatchSettings::default(),
     };
     debug!("Game config created: {:?}", game_config);
     
@@ -280,6 +379,9 @@ pub async fn add_game_with_dialogs(
         name: display_name.clone(),
         save_paths: save_paths.clone(),
         sync_enabled: enabled,
+        conflict_policy: ConflictPolicy::default(),
+        filter: SaveFilter::default(),
+        watch: WatchSettings::default(),
     };
     
     config.games.insert(game_name.clone(), game_config);
@@ -333,6 +435,17 @@ pub async fn remove_game(name: String, state: State<'_, AppState>) -> Result<Str
 #[command]
 pub async fn sync_game(game_name: String, state: State<'_, AppState>) -> Result<String, String> {
     info!("sync_game command called for: {}", game_name);
+
+    if let Some(mut client) = daemon_client().await {
+        client.sync_now(Some(game_name.clone())).await.map_err(|e| {
+            error!("Daemon sync failed for {}: {}", game_name, e);
+            e.to_string()
+        })?;
+        if let Ok(mut history) = state.sync_history.lock() {
+            history.insert(game_name.clone(), chrono::Utc::now().to_rfc3339());
+        }
+        return Ok(format!("Successfully synced {}", game_name));
+    }
     
     let config = state.config_manager.load_config().await.map_err(|e| {
         error!("Failed to load config: {}", e);
@@ -389,6 +502,15 @@ pub async fn start_watching_game(
         return Err(format!("Sync is disabled for game '{}'", game_name));
     }
 
+    if let Some(mut client) = daemon_client().await {
+        client.resume(&game_name).await.map_err(|e| e.to_string())?;
+        app_handle.emit("game-watch-started", &game_name).unwrap_or_else(|e| {
+            error!("Failed to emit game-watch-started event: {}", e);
+        });
+        info!("Daemon resumed watching game: {}", game_name);
+        return Ok(format!("Started watching {}", game_name));
+    }
+
     let session_id = Uuid::new_v4().to_string();
     
     // Store session
@@ -414,6 +536,10 @@ pub async fn stop_watching_game(
     state: State<'_, AppState>,
     app_handle: AppHandle
 ) -> Result<String, String> {
+    if let Some(mut client) = daemon_client().await {
+        client.pause(&game_name).await.map_err(|e| e.to_string())?;
+    }
+
     // Remove session
     if let Ok(mut sessions) = state.sync_sessions.lock() {
         sessions.remove(&game_name);
@@ -433,6 +559,14 @@ pub async fn stop_watching_game(
 
 #[command]
 pub async fn get_watching_games(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    if let Some(mut client) = daemon_client().await {
+        let status = client.status().await.map_err(|e| e.to_string())?;
+        return Ok(status.games.into_iter()
+            .filter(|game| game.watching)
+            .map(|game| game.name)
+            .collect());
+    }
+
     if let Ok(watcher) = state.watcher_manager.lock() {
         Ok(watcher.watched_games())
     } else {
@@ -440,6 +574,23 @@ pub async fn get_watching_games(state: State<'_, AppState>) -> Result<Vec<String
     }
 }
 
+// Daemon commands
+#[command]
+pub async fn get_daemon_status() -> Result<Option<DaemonStatus>, String> {
+    match daemon_client().await {
+        Some(mut client) => client.status().await.map(Some).map_err(|e| e.to_string()),
+        None => Ok(None),
+    }
+}
+
+#[command]
+pub async fn reload_daemon() -> Result<String, String> {
+    let mut client = daemon_client().await
+        .ok_or_else(|| "The daemon is not running".to_string())?;
+    client.reload().await.map_err(|e| e.to_string())?;
+    Ok("Daemon configuration reloaded".to_string())
+}
+
 // File system commands
 #[command]
 pub async fn validate_path(path: String) -> Result<bool, String> {
@@ -514,9 +665,11 @@ pub async fn test_aws_connection(
     access_key_id: String, 
     secret_access_key: String, 
     region: String, 
-    bucket: String
+    bucket: String,
+    endpoint_url: Option<String>,
+    force_path_style: Option<bool>,
 ) -> Result<String, String> {
-    use aws_sdk_s3::{Client, config::Region};
+    use aws_sdk_s3::config::Region;
     use aws_config::BehaviorVersion;
     use aws_credential_types::Credentials;
     
@@ -536,7 +689,12 @@ pub async fn test_aws_connection(
         .load()
         .await;
     
-    let client = Client::new(&config);
+    let endpoint = decksaves_core::storage::S3Endpoint {
+        endpoint_url: endpoint_url.filter(|url| !url.is_empty()),
+        force_path_style: force_path_style.unwrap_or(false),
+        key_prefix: None,
+    };
+    let client = endpoint.build_client(&config, decksaves_core::storage::StorageConfig::default().idle_timeout());
     
     // Test connection by trying to list objects (with limit)
     match client.list_objects_v2()
@@ -893,24 +1051,22 @@ pub async fn test_steam_detection() -> Result<String, String> {
 pub async fn get_steam_save_suggestions(steam_game: SteamGame) -> Result<Vec<String>, String> {
     info!("Getting save path suggestions for game: {}", steam_game.name);
     
-    let detector = SteamDetector::new().map_err(|e| {
+    let mut detector = SteamDetector::new().map_err(|e| {
         error!("Failed to create Steam detector: {}", e);
         e.to_string()
     })?;
+
+    // Use the Ludusavi manifest as the save location database when one is installed
+    match LudusaviManifest::load_default() {
+        Ok(Some(manifest)) => detector = detector.with_manifest(manifest),
+        Ok(None) => debug!("No Ludusavi manifest installed, using built-in save patterns"),
+        Err(e) => warn!("Failed to load Ludusavi manifest: {}", e),
+    }
     
-    let save_paths = detector.get_common_save_paths(&steam_game);
-    
-    // Filter to existing paths
-    let existing_paths: Vec<String> = save_paths.into_iter()
-        .filter(|path| {
-            let path_buf = std::path::PathBuf::from(path);
-            let exists = path_buf.exists();
-            if exists {
-                debug!("Found existing save path: {}", path);
-            }
-            exists
-        })
-        .collect();
+    let existing_paths = detector.existing_save_paths(&steam_game);
+    for path in &existing_paths {
+        debug!("Found existing save path: {}", path);
+    }
     
     info!("Found {} existing save paths for {}", existing_paths.len(), steam_game.name);
     Ok(existing_paths)
@@ -933,6 +1089,9 @@ pub async fn add_steam_game_to_config(
         name: steam_game.name.clone(),
         save_paths: save_paths.clone(),
         sync_enabled: true,
+        conflict_policy: ConflictPolicy::default(),
+        filter: SaveFilter::default(),
+        watch: WatchSettings::default(),
     };
     
     // Use app_id as the key for Steam games
@@ -55,6 +55,7 @@ async fn main() {
             commands::set_aws_credentials,
             commands::set_aws_credentials_and_config,
             commands::get_aws_credentials,
+            commands::set_encryption_passphrase,
             commands::debug_credentials,
             commands::test_command,
             commands::get_config_path,
@@ -70,6 +71,8 @@ async fn main() {
             commands::start_watching_game,
             commands::stop_watching_game,
             commands::get_watching_games,
+            commands::get_daemon_status,
+            commands::reload_daemon,
             commands::validate_path,
             commands::install_service,
             commands::get_system_info,
@@ -86,6 +89,10 @@ async fn main() {
             commands::cleanup_old_versions,
             commands::delete_version,
             commands::test_s3_connection,
+            // Conflict commands
+            commands::get_conflicts,
+            commands::resolve_conflict,
+            commands::set_conflict_policy,
         ])
         .setup(|app| {
             // Setup tray icon